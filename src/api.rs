@@ -0,0 +1,2147 @@
+use crate::bdk_utils::{get_fee_rate, get_tx, sync_wallet_and_utxos, ElectrumConfig};
+use crate::bitcoind_client::BitcoindClient;
+use crate::broadcast_tx;
+use crate::cli::{connect_peer_if_necessary, parse_peer_info};
+use crate::disk;
+use crate::error::Error;
+use crate::hex_utils;
+use crate::rgb_utils::find_witness_vout;
+use crate::rgb_utils::get_asset_owned_values;
+use crate::rgb_utils::get_received_amount;
+use crate::rgb_utils::get_rgb_total_amount;
+use crate::rgb_utils::RgbUtilities;
+use crate::rgb_utils::{get_rgb_network, is_channel_rgb};
+use crate::seal::Revealed;
+use crate::transport::{proxy_endpoint, ConsignmentTransport};
+use crate::utxo_store::{RgbUtxoStatus, RgbUtxoStore};
+use crate::{
+	ChainMonitor, ChannelManager, HTLCStatus, MillisatAmount, NetworkGraph, OnionMessenger,
+	PaymentInfo, PaymentInfoStorage, PeerManager,
+};
+use amplify::s;
+use bdk::bitcoin::hashes::Hash;
+use bdk::bitcoin::{Address, OutPoint, Script};
+use bdk::database::SqliteDatabase;
+use bdk::{SignOptions, Wallet};
+use bitcoin::hashes::sha256::Hash as Sha256;
+use bitcoin::network::constants::Network;
+use bitcoin::secp256k1::PublicKey;
+use bp::seals::txout::ExplicitSeal;
+use bp::seals::txout::{blind::ConcealedSeal, CloseMethod};
+use invoice::ConsignmentEndpoint;
+use lightning::chain::chaininterface::ConfirmationTarget;
+use lightning::chain::keysinterface::{EntropySource, KeysManager};
+use lightning::ln::channelmanager::{PaymentId, RecipientOnionFields, Retry};
+use lightning::ln::{PaymentHash, PaymentPreimage};
+use lightning::onion_message::{CustomOnionMessageContents, Destination, OnionMessageContents};
+use lightning::rgb_utils::write_rgb_payment_info_file;
+use lightning::rgb_utils::{get_rgb_channel_info, write_rgb_channel_info, RgbInfo};
+use lightning::routing::gossip::NodeId;
+use lightning::routing::router::{PaymentParameters, RouteParameters};
+use lightning::util::config::{ChannelHandshakeConfig, ChannelHandshakeLimits, UserConfig};
+use lightning::util::ser::{Writeable, Writer};
+use lightning::{log_error, log_info};
+use lightning_invoice::payment::pay_invoice;
+use lightning_invoice::{utils, Currency, Invoice};
+use reqwest::Client as RestClient;
+use rgb::fungible::allocation::AllocatedValue;
+use rgb::Consignment;
+use rgb::Contract;
+use rgb::ContractId;
+use rgb::EndpointValueMap;
+use rgb::SealEndpoint;
+use rgb::{seal, StateTransfer};
+use rgb20::Asset as Rgb20Asset;
+use rgb_rpc::Client;
+use rgb_rpc::ContractValidity;
+use rgb_rpc::Reveal;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::convert::{TryFrom, TryInto};
+use std::fs;
+use std::iter::FromIterator;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use stens::AsciiString;
+use strict_encoding::strict_deserialize;
+use strict_encoding::strict_serialize;
+use strict_encoding::StrictEncode;
+use tokio::sync::broadcast;
+
+const OPENCHANNEL_MIN_SAT: u64 = 5000;
+const OPENCHANNEL_MAX_SAT: u64 = 16777215;
+
+const DUST_LIMIT_MSAT: u64 = 546000;
+
+const HTLC_MIN_MSAT: u64 = 3000000;
+
+const INVOICE_MIN_MSAT: u64 = HTLC_MIN_MSAT;
+
+const WITNESS_RECIPIENT_PREFIX: &str = "wvout:";
+
+const RECEIVE_EXPIRY_SECS: u64 = 86400;
+
+// how long sendasset waits for the recipients to accept the transfer, checking every few seconds
+const ACK_POLL_SECS: u64 = 5;
+
+// handed to the LDK fork for Bitcoin-only channels when there's no endpoint, it's only used to
+// fetch the consignments of RGB channels
+const UNUSED_CONSIGNMENT_ENDPOINT: &str = "rgbhttpjsonrpc:http://127.0.0.1/json-rpc";
+
+/// Handles to the running node, shared by the CLI and the RPC server
+pub(crate) struct NodeContext {
+	pub(crate) peer_manager: Arc<PeerManager>,
+	pub(crate) channel_manager: Arc<ChannelManager>,
+	pub(crate) chain_monitor: Arc<ChainMonitor>,
+	pub(crate) keys_manager: Arc<KeysManager>,
+	pub(crate) network_graph: Arc<NetworkGraph>,
+	pub(crate) onion_messenger: Arc<OnionMessenger>,
+	pub(crate) inbound_payments: PaymentInfoStorage,
+	pub(crate) outbound_payments: PaymentInfoStorage,
+	pub(crate) ldk_data_dir: String,
+	pub(crate) network: Network,
+	pub(crate) logger: Arc<disk::FilesystemLogger>,
+	pub(crate) bitcoind_client: Arc<BitcoindClient>,
+	pub(crate) rgb_node_client: Arc<Mutex<Client>>,
+	pub(crate) proxy_client: Arc<RestClient>,
+	pub(crate) proxy_url: Option<String>,
+	pub(crate) transport: Arc<dyn ConsignmentTransport>,
+	pub(crate) wallet: Arc<Mutex<Wallet<SqliteDatabase>>>,
+	pub(crate) electrum_config: ElectrumConfig,
+	pub(crate) pending_channels: PendingChannelStorage,
+	pub(crate) rgb_utxos: Arc<RgbUtxoStore>,
+	pub(crate) fee_rate: Option<f32>,
+	pub(crate) utxo_size_sat: u64,
+	pub(crate) utxo_num: u8,
+	pub(crate) min_uncolored_utxos: Option<u8>,
+	/// Seconds `sendasset` waits for the recipients to acknowledge the transfer
+	pub(crate) ack_timeout: u64,
+	/// Serializes refreshes, which can be started by the user and by the background task
+	pub(crate) refresh_lock: tokio::sync::Mutex<()>,
+	/// Transfers handled by refreshes, for the subscribers (e.g. the RPC server)
+	pub(crate) transfer_events: broadcast::Sender<TransferEvent>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BlindedInfo {
+	contract_id: Option<ContractId>,
+	/// Without a txid when the asset is received on a new output of the transfer transaction
+	seal: seal::Revealed,
+	consumed: bool,
+	/// Address of the new output, for witness receives
+	#[serde(default)]
+	witness_address: Option<String>,
+	/// Minimum amount expected with the pinned contract
+	#[serde(default)]
+	amount: Option<u64>,
+	/// Unix time after which the request is dropped if no transfer has been sent to it
+	#[serde(default)]
+	expires_at: Option<u64>,
+	/// Why the transfer sent to the request has been refused, it's no longer checked if set
+	#[serde(default)]
+	rejected: Option<String>,
+	/// Whether the transfer has been accepted, the sender then broadcasts its transaction
+	#[serde(default)]
+	acked: bool,
+	/// Unix time of the ACK, the request is dropped if the sender doesn't broadcast in time
+	#[serde(default)]
+	acked_at: Option<u64>,
+}
+
+impl BlindedInfo {
+	fn is_expired(&self) -> bool {
+		self.expires_at.map_or(false, |expires_at| now_secs() >= expires_at)
+	}
+
+	/// Whether a transfer can still be accepted by the request
+	fn is_open(&self) -> bool {
+		!self.consumed && self.rejected.is_none()
+	}
+
+	fn save(&self, blinded_path: &Path) {
+		let serialized_info = serde_json::to_string(self).expect("valid rgb info");
+		fs::write(blinded_path, serialized_info).expect("successful file write");
+	}
+
+	/// Script of the address a witness receive request is paid to
+	fn witness_script(&self) -> Option<Script> {
+		let address = self.witness_address.as_ref()?;
+		Some(Address::from_str(address).expect("valid address").script_pubkey())
+	}
+
+	/// The ID under which the sender posts the consignment
+	fn recipient(&self) -> String {
+		match &self.witness_address {
+			Some(address) => format!("{WITNESS_RECIPIENT_PREFIX}{address}:{}", self.seal.blinding),
+			None => self.seal.to_concealed_seal().to_string(),
+		}
+	}
+}
+
+/// Where a recipient wants to receive an asset
+enum RecipientSeal {
+	/// A UTXO the recipient already owns, concealed
+	Blinded(ConcealedSeal),
+	/// A new output to the recipient's address, created by the transfer transaction
+	Witness { address: Address, blinding: u64 },
+}
+
+fn parse_recipient(recipient: &str, network: Network) -> Result<RecipientSeal, Error> {
+	match recipient.strip_prefix(WITNESS_RECIPIENT_PREFIX) {
+		Some(witness) => {
+			let (address, blinding) =
+				witness.split_once(':').ok_or(Error::InvalidWitnessRecipient)?;
+			let address = Address::from_str(address)
+				.ok()
+				.filter(|a| a.is_valid_for_network(network))
+				.ok_or(Error::InvalidWitnessRecipient)?;
+			let blinding = blinding.parse().map_err(|_| Error::InvalidWitnessRecipient)?;
+			Ok(RecipientSeal::Witness { address, blinding })
+		}
+		None => ConcealedSeal::from_str(recipient)
+			.map(RecipientSeal::Blinded)
+			.map_err(|_| Error::InvalidBlindedUtxo),
+	}
+}
+
+struct UserOnionMessageContents {
+	tlv_type: u64,
+	data: Vec<u8>,
+}
+
+impl CustomOnionMessageContents for UserOnionMessageContents {
+	fn tlv_type(&self) -> u64 {
+		self.tlv_type
+	}
+}
+
+impl Writeable for UserOnionMessageContents {
+	fn write<W: Writer>(&self, w: &mut W) -> Result<(), std::io::Error> {
+		w.write_all(&self.data)
+	}
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct EmptyResponse {}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct MineRequest {
+	pub(crate) num_blocks: u16,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct Unspent {
+	pub(crate) outpoint: String,
+	pub(crate) amount_sat: u64,
+	pub(crate) is_spent: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct ListUnspentResponse {
+	pub(crate) unspents: Vec<Unspent>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct RgbUtxo {
+	pub(crate) outpoint: String,
+	pub(crate) status: RgbUtxoStatus,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct ListRgbUtxosResponse {
+	pub(crate) rgb_utxos: Vec<RgbUtxo>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct CheckUtxosResponse {
+	/// UTXOs no longer tracked, as they were spent or their transaction was dropped
+	pub(crate) removed: Vec<String>,
+	/// Colored UTXOs whose RGB allocations were lost to a non-RGB spend
+	pub(crate) lost: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct GetAddressResponse {
+	pub(crate) address: String,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+pub(crate) struct CreateUtxosRequest {
+	pub(crate) num: Option<u8>,
+	pub(crate) size_sat: Option<u64>,
+	/// Only create the UTXOs needed to have `num` uncolored ones
+	#[serde(default)]
+	pub(crate) up_to: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct CreateUtxosResponse {
+	pub(crate) txid: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct IssueAssetRequest {
+	pub(crate) amount: u64,
+	pub(crate) ticker: String,
+	pub(crate) name: String,
+	pub(crate) precision: u8,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct IssueAssetResponse {
+	pub(crate) contract_id: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct AssetBalanceRequest {
+	pub(crate) contract_id: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct AssetBalanceResponse {
+	/// Amount held by the on-chain wallet, i.e. `settled` + `unconfirmed`
+	pub(crate) amount: u64,
+	#[serde(flatten)]
+	pub(crate) balance: AssetBalances,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+pub(crate) struct AssetBalances {
+	/// Allocations on confirmed wallet UTXOs
+	pub(crate) settled: u64,
+	/// Allocations on wallet UTXOs whose transaction is not confirmed yet
+	pub(crate) unconfirmed: u64,
+	/// Amount requested by the open receive requests pinned to the asset and an amount
+	pub(crate) pending_receives: u64,
+	/// Open receive requests that may bring the asset but don't pin an amount, or an asset
+	pub(crate) unpinned_receives: usize,
+	/// Local amount of the open RGB channels for the asset
+	pub(crate) in_channels: u64,
+	/// Local amount of closed RGB channels whose outputs have not been swept yet
+	pub(crate) claimable_after_close: u64,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct AssetInfo {
+	pub(crate) contract_id: String,
+	pub(crate) ticker: String,
+	pub(crate) name: String,
+	pub(crate) precision: u8,
+	pub(crate) issued_supply: u64,
+	pub(crate) balance: AssetBalances,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct ListAssetsResponse {
+	pub(crate) assets: Vec<AssetInfo>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct AssetInfoRequest {
+	pub(crate) contract_id: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct SendAssetRequest {
+	pub(crate) contract_id: String,
+	pub(crate) recipients: Vec<AssetRecipient>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct AssetRecipient {
+	/// A blinded UTXO or a `wvout:<address>:<blinding>` witness recipient
+	#[serde(alias = "blinded_utxo")]
+	pub(crate) recipient: String,
+	pub(crate) amount: u64,
+	/// The recipient's node, which the peer consignment transport sends the consignment to
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub(crate) node_id: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct SendAssetResponse {
+	pub(crate) txid: String,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+pub(crate) struct ReceiveAssetRequest {
+	/// Receive on a new output of the transfer transaction instead of on a blinded UTXO
+	#[serde(default)]
+	pub(crate) witness: bool,
+	pub(crate) contract_id: Option<String>,
+	/// Minimum amount to receive, requires `contract_id`
+	pub(crate) amount: Option<u64>,
+	/// Defaults to a day, 0 means the request never expires
+	pub(crate) expiry_secs: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct ReceiveAssetResponse {
+	/// The blinded UTXO or witness recipient to give to the sender
+	pub(crate) recipient: String,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub(crate) struct ReceivedTransfer {
+	pub(crate) recipient: String,
+	pub(crate) contract_id: String,
+	pub(crate) ticker: String,
+	pub(crate) amount: u64,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub(crate) struct RejectedTransfer {
+	pub(crate) recipient: String,
+	pub(crate) reason: String,
+}
+
+/// An incoming transfer completed or rejected by `refresh`
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub(crate) enum TransferEvent {
+	Received(ReceivedTransfer),
+	Rejected(RejectedTransfer),
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct TransferEventsResponse {
+	pub(crate) events: Vec<TransferEvent>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct RefreshResponse {
+	pub(crate) received: Vec<ReceivedTransfer>,
+	/// Recipients of the transfers acknowledged to their sender, received once it broadcasts them
+	pub(crate) accepted: Vec<String>,
+	/// Invalid transfers and the ones not matching the contract or amount pinned by their request
+	pub(crate) rejected: Vec<RejectedTransfer>,
+	/// Set when consignments couldn't be fetched, the remaining requests are left for the next
+	/// refresh
+	pub(crate) transport_error: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct Receive {
+	pub(crate) recipient: String,
+	pub(crate) contract_id: Option<String>,
+	pub(crate) amount: Option<u64>,
+	pub(crate) expires_at: Option<u64>,
+	pub(crate) expired: bool,
+	/// Set when the transfer sent to the request has been refused
+	pub(crate) rejected: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct ListReceivesResponse {
+	pub(crate) receives: Vec<Receive>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct CancelReceiveRequest {
+	pub(crate) recipient: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct OpenChannelRequest {
+	pub(crate) peer_pubkey_and_addr: String,
+	pub(crate) capacity_sat: u64,
+	pub(crate) push_msat: u64,
+	pub(crate) contract_id: Option<String>,
+	pub(crate) asset_amount: Option<u64>,
+	#[serde(default)]
+	pub(crate) public: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct OpenChannelResponse {
+	pub(crate) temporary_channel_id: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct SendPaymentRequest {
+	pub(crate) invoice: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct KeysendRequest {
+	pub(crate) dest_pubkey: String,
+	pub(crate) amt_msat: u64,
+	pub(crate) contract_id: Option<String>,
+	pub(crate) amt_rgb: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct PaymentResponse {
+	pub(crate) payment_hash: String,
+	pub(crate) amt_msat: u64,
+	pub(crate) payee_pubkey: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct GetInvoiceRequest {
+	pub(crate) amt_msat: u64,
+	pub(crate) expiry_secs: u32,
+	pub(crate) contract_id: Option<String>,
+	pub(crate) amt_rgb: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct GetInvoiceResponse {
+	pub(crate) invoice: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct InvoiceStatusRequest {
+	pub(crate) invoice: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct InvoiceStatusResponse {
+	pub(crate) status: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct ConnectPeerRequest {
+	pub(crate) peer_pubkey_and_addr: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct DisconnectPeerRequest {
+	pub(crate) peer_pubkey: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct Peer {
+	pub(crate) pubkey: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct ListPeersResponse {
+	pub(crate) peers: Vec<Peer>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct Channel {
+	pub(crate) channel_id: String,
+	pub(crate) funding_txid: Option<String>,
+	pub(crate) peer_pubkey: String,
+	pub(crate) peer_alias: Option<String>,
+	pub(crate) short_channel_id: Option<u64>,
+	pub(crate) is_channel_ready: bool,
+	pub(crate) channel_value_satoshis: u64,
+	pub(crate) local_balance_msat: u64,
+	pub(crate) available_balance_for_send_msat: Option<u64>,
+	pub(crate) available_balance_for_recv_msat: Option<u64>,
+	pub(crate) channel_can_send_payments: bool,
+	pub(crate) public: bool,
+	pub(crate) rgb_contract_id: Option<String>,
+	pub(crate) rgb_local_amount: Option<u64>,
+	pub(crate) rgb_remote_amount: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct ListChannelsResponse {
+	pub(crate) channels: Vec<Channel>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct ChannelIdRequest {
+	pub(crate) channel_id: String,
+	pub(crate) peer_pubkey: String,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+pub(crate) struct PendingChannel {
+	pub(crate) temporary_channel_id: String,
+	pub(crate) peer_pubkey: String,
+	pub(crate) funding_satoshis: u64,
+	pub(crate) push_msat: u64,
+	pub(crate) rgb_contract_id: Option<String>,
+	pub(crate) rgb_amount: Option<u64>,
+}
+
+/// Inbound channel requests awaiting `acceptchannel`/`rejectchannel`
+pub(crate) type PendingChannelStorage = Arc<Mutex<HashMap<[u8; 32], PendingChannel>>>;
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct ListPendingChannelsResponse {
+	pub(crate) pending_channels: Vec<PendingChannel>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct PendingChannelRequest {
+	pub(crate) temporary_channel_id: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct Payment {
+	pub(crate) amount_msat: Option<u64>,
+	pub(crate) payment_hash: String,
+	pub(crate) htlc_direction: String,
+	pub(crate) htlc_status: String,
+	pub(crate) rgb_contract_id: Option<String>,
+	pub(crate) rgb_amount: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct ListPaymentsResponse {
+	pub(crate) payments: Vec<Payment>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct NodeInfoResponse {
+	pub(crate) node_pubkey: String,
+	pub(crate) num_channels: usize,
+	pub(crate) num_usable_channels: usize,
+	pub(crate) local_balance_msat: u64,
+	pub(crate) num_peers: usize,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct SignMessageRequest {
+	pub(crate) message: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct SignMessageResponse {
+	pub(crate) signed_message: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct SendOnionMessageRequest {
+	pub(crate) node_ids: Vec<String>,
+	pub(crate) tlv_type: u64,
+	pub(crate) data: String,
+}
+
+fn parse_contract_id(contract_id: &str) -> Result<ContractId, Error> {
+	ContractId::from_str(contract_id).map_err(|_| Error::InvalidContractId)
+}
+
+/// Parses the optional RGB part of a request, which must be either fully present or absent
+fn parse_rgb_params(
+	contract_id: Option<&str>, amount: Option<u64>,
+) -> Result<Option<(ContractId, u64)>, Error> {
+	match (contract_id, amount) {
+		(Some(contract_id), Some(amount)) => Ok(Some((parse_contract_id(contract_id)?, amount))),
+		(None, None) => Ok(None),
+		_ => Err(Error::InvalidArgument(
+			"RGB contract ID and amount must be provided together".to_string(),
+		)),
+	}
+}
+
+fn parse_pubkey(pubkey: &str) -> Result<PublicKey, Error> {
+	hex_utils::to_compressed_pubkey(pubkey).ok_or(Error::InvalidPubkey)
+}
+
+fn parse_channel_id(channel_id: &str) -> Result<[u8; 32], Error> {
+	let channel_id_vec = hex_utils::to_vec(channel_id);
+	if channel_id_vec.is_none() || channel_id_vec.as_ref().unwrap().len() != 32 {
+		return Err(Error::InvalidChannelId);
+	}
+	let mut channel_id = [0; 32];
+	channel_id.copy_from_slice(&channel_id_vec.unwrap());
+	Ok(channel_id)
+}
+
+fn parse_invoice(invoice: &str) -> Result<Invoice, Error> {
+	Invoice::from_str(invoice).map_err(|e| Error::InvalidInvoice(format!("{:?}", e)))
+}
+
+pub(crate) async fn mine(ctx: &NodeContext, req: MineRequest) -> Result<EmptyResponse, Error> {
+	if ctx.network != Network::Regtest {
+		return Err(Error::RegtestOnly);
+	}
+	let address = ctx.bitcoind_client.get_new_address().await.to_string();
+	ctx.bitcoind_client.generate_to_adress(req.num_blocks, address).await;
+	Ok(EmptyResponse {})
+}
+
+pub(crate) fn list_unspent(ctx: &NodeContext) -> Result<ListUnspentResponse, Error> {
+	let wallet = ctx.wallet.lock().unwrap();
+	let unspents = wallet
+		.list_unspent()
+		.expect("unspents")
+		.into_iter()
+		.map(|u| Unspent {
+			outpoint: u.outpoint.to_string(),
+			amount_sat: u.txout.value,
+			is_spent: u.is_spent,
+		})
+		.collect();
+	Ok(ListUnspentResponse { unspents })
+}
+
+pub(crate) fn list_rgb_utxos(ctx: &NodeContext) -> Result<ListRgbUtxosResponse, Error> {
+	let rgb_utxos = ctx
+		.rgb_utxos
+		.list()
+		.into_iter()
+		.map(|(outpoint, status)| RgbUtxo { outpoint: outpoint.to_string(), status })
+		.collect();
+	Ok(ListRgbUtxosResponse { rgb_utxos })
+}
+
+pub(crate) fn check_utxos(ctx: &NodeContext) -> Result<CheckUtxosResponse, Error> {
+	let wallet = ctx.wallet.lock().unwrap();
+	let reconciliation =
+		sync_wallet_and_utxos(&wallet, ctx.electrum_config.clone(), &ctx.rgb_utxos);
+	Ok(CheckUtxosResponse {
+		removed: reconciliation.removed.iter().map(|o| o.to_string()).collect(),
+		lost: reconciliation.lost.iter().map(|o| o.to_string()).collect(),
+	})
+}
+
+pub(crate) fn get_address(ctx: &NodeContext) -> Result<GetAddressResponse, Error> {
+	let wallet = ctx.wallet.lock().unwrap();
+	let address =
+		wallet.get_address(bdk::wallet::AddressIndex::New).expect("valid address").address;
+	Ok(GetAddressResponse { address: address.to_string() })
+}
+
+pub(crate) fn create_utxos(
+	ctx: &NodeContext, req: CreateUtxosRequest,
+) -> Result<CreateUtxosResponse, Error> {
+	let mut num = req.num.unwrap_or(ctx.utxo_num);
+	let size_sat = req.size_sat.unwrap_or(ctx.utxo_size_sat);
+	if num == 0 {
+		return Err(Error::InvalidArgument("the number of UTXOs must be positive".to_string()));
+	}
+	let too_big = || Error::InvalidAmount(s!("UTXO size is too big"));
+	if size_sat.checked_mul(1000).ok_or_else(too_big)? < DUST_LIMIT_MSAT {
+		return Err(Error::InvalidAmount(format!(
+			"UTXO size must be at least {} sats",
+			DUST_LIMIT_MSAT / 1000
+		)));
+	}
+	if req.up_to {
+		let num_uncolored = ctx.rgb_utxos.num_uncolored();
+		if num_uncolored >= num as usize {
+			return Err(Error::UtxosAlreadyAvailable(num_uncolored));
+		}
+		num -= num_uncolored as u8;
+	}
+
+	let wallet = ctx.wallet.lock().unwrap();
+	sync_wallet_and_utxos(&wallet, ctx.electrum_config.clone(), &ctx.rgb_utxos);
+
+	let unspendable_utxos = ctx.rgb_utxos.unspendable(&wallet);
+
+	let unspendable_amt: u64 = wallet
+		.list_unspent()
+		.expect("unspents")
+		.iter()
+		.filter(|u| unspendable_utxos.contains(&u.outpoint))
+		.map(|u| u.txout.value)
+		.sum();
+	let available = wallet.get_balance().expect("wallet balance").get_total() - unspendable_amt;
+	let needed = size_sat.checked_mul(num as u64).ok_or_else(too_big)?;
+	if available < needed {
+		return Err(Error::InsufficientFunds(needed - available));
+	}
+
+	let mut tx_builder = wallet.build_tx();
+	tx_builder
+		.unspendable(unspendable_utxos)
+		.fee_rate(get_fee_rate(
+			&ctx.bitcoind_client,
+			ctx.network,
+			ctx.fee_rate,
+			ConfirmationTarget::Normal,
+		))
+		.ordering(bdk::wallet::tx_builder::TxOrdering::Untouched);
+	for _i in 0..num {
+		tx_builder.add_recipient(
+			wallet.get_address(bdk::wallet::AddressIndex::New).expect("address").script_pubkey(),
+			size_sat,
+		);
+	}
+	// the fee is not included in the check above
+	let (mut psbt, _details) = tx_builder.finish().map_err(tx_creation_error)?;
+
+	wallet.sign(&mut psbt, SignOptions::default()).expect("successful sign");
+
+	let tx = psbt.extract_tx();
+	broadcast_tx(&tx, ctx.electrum_config.clone())?;
+
+	for i in 0..num {
+		ctx.rgb_utxos.add(OutPoint { txid: tx.txid(), vout: i as u32 }, false);
+	}
+
+	sync_wallet_and_utxos(&wallet, ctx.electrum_config.clone(), &ctx.rgb_utxos);
+	Ok(CreateUtxosResponse { txid: tx.txid().to_string() })
+}
+
+/// Error of a wallet transaction that couldn't be built, telling how many satoshis are missing
+/// when that's the cause
+fn tx_creation_error(e: bdk::Error) -> Error {
+	match e {
+		bdk::Error::InsufficientFunds { needed, available } => {
+			Error::InsufficientFunds(needed - available)
+		}
+		e => Error::FailedTxCreation(e.to_string()),
+	}
+}
+
+/// Creates UTXOs when fewer than `min_uncolored_utxos` uncolored ones are available
+pub(crate) fn top_up_utxos(ctx: &NodeContext) -> Result<(), Error> {
+	let min_uncolored_utxos = match ctx.min_uncolored_utxos {
+		Some(min) => min,
+		None => return Ok(()),
+	};
+	if ctx.rgb_utxos.num_uncolored() >= min_uncolored_utxos as usize {
+		return Ok(());
+	}
+	let req = CreateUtxosRequest {
+		num: Some(std::cmp::max(min_uncolored_utxos, ctx.utxo_num)),
+		size_sat: None,
+		up_to: true,
+	};
+	create_utxos(ctx, req)?;
+	Ok(())
+}
+
+/// Reserves an uncolored UTXO, topping up the pool first if it's empty and top-up is enabled
+fn reserve_utxo(ctx: &NodeContext) -> Result<OutPoint, Error> {
+	match ctx.rgb_utxos.reserve() {
+		Err(Error::NoAvailableUtxos) if ctx.min_uncolored_utxos.is_some() => {
+			top_up_utxos(ctx)?;
+			ctx.rgb_utxos.reserve()
+		}
+		res => res,
+	}
+}
+
+pub(crate) fn issue_asset(
+	ctx: &NodeContext, req: IssueAssetRequest,
+) -> Result<IssueAssetResponse, Error> {
+	let ticker = AsciiString::from_str(&req.ticker).map_err(|_| Error::InvalidTicker)?;
+	let name = AsciiString::from_str(&req.name).map_err(|_| Error::InvalidName)?;
+
+	let outpoint = reserve_utxo(ctx)?;
+	let contract_id = ctx.rgb_node_client.lock().unwrap().issue_contract(
+		get_rgb_network(ctx.network),
+		req.amount,
+		outpoint,
+		ticker,
+		name,
+		req.precision,
+	);
+	ctx.rgb_utxos.commit(outpoint)?;
+	Ok(IssueAssetResponse { contract_id: contract_id.to_string() })
+}
+
+pub(crate) fn asset_balance(
+	ctx: &NodeContext, req: AssetBalanceRequest,
+) -> Result<AssetBalanceResponse, Error> {
+	let contract_id = parse_contract_id(&req.contract_id)?;
+	let unspents = get_wallet_unspents(ctx);
+	let channel_balances = get_channel_rgb_balances(ctx);
+	let mut rgb_client = ctx.rgb_node_client.lock().unwrap();
+	let balance =
+		get_asset_balances(ctx, &mut rgb_client, contract_id, &unspents, channel_balances)?;
+	Ok(AssetBalanceResponse { amount: balance.settled + balance.unconfirmed, balance })
+}
+
+pub(crate) fn list_assets(ctx: &NodeContext) -> Result<ListAssetsResponse, Error> {
+	let unspents = get_wallet_unspents(ctx);
+	let mut channel_balances = get_channel_rgb_balances(ctx);
+	let mut rgb_client = ctx.rgb_node_client.lock().unwrap();
+	let contract_ids = rgb_client.list_contracts().expect("successful list_contracts call");
+	let mut assets = vec![];
+	for contract_id in contract_ids {
+		let channel_balances = channel_balances.remove(&contract_id).unwrap_or_default();
+		assets.push(get_asset_info(
+			ctx,
+			&mut rgb_client,
+			contract_id,
+			&unspents,
+			channel_balances,
+		)?);
+	}
+	Ok(ListAssetsResponse { assets })
+}
+
+pub(crate) fn asset_info(ctx: &NodeContext, req: AssetInfoRequest) -> Result<AssetInfo, Error> {
+	let contract_id = parse_contract_id(&req.contract_id)?;
+	let unspents = get_wallet_unspents(ctx);
+	let channel_balances = get_channel_rgb_balances(ctx).remove(&contract_id).unwrap_or_default();
+	let mut rgb_client = ctx.rgb_node_client.lock().unwrap();
+	get_asset_info(ctx, &mut rgb_client, contract_id, &unspents, channel_balances)
+}
+
+/// The wallet UTXOs after a sync, each with whether its transaction is confirmed
+fn get_wallet_unspents(ctx: &NodeContext) -> HashMap<OutPoint, bool> {
+	let wallet = ctx.wallet.lock().unwrap();
+	sync_wallet_and_utxos(&wallet, ctx.electrum_config.clone(), &ctx.rgb_utxos);
+	wallet
+		.list_unspent()
+		.expect("valid unspent list")
+		.into_iter()
+		.map(|u| {
+			let tx_details = wallet.get_tx(&u.outpoint.txid, false).expect("valid wallet db");
+			(u.outpoint, tx_details.and_then(|d| d.confirmation_time).is_some())
+		})
+		.collect()
+}
+
+/// The local RGB amount of open channels and of closed channels still holding claimable
+/// outputs, summed by contract
+fn get_channel_rgb_balances(ctx: &NodeContext) -> HashMap<ContractId, AssetBalances> {
+	let ldk_data_dir_path = PathBuf::from(&ctx.ldk_data_dir);
+	let mut balances: HashMap<ContractId, AssetBalances> = HashMap::new();
+	let mut open_channel_ids = HashSet::new();
+	for chan_info in ctx.channel_manager.list_channels() {
+		open_channel_ids.insert(chan_info.channel_id);
+		if !is_channel_rgb(&chan_info.channel_id, &ldk_data_dir_path) {
+			continue;
+		}
+		let (rgb_info, _) = get_rgb_channel_info(&chan_info.channel_id, &ldk_data_dir_path);
+		balances.entry(rgb_info.contract_id).or_default().in_channels += rgb_info.local_rgb_amount;
+	}
+	for funding_txo in ctx.chain_monitor.list_monitors() {
+		let channel_id = funding_txo.to_channel_id();
+		if open_channel_ids.contains(&channel_id)
+			|| !is_channel_rgb(&channel_id, &ldk_data_dir_path)
+		{
+			continue;
+		}
+		let monitor = ctx.chain_monitor.get_monitor(funding_txo).expect("listed monitor");
+		if monitor.get_claimable_balances().is_empty() {
+			continue;
+		}
+		let (rgb_info, _) = get_rgb_channel_info(&channel_id, &ldk_data_dir_path);
+		balances.entry(rgb_info.contract_id).or_default().claimable_after_close +=
+			rgb_info.local_rgb_amount;
+	}
+	balances
+}
+
+/// Pending amount of the open receive requests for `contract_id` and number of the open ones
+/// that may bring it without a pinned amount
+fn get_pending_receives(ctx: &NodeContext, contract_id: ContractId) -> (u64, usize) {
+	let mut pending_amount = 0;
+	let mut unpinned = 0;
+	for (_, blinded_info) in list_blinded_infos(ctx) {
+		if !blinded_info.is_open() || blinded_info.is_expired() {
+			continue;
+		}
+		match (blinded_info.contract_id, blinded_info.amount) {
+			(Some(cid), Some(amount)) if cid == contract_id => pending_amount += amount,
+			(Some(cid), None) if cid == contract_id => unpinned += 1,
+			(None, _) => unpinned += 1,
+			_ => {}
+		}
+	}
+	(pending_amount, unpinned)
+}
+
+/// Completes the channel balances of an asset with its on-chain ones
+fn get_asset_balances(
+	ctx: &NodeContext, rgb_client: &mut Client, contract_id: ContractId,
+	unspents: &HashMap<OutPoint, bool>, mut balance: AssetBalances,
+) -> Result<AssetBalances, Error> {
+	let contract_state =
+		rgb_client.contract_state(contract_id).map_err(|_| Error::UnknownContractId)?;
+	for ov in contract_state.owned_values {
+		match unspents.get(&OutPoint { txid: ov.seal.txid, vout: ov.seal.vout }) {
+			Some(true) => balance.settled += ov.state.value,
+			Some(false) => balance.unconfirmed += ov.state.value,
+			None => {}
+		}
+	}
+	let (pending_receives, unpinned_receives) = get_pending_receives(ctx, contract_id);
+	balance.pending_receives = pending_receives;
+	balance.unpinned_receives = unpinned_receives;
+	Ok(balance)
+}
+
+/// Decimal precision of an asset, i.e. the number of digits after its decimal point
+pub(crate) fn asset_precision(ctx: &NodeContext, contract_id: &str) -> Result<u8, Error> {
+	let contract_id = parse_contract_id(contract_id)?;
+	let mut rgb_client = ctx.rgb_node_client.lock().unwrap();
+	Ok(get_rgb20_asset(&mut rgb_client, contract_id)?.precision())
+}
+
+fn get_rgb20_asset(rgb_client: &mut Client, contract_id: ContractId) -> Result<Rgb20Asset, Error> {
+	let contract =
+		rgb_client.contract(contract_id, vec![], |_| ()).map_err(|_| Error::UnknownContractId)?;
+	Rgb20Asset::try_from(&contract).map_err(|_| Error::UnknownContractId)
+}
+
+fn get_asset_info(
+	ctx: &NodeContext, rgb_client: &mut Client, contract_id: ContractId,
+	unspents: &HashMap<OutPoint, bool>, channel_balances: AssetBalances,
+) -> Result<AssetInfo, Error> {
+	let asset = get_rgb20_asset(rgb_client, contract_id)?;
+	let balance = get_asset_balances(ctx, rgb_client, contract_id, unspents, channel_balances)?;
+	Ok(AssetInfo {
+		contract_id: contract_id.to_string(),
+		ticker: asset.ticker().to_string(),
+		name: asset.name().to_string(),
+		precision: asset.precision(),
+		issued_supply: asset.total_issued_supply(),
+		balance,
+	})
+}
+
+pub(crate) async fn send_asset(
+	ctx: &NodeContext, req: SendAssetRequest,
+) -> Result<SendAssetResponse, Error> {
+	let contract_id = parse_contract_id(&req.contract_id)?;
+	if req.recipients.is_empty() {
+		return Err(Error::InvalidArgument(s!("at least one recipient is required")));
+	}
+	let mut beneficiaries = EndpointValueMap::new();
+	// outputs for witness recipients come first in the transaction, in the given order
+	let mut witness_scripts = vec![];
+	let mut amt_rgb: u64 = 0;
+	let mut recipient_nodes = vec![];
+	for recipient in &req.recipients {
+		recipient_nodes.push(recipient.node_id.as_deref().map(parse_pubkey).transpose()?);
+		let endpoint = match parse_recipient(&recipient.recipient, ctx.network)? {
+			RecipientSeal::Blinded(concealed_seal) => SealEndpoint::ConcealedUtxo(concealed_seal),
+			RecipientSeal::Witness { address, blinding } => {
+				witness_scripts.push(address.script_pubkey());
+				SealEndpoint::WitnessVout {
+					method: CloseMethod::OpretFirst,
+					vout: witness_scripts.len() as u32 - 1,
+					blinding,
+				}
+			}
+		};
+		if beneficiaries.insert(endpoint, recipient.amount).is_some() {
+			return Err(Error::InvalidArgument(format!(
+				"recipient {} is given more than once",
+				recipient.recipient
+			)));
+		}
+		amt_rgb = amt_rgb
+			.checked_add(recipient.amount)
+			.ok_or_else(|| Error::InvalidAmount(s!("total amount is too big")))?;
+	}
+
+	let total_rgb_amount = get_rgb_total_amount(
+		contract_id,
+		ctx.rgb_node_client.clone(),
+		ctx.wallet.clone(),
+		ctx.electrum_config.clone(),
+		&ctx.rgb_utxos,
+	)?;
+	if amt_rgb > total_rgb_amount {
+		return Err(Error::InsufficientAssets);
+	}
+
+	let asset_owned_values = get_asset_owned_values(
+		contract_id,
+		ctx.rgb_node_client.clone(),
+		ctx.wallet.clone(),
+		ctx.electrum_config.clone(),
+		&ctx.rgb_utxos,
+	)
+	.expect("known contract");
+	let mut rgb_inputs: Vec<OutPoint> = vec![];
+	let mut input_amount: u64 = 0;
+	for owned_value in asset_owned_values {
+		if input_amount >= amt_rgb {
+			break;
+		}
+		let outpoint = OutPoint { txid: owned_value.seal.txid, vout: owned_value.seal.vout };
+		rgb_inputs.push(outpoint);
+		input_amount += owned_value.state.value
+	}
+
+	// reserved before locking the wallet, as topping up the UTXO pool needs it
+	let rgb_change_amount = input_amount - amt_rgb;
+	let rgb_change_outpoint = if rgb_change_amount > 0 { Some(reserve_utxo(ctx)?) } else { None };
+
+	// wallet and RGB node locks must not be held while waiting on the transport
+	let (mut psbt, consignment, consignment_path) = {
+		let wallet = ctx.wallet.lock().unwrap();
+
+		let rgb_change: Vec<AllocatedValue> = if let Some(rgb_change_outpoint) = rgb_change_outpoint
+		{
+			vec![AllocatedValue {
+				value: rgb_change_amount,
+				seal: ExplicitSeal::from_str(&format!("opret1st:{rgb_change_outpoint}"))
+					.expect("valid explicit seal"),
+			}]
+		} else {
+			vec![]
+		};
+
+		let btc_outpoints = rgb_inputs.iter().map(|o| OutPoint { txid: o.txid, vout: o.vout });
+		let inputs: BTreeSet<OutPoint> = FromIterator::from_iter(btc_outpoints);
+
+		let mut builder = wallet.build_tx();
+		let address =
+			wallet.get_address(bdk::wallet::AddressIndex::New).expect("valid address").address;
+		builder
+			.add_utxos(&rgb_inputs)
+			.expect("valid utxos")
+			.fee_rate(get_fee_rate(
+				&ctx.bitcoind_client,
+				ctx.network,
+				ctx.fee_rate,
+				ConfirmationTarget::Normal,
+			))
+			.ordering(bdk::wallet::tx_builder::TxOrdering::Untouched)
+			.drain_to(address.script_pubkey());
+		if witness_scripts.is_empty() {
+			builder.manually_selected_only();
+		} else {
+			// the new outputs may need more bitcoins than the RGB inputs have
+			let unspendable_utxos: Vec<OutPoint> = ctx
+				.rgb_utxos
+				.unspendable(&wallet)
+				.into_iter()
+				.filter(|o| !rgb_inputs.contains(o))
+				.collect();
+			builder.unspendable(unspendable_utxos);
+			for script in witness_scripts {
+				builder.add_recipient(script, ctx.utxo_size_sat);
+			}
+		}
+		let psbt = match builder.finish() {
+			Ok((psbt, _details)) => psbt,
+			Err(e) => {
+				if let Some(rgb_change_outpoint) = rgb_change_outpoint {
+					let _ = ctx.rgb_utxos.release(rgb_change_outpoint);
+				}
+				return Err(tx_creation_error(e));
+			}
+		};
+
+		let mut rgb_client = ctx.rgb_node_client.lock().unwrap();
+		let (psbt, consignment) =
+			rgb_client.send_rgb(contract_id, psbt, inputs, beneficiaries, rgb_change);
+
+		let consignment_path = format!("{}/consignment", ctx.ldk_data_dir);
+		consignment.strict_file_save(consignment_path.clone()).expect("consignment save ok");
+		(psbt, consignment, consignment_path)
+	};
+
+	// the consignment covers all the recipients, each one gets it under its own ID
+	let recipient_ids: Vec<String> = req.recipients.into_iter().map(|r| r.recipient).collect();
+	for (recipient_id, recipient_node) in recipient_ids.iter().zip(recipient_nodes) {
+		let res = ctx
+			.transport
+			.post_consignment(recipient_id, recipient_node, Path::new(&consignment_path))
+			.await;
+		if let Err(e) = res {
+			if let Some(rgb_change_outpoint) = rgb_change_outpoint {
+				let _ = ctx.rgb_utxos.release(rgb_change_outpoint);
+			}
+			return Err(e);
+		}
+	}
+
+	// nothing has been spent yet, so only the change UTXO has to be released on failure
+	if let Err(e) = wait_for_acks(ctx, &recipient_ids).await {
+		if let Some(rgb_change_outpoint) = rgb_change_outpoint {
+			let _ = ctx.rgb_utxos.release(rgb_change_outpoint);
+		}
+		return Err(e);
+	}
+
+	let wallet = ctx.wallet.lock().unwrap();
+	wallet.sign(&mut psbt, SignOptions::default()).expect("able to sign");
+	let tx = psbt.extract_tx();
+	if let Err(e) = broadcast_tx(&tx, ctx.electrum_config.clone()) {
+		if let Some(rgb_change_outpoint) = rgb_change_outpoint {
+			let _ = ctx.rgb_utxos.release(rgb_change_outpoint);
+		}
+		return Err(e);
+	}
+	if let Some(rgb_change_outpoint) = rgb_change_outpoint {
+		// the transaction is out, keep going to record the rest of the transfer
+		if let Err(e) = ctx.rgb_utxos.commit(rgb_change_outpoint) {
+			log_error!(ctx.logger, "{}", e);
+		}
+	}
+	ctx.rgb_utxos.spend(&rgb_inputs);
+
+	let mut rgb_client = ctx.rgb_node_client.lock().unwrap();
+	let _status = rgb_client
+		.consume_transfer(consignment, true, None, |_| ())
+		.expect("valid consume tranfer");
+
+	drop(rgb_client);
+	sync_wallet_and_utxos(&wallet, ctx.electrum_config.clone(), &ctx.rgb_utxos);
+	Ok(SendAssetResponse { txid: tx.txid().to_string() })
+}
+
+/// Waits until every recipient has accepted the consignment posted for it
+async fn wait_for_acks(ctx: &NodeContext, recipient_ids: &[String]) -> Result<(), Error> {
+	let mut pending: Vec<&String> = recipient_ids.iter().collect();
+	let started = now_secs();
+	loop {
+		let mut still_pending = vec![];
+		for recipient_id in pending {
+			match ctx.transport.get_ack(recipient_id).await? {
+				Some(true) => {}
+				Some(false) => return Err(Error::TransferRejected(recipient_id.clone())),
+				None => still_pending.push(recipient_id),
+			}
+		}
+		pending = still_pending;
+		match pending.first() {
+			None => return Ok(()),
+			Some(recipient_id) if now_secs() - started >= ctx.ack_timeout => {
+				return Err(Error::AckTimeout(recipient_id.to_string()))
+			}
+			Some(_) => tokio::time::sleep(Duration::from_secs(ACK_POLL_SECS)).await,
+		}
+	}
+}
+
+pub(crate) fn receive_asset(
+	ctx: &NodeContext, req: ReceiveAssetRequest,
+) -> Result<ReceiveAssetResponse, Error> {
+	let contract_id = req.contract_id.as_deref().map(parse_contract_id).transpose()?;
+	if req.amount.is_some() && contract_id.is_none() {
+		return Err(Error::InvalidArgument(s!("an expected amount requires a contract_id")));
+	}
+	let expires_at = match req.expiry_secs.unwrap_or(RECEIVE_EXPIRY_SECS) {
+		0 => None,
+		expiry_secs => Some(now_secs() + expiry_secs),
+	};
+
+	let (seal, witness_address) = if req.witness {
+		let address = ctx
+			.wallet
+			.lock()
+			.unwrap()
+			.get_address(bdk::wallet::AddressIndex::New)
+			.expect("valid address")
+			.address;
+		// the output must not be spent as plain bitcoins before the transfer is accepted
+		ctx.rgb_utxos.add_witness_script(&address.script_pubkey());
+		let random_bytes = ctx.keys_manager.get_secure_random_bytes();
+		let blinding = u64::from_le_bytes(random_bytes[..8].try_into().expect("8 bytes"));
+		// txid and vout are known once the sender creates the output
+		let seal = Revealed { method: CloseMethod::OpretFirst, txid: None, vout: 0, blinding };
+		(seal, Some(address.to_string()))
+	} else {
+		// the UTXO stays reserved until a transfer is received or the request is dropped
+		let outpoint = reserve_utxo(ctx)?;
+		(Revealed::new(CloseMethod::OpretFirst, outpoint), None)
+	};
+	let blinded_info = BlindedInfo {
+		contract_id,
+		seal,
+		consumed: false,
+		witness_address,
+		amount: req.amount,
+		expires_at,
+		rejected: None,
+		acked: false,
+		acked_at: None,
+	};
+	let recipient = blinded_info.recipient();
+
+	blinded_info.save(&blinded_dir(ctx).join(&recipient));
+
+	Ok(ReceiveAssetResponse { recipient })
+}
+
+pub(crate) fn list_receives(ctx: &NodeContext) -> Result<ListReceivesResponse, Error> {
+	let receives = list_blinded_infos(ctx)
+		.into_iter()
+		.filter(|(_, blinded_info)| !blinded_info.consumed)
+		.map(|(_, blinded_info)| Receive {
+			recipient: blinded_info.recipient(),
+			contract_id: blinded_info.contract_id.map(|cid| cid.to_string()),
+			amount: blinded_info.amount,
+			expires_at: blinded_info.expires_at,
+			expired: blinded_info.is_expired(),
+			rejected: blinded_info.rejected,
+		})
+		.collect();
+	Ok(ListReceivesResponse { receives })
+}
+
+pub(crate) async fn cancel_receive(
+	ctx: &NodeContext, req: CancelReceiveRequest,
+) -> Result<EmptyResponse, Error> {
+	let _refresh_guard = ctx.refresh_lock.lock().await;
+	let (blinded_path, blinded_info) = list_blinded_infos(ctx)
+		.into_iter()
+		.find(|(_, blinded_info)| {
+			!blinded_info.consumed && blinded_info.recipient() == req.recipient
+		})
+		.ok_or(Error::UnknownReceive)?;
+
+	// the assets would be lost if the UTXO went back to the pool after a transfer to it, unless
+	// the transfer has been rejected and will never be accepted
+	if blinded_info.rejected.is_none() {
+		if let Some(consignment) = ctx.transport.get_consignment(&req.recipient).await? {
+			// an acknowledged transfer the sender never broadcast will not be completed either
+			let abandoned = blinded_info.acked
+				&& strict_deserialize(consignment).map_or(false, |consignment: StateTransfer| {
+					is_ack_abandoned(ctx, &blinded_info, &consignment)
+				});
+			if !abandoned {
+				return Err(Error::ReceiveAlreadyFulfilled);
+			}
+		}
+	}
+
+	discard_receive(ctx, &blinded_path, &blinded_info);
+	Ok(EmptyResponse {})
+}
+
+fn blinded_dir(ctx: &NodeContext) -> PathBuf {
+	PathBuf::from_str(&ctx.ldk_data_dir).expect("valid data dir").join("blinded_utxos")
+}
+
+/// All the receive requests, with the path of their file
+fn list_blinded_infos(ctx: &NodeContext) -> Vec<(PathBuf, BlindedInfo)> {
+	fs::read_dir(blinded_dir(ctx))
+		.expect("successful dir read")
+		.map(|bf| {
+			let blinded_path = bf.expect("valid dir entry").path();
+			let serialized_info =
+				fs::read_to_string(&blinded_path).expect("valid blinded info file");
+			let blinded_info: BlindedInfo =
+				serde_json::from_str(&serialized_info).expect("valid blinded data");
+			(blinded_path, blinded_info)
+		})
+		.collect()
+}
+
+/// Drops a receive request no transfer has been sent to, returning its UTXO to the pool
+fn discard_receive(ctx: &NodeContext, blinded_path: &Path, blinded_info: &BlindedInfo) {
+	if let Some(txid) = blinded_info.seal.txid {
+		ctx.rgb_utxos.release_receive(OutPoint { txid, vout: blinded_info.seal.vout });
+	}
+	if let Some(script_pubkey) = blinded_info.witness_script() {
+		ctx.rgb_utxos.remove_witness_script(&script_pubkey);
+	}
+	fs::remove_file(blinded_path).expect("successful file remove");
+}
+
+fn now_secs() -> u64 {
+	SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs()
+}
+
+pub(crate) async fn refresh(ctx: &NodeContext) -> Result<RefreshResponse, Error> {
+	let _refresh_guard = ctx.refresh_lock.lock().await;
+	let mut received = vec![];
+	let mut accepted = vec![];
+	let mut rejected = vec![];
+	for (blinded_path, mut blinded_info) in list_blinded_infos(ctx) {
+		if !blinded_info.is_open() {
+			// a refused transfer is never broadcast, so the seal can be freed on expiry
+			if blinded_info.rejected.is_some() && blinded_info.is_expired() {
+				discard_receive(ctx, &blinded_path, &blinded_info);
+			}
+			continue;
+		}
+
+		let recipient = blinded_info.recipient();
+		let consignment = match ctx.transport.get_consignment(&recipient).await {
+			Ok(Some(consignment)) => consignment,
+			// only drop the request once the transport confirms nothing has been sent to it
+			Ok(_) if blinded_info.is_expired() => {
+				println!("WARNING: receive request {recipient} expired without a transfer");
+				discard_receive(ctx, &blinded_path, &blinded_info);
+				continue;
+			}
+			// nothing has been sent yet
+			Ok(_) => continue,
+			Err(e) => {
+				return Ok(RefreshResponse {
+					received,
+					accepted,
+					rejected,
+					transport_error: Some(e.to_string()),
+				})
+			}
+		};
+		let consignment: Result<StateTransfer, String> =
+			strict_deserialize(consignment).map_err(|_| s!("invalid consignment"));
+
+		// the sender waits for the validation result before broadcasting the transaction
+		if !blinded_info.acked {
+			// don't let the sender register an arbitrary contract into the RGB node
+			let reason = match &consignment {
+				Ok(consignment) => match check_transfer(&blinded_info, consignment) {
+					Some(reason) => Some(reason),
+					None => match validate_transfer(ctx, consignment) {
+						Ok(reason) => reason,
+						// neither ACK nor NACK, the transfer is validated again on the next refresh
+						Err(e) => {
+							println!(
+								"WARNING: unable to validate the transfer to {recipient}: {e}"
+							);
+							continue;
+						}
+					},
+				},
+				Err(e) => Some(e.clone()),
+			};
+			if let Some(reason) = &reason {
+				match ctx.transport.discard_consignment(&recipient).await {
+					// the expected consignment may still arrive
+					Ok(true) => {
+						println!("WARNING: ignored a consignment for {recipient}: {reason}");
+						continue;
+					}
+					Ok(false) => {}
+					Err(e) => {
+						return Ok(RefreshResponse {
+							received,
+							accepted,
+							rejected,
+							transport_error: Some(e.to_string()),
+						})
+					}
+				}
+			}
+			if let Err(e) = ctx.transport.post_ack(&recipient, reason.is_none()).await {
+				return Ok(RefreshResponse {
+					received,
+					accepted,
+					rejected,
+					transport_error: Some(e.to_string()),
+				});
+			}
+			match reason {
+				Some(reason) => {
+					log_info!(ctx.logger, "RGB transfer to {} rejected: {}", recipient, reason);
+					blinded_info.rejected = Some(reason.clone());
+					let transfer = RejectedTransfer { recipient, reason };
+					publish_transfer_event(ctx, TransferEvent::Rejected(transfer.clone()));
+					rejected.push(transfer);
+				}
+				None => {
+					blinded_info.acked = true;
+					blinded_info.acked_at = Some(now_secs());
+					accepted.push(recipient);
+				}
+			}
+			blinded_info.save(&blinded_path);
+			// the transfer can be completed once the sender has broadcast its transaction
+			continue;
+		}
+		// requests acknowledged before the ACK time was recorded wait from now on
+		if blinded_info.acked_at.is_none() {
+			blinded_info.acked_at = Some(now_secs());
+			blinded_info.save(&blinded_path);
+		}
+		let consignment = match consignment {
+			Ok(consignment) => consignment,
+			// the transport no longer has the consignment that was acknowledged
+			Err(e) => {
+				println!("WARNING: acknowledged transfer to {recipient} has an {e}");
+				continue;
+			}
+		};
+
+		// the sender broadcasts the transaction after the ACK, wait for it before accepting
+		let broadcast = match &blinded_info.witness_address {
+			Some(address) => {
+				find_witness_outpoint(ctx, &consignment, address).map(|outpoint| (outpoint, true))
+			}
+			None if is_transfer_broadcast(ctx, &consignment) => {
+				let seal = &blinded_info.seal;
+				let txid = seal.txid.expect("blinded UTXO txid");
+				Some((OutPoint { txid, vout: seal.vout }, false))
+			}
+			None => None,
+		};
+		let (outpoint, witness_vout) = match broadcast {
+			Some(broadcast) => broadcast,
+			None => {
+				// e.g. another recipient of the same transaction didn't acknowledge it
+				if is_ack_abandoned(ctx, &blinded_info, &consignment) {
+					println!(
+						"WARNING: the sender never broadcast the transfer to {recipient}, dropping the request"
+					);
+					discard_receive(ctx, &blinded_path, &blinded_info);
+				}
+				continue;
+			}
+		};
+
+		let ser_cons = strict_serialize(&consignment).expect("valid consignment");
+		let contract_consignment: Contract =
+			strict_deserialize(ser_cons).expect("valid serialized consignment");
+
+		let mut rgb_client = ctx.rgb_node_client.lock().unwrap();
+		rgb_client.register_contract(contract_consignment, true, |_| ()).expect("valid contract");
+
+		let reveal = Reveal {
+			blinding_factor: blinded_info.seal.blinding,
+			outpoint,
+			close_method: CloseMethod::OpretFirst,
+			witness_vout,
+		};
+		let status = rgb_client
+			.consume_transfer(consignment.clone(), true, Some(reveal), |_| ())
+			.expect("valid consume tranfer");
+		if !matches!(status, ContractValidity::Valid) {
+			println!("WARNING: error consuming transfer");
+			continue;
+		}
+		if witness_vout {
+			ctx.rgb_utxos.add(outpoint, true);
+			if let Some(script_pubkey) = blinded_info.witness_script() {
+				ctx.rgb_utxos.remove_witness_script(&script_pubkey);
+			}
+		} else {
+			ctx.rgb_utxos.commit_receive(outpoint);
+		}
+
+		let contract_id = consignment.contract_id();
+		let amount = rgb_client
+			.contract_state(contract_id)
+			.expect("registered contract")
+			.owned_values
+			.iter()
+			.filter(|ov| ov.seal.txid == outpoint.txid && ov.seal.vout == outpoint.vout)
+			.map(|ov| ov.state.value)
+			.sum();
+		let ticker = get_rgb20_asset(&mut rgb_client, contract_id)
+			.map(|asset| asset.ticker().to_string())
+			.unwrap_or_else(|_| contract_id.to_string());
+		let transfer =
+			ReceivedTransfer { recipient, contract_id: contract_id.to_string(), ticker, amount };
+		log_info!(
+			ctx.logger,
+			"RGB transfer received: {}",
+			serde_json::to_string(&transfer).expect("valid transfer")
+		);
+		publish_transfer_event(ctx, TransferEvent::Received(transfer.clone()));
+		received.push(transfer);
+		drop(rgb_client);
+
+		let wallet = ctx.wallet.lock().unwrap();
+		sync_wallet_and_utxos(&wallet, ctx.electrum_config.clone(), &ctx.rgb_utxos);
+
+		fs::remove_file(blinded_path).expect("successful file remove");
+	}
+
+	Ok(RefreshResponse { received, accepted, rejected, transport_error: None })
+}
+
+fn publish_transfer_event(ctx: &NodeContext, event: TransferEvent) {
+	// sending only fails when nobody is subscribed
+	let _ = ctx.transfer_events.send(event);
+}
+
+/// Why the consignment doesn't satisfy the contract and amount pinned by the receive request
+fn check_transfer(blinded_info: &BlindedInfo, consignment: &StateTransfer) -> Option<String> {
+	let contract_id = consignment.contract_id();
+	if let Some(expected_id) = blinded_info.contract_id {
+		if contract_id != expected_id {
+			return Some(format!("expected contract {expected_id}, got {contract_id}"));
+		}
+	}
+	if let Some(min_amount) = blinded_info.amount {
+		let vout = match blinded_info.witness_address {
+			// the output of a witness receive is only known from the consignment
+			Some(_) => match find_witness_vout(consignment, blinded_info.seal.blinding) {
+				Some(vout) => vout,
+				None => return Some(s!("no output of the transfer is assigned to the request")),
+			},
+			None => blinded_info.seal.vout,
+		};
+		let seal = Revealed { vout, ..blinded_info.seal };
+		let amount = get_received_amount(consignment, &seal);
+		if amount < min_amount {
+			return Some(format!("expected at least {min_amount}, got {amount}"));
+		}
+	}
+	None
+}
+
+/// Validates the consignment with the RGB node before it's acknowledged. The sender broadcasts
+/// the transfer transaction only after the ACK, so its txid is the only one that may be unknown.
+/// Fails when the RGB node can't be reached
+fn validate_transfer(
+	ctx: &NodeContext, consignment: &StateTransfer,
+) -> Result<Option<String>, String> {
+	let txid = match consignment.anchored_bundles().last() {
+		Some(anchored_bundle) => anchored_bundle.0.txid,
+		None => return Ok(Some(s!("the consignment has no transfer"))),
+	};
+	let ser_cons = strict_serialize(consignment).expect("valid consignment");
+	let contract_consignment: Contract =
+		strict_deserialize(ser_cons).expect("valid serialized consignment");
+
+	let mut rgb_client = ctx.rgb_node_client.lock().unwrap();
+	rgb_client.register_contract(contract_consignment, true, |_| ()).map_err(|e| e.to_string())?;
+	let validity = rgb_client
+		.consume_transfer(consignment.clone(), false, None, |_| ())
+		.map_err(|e| e.to_string())?;
+	Ok(match validity {
+		ContractValidity::Valid => None,
+		ContractValidity::UnknownTxids(txids) if txids.iter().all(|t| *t == txid) => None,
+		validity => Some(format!("invalid consignment: {:?}", validity)),
+	})
+}
+
+/// Whether the transaction of the consignment's latest transfer has been broadcast
+fn is_transfer_broadcast(ctx: &NodeContext, consignment: &StateTransfer) -> bool {
+	match consignment.anchored_bundles().last() {
+		Some(anchored_bundle) => {
+			matches!(get_tx(&anchored_bundle.0.txid, ctx.electrum_config.clone()), Ok(Some(_)))
+		}
+		None => false,
+	}
+}
+
+/// Whether the sender stopped waiting for the ACKs without broadcasting the transaction of the
+/// consignment's latest transfer, which is then never broadcast
+fn is_ack_abandoned(
+	ctx: &NodeContext, blinded_info: &BlindedInfo, consignment: &StateTransfer,
+) -> bool {
+	let ack_timed_out = blinded_info
+		.acked_at
+		.map_or(false, |acked_at| now_secs() >= acked_at.saturating_add(ctx.ack_timeout));
+	if !ack_timed_out {
+		return false;
+	}
+	// an unreachable Electrum server doesn't prove the transaction is missing
+	match consignment.anchored_bundles().last() {
+		Some(anchored_bundle) => {
+			matches!(get_tx(&anchored_bundle.0.txid, ctx.electrum_config.clone()), Ok(None))
+		}
+		None => true,
+	}
+}
+
+/// The wallet output paying to `address` in the transaction of the consignment's latest transfer
+fn find_witness_outpoint(
+	ctx: &NodeContext, consignment: &StateTransfer, address: &str,
+) -> Option<OutPoint> {
+	let script_pubkey = Address::from_str(address).expect("valid address").script_pubkey();
+	let txid = consignment.anchored_bundles().last()?.0.txid;
+	let wallet = ctx.wallet.lock().unwrap();
+	sync_wallet_and_utxos(&wallet, ctx.electrum_config.clone(), &ctx.rgb_utxos);
+	let tx = wallet.get_tx(&txid, true).expect("valid wallet db")?.transaction?;
+	let vout = tx.output.iter().position(|o| o.script_pubkey == script_pubkey)?;
+	Some(OutPoint { txid, vout: vout as u32 })
+}
+
+pub(crate) async fn open_channel(
+	ctx: &NodeContext, req: OpenChannelRequest,
+) -> Result<OpenChannelResponse, Error> {
+	let (pubkey, peer_addr) = parse_peer_info(req.peer_pubkey_and_addr.clone())
+		.map_err(|e| Error::InvalidPeerInfo(e.to_string()))?;
+
+	let chan_amt_sat = req.capacity_sat;
+	if chan_amt_sat < OPENCHANNEL_MIN_SAT {
+		return Err(Error::InvalidAmount(format!(
+			"channel amount must be equal or higher than {}",
+			OPENCHANNEL_MIN_SAT
+		)));
+	}
+	if chan_amt_sat > OPENCHANNEL_MAX_SAT {
+		return Err(Error::InvalidAmount(format!(
+			"channel amount must be equal or less than {}",
+			OPENCHANNEL_MAX_SAT
+		)));
+	}
+
+	let push_amt_msat = req.push_msat;
+	if push_amt_msat < DUST_LIMIT_MSAT {
+		return Err(Error::InvalidAmount(format!(
+			"push amount must be equal or higher than the dust limit ({})",
+			DUST_LIMIT_MSAT
+		)));
+	}
+
+	let rgb_params = parse_rgb_params(req.contract_id.as_deref(), req.asset_amount)?;
+	if let Some((contract_id, chan_amt_rgb)) = rgb_params {
+		let total_rgb_amount = get_rgb_total_amount(
+			contract_id,
+			ctx.rgb_node_client.clone(),
+			ctx.wallet.clone(),
+			ctx.electrum_config.clone(),
+			&ctx.rgb_utxos,
+		)?;
+		if chan_amt_rgb > total_rgb_amount {
+			return Err(Error::InsufficientAssets);
+		}
+	}
+
+	connect_peer_if_necessary(pubkey, peer_addr, ctx.peer_manager.clone())
+		.await
+		.map_err(|_| Error::FailedPeerConnection)?;
+
+	let config = UserConfig {
+		channel_handshake_limits: ChannelHandshakeLimits {
+			// lnd's max to_self_delay is 2016, so we want to be compatible.
+			their_to_self_delay: 2016,
+			..Default::default()
+		},
+		channel_handshake_config: ChannelHandshakeConfig {
+			announced_channel: req.public,
+			our_htlc_minimum_msat: HTLC_MIN_MSAT,
+			..Default::default()
+		},
+		..Default::default()
+	};
+
+	// the counterparty's LDK fork fetches the funding consignment of an RGB channel from this
+	// endpoint, with any transport, so the proxy is needed when the transport has no endpoint
+	let consignment_endpoint = ctx
+		.transport
+		.consignment_endpoint()
+		.or_else(|| ctx.proxy_url.as_deref().map(proxy_endpoint));
+	let consignment_endpoint = match (consignment_endpoint, rgb_params) {
+		(Some(consignment_endpoint), _) => consignment_endpoint,
+		(None, Some(_)) => return Err(Error::NoConsignmentEndpoint),
+		(None, None) => s!(UNUSED_CONSIGNMENT_ENDPOINT),
+	};
+	let consignment_endpoint = ConsignmentEndpoint::from_str(&consignment_endpoint)
+		.map_err(|_| Error::InvalidArgument(format!("invalid endpoint {consignment_endpoint}")))?;
+	let temporary_channel_id = ctx
+		.channel_manager
+		.create_channel(pubkey, chan_amt_sat, push_amt_msat, 0, Some(config), consignment_endpoint)
+		.map_err(|e| Error::FailedOpenChannel(format!("{:?}", e)))?;
+
+	let peer_data_path = format!("{}/channel_peer_data", ctx.ldk_data_dir);
+	let _ = disk::persist_channel_peer(Path::new(&peer_data_path), &req.peer_pubkey_and_addr);
+
+	if let Some((contract_id, chan_amt_rgb)) = rgb_params {
+		let channel_rgb_info_path =
+			format!("{}/{}", ctx.ldk_data_dir, hex::encode(&temporary_channel_id));
+		let rgb_info =
+			RgbInfo { contract_id, local_rgb_amount: chan_amt_rgb, remote_rgb_amount: 0 };
+		write_rgb_channel_info(&PathBuf::from(&channel_rgb_info_path), &rgb_info);
+	}
+
+	Ok(OpenChannelResponse { temporary_channel_id: hex::encode(&temporary_channel_id) })
+}
+
+pub(crate) fn send_payment(
+	ctx: &NodeContext, req: SendPaymentRequest,
+) -> Result<PaymentResponse, Error> {
+	let invoice = parse_invoice(&req.invoice)?;
+	let amt_msat = match invoice.amount_milli_satoshis() {
+		Some(amt_msat) if amt_msat < INVOICE_MIN_MSAT => {
+			return Err(Error::InvalidAmount(format!(
+				"msat amount in invoice cannot be less than {INVOICE_MIN_MSAT}"
+			)))
+		}
+		Some(amt_msat) => amt_msat,
+		None => return Err(Error::InvalidAmount("msat amount missing in invoice".to_string())),
+	};
+
+	let payment_hash = PaymentHash(invoice.payment_hash().clone().into_inner());
+	if let (Some(contract_id), Some(amt_rgb)) = (invoice.rgb_contract_id(), invoice.rgb_amount()) {
+		write_rgb_payment_info_file(
+			&PathBuf::from(&ctx.ldk_data_dir),
+			&payment_hash,
+			contract_id,
+			amt_rgb,
+		);
+	}
+	let res = pay_invoice(&invoice, Retry::Timeout(Duration::from_secs(10)), &*ctx.channel_manager);
+	let status = if res.is_ok() { HTLCStatus::Pending } else { HTLCStatus::Failed };
+	let payment_secret = Some(invoice.payment_secret().clone());
+
+	let mut payments = ctx.outbound_payments.lock().unwrap();
+	payments.insert(
+		payment_hash,
+		PaymentInfo {
+			preimage: None,
+			secret: payment_secret,
+			status,
+			amt_msat: MillisatAmount(invoice.amount_milli_satoshis()),
+			rgb_contract_id: invoice.rgb_contract_id(),
+			rgb_amount: invoice.rgb_amount(),
+		},
+	);
+	disk::persist_payments(
+		Path::new(&format!("{}/{}", ctx.ldk_data_dir, disk::OUTBOUND_PAYMENTS_FNAME)),
+		&payments,
+	);
+
+	match res {
+		Ok(_payment_id) => Ok(PaymentResponse {
+			payment_hash: hex_utils::hex_str(&payment_hash.0),
+			amt_msat,
+			payee_pubkey: invoice.recover_payee_pub_key().to_string(),
+		}),
+		Err(e) => Err(Error::FailedPayment(format!("{:?}", e))),
+	}
+}
+
+pub(crate) fn keysend(ctx: &NodeContext, req: KeysendRequest) -> Result<PaymentResponse, Error> {
+	let payee_pubkey = parse_pubkey(&req.dest_pubkey)?;
+	let amt_msat = req.amt_msat;
+	if amt_msat < HTLC_MIN_MSAT {
+		return Err(Error::InvalidAmount(format!(
+			"amount_msat cannot be less than {HTLC_MIN_MSAT}"
+		)));
+	}
+	let rgb_params = parse_rgb_params(req.contract_id.as_deref(), req.amt_rgb)?;
+
+	let payment_preimage = PaymentPreimage(ctx.keys_manager.get_secure_random_bytes());
+	let payment_hash = PaymentHash(Sha256::hash(&payment_preimage.0[..]).into_inner());
+	if let Some((contract_id, amt_rgb)) = rgb_params {
+		write_rgb_payment_info_file(
+			&PathBuf::from(&ctx.ldk_data_dir),
+			&payment_hash,
+			contract_id,
+			amt_rgb,
+		);
+	}
+
+	let route_params = RouteParameters {
+		payment_params: PaymentParameters::for_keysend(payee_pubkey, 40),
+		final_value_msat: amt_msat,
+	};
+	let res = ctx.channel_manager.send_spontaneous_payment_with_retry(
+		Some(payment_preimage),
+		RecipientOnionFields::spontaneous_empty(),
+		PaymentId(payment_hash.0),
+		route_params,
+		Retry::Timeout(Duration::from_secs(10)),
+	);
+	let status = if res.is_ok() { HTLCStatus::Pending } else { HTLCStatus::Failed };
+
+	let mut payments = ctx.outbound_payments.lock().unwrap();
+	payments.insert(
+		payment_hash,
+		PaymentInfo {
+			preimage: None,
+			secret: None,
+			status,
+			amt_msat: MillisatAmount(Some(amt_msat)),
+			rgb_contract_id: rgb_params.map(|(contract_id, _)| contract_id),
+			rgb_amount: rgb_params.map(|(_, amt_rgb)| amt_rgb),
+		},
+	);
+	disk::persist_payments(
+		Path::new(&format!("{}/{}", ctx.ldk_data_dir, disk::OUTBOUND_PAYMENTS_FNAME)),
+		&payments,
+	);
+
+	match res {
+		Ok(_payment_hash) => Ok(PaymentResponse {
+			payment_hash: hex_utils::hex_str(&payment_hash.0),
+			amt_msat,
+			payee_pubkey: payee_pubkey.to_string(),
+		}),
+		Err(e) => Err(Error::FailedPayment(format!("{:?}", e))),
+	}
+}
+
+pub(crate) fn get_invoice(
+	ctx: &NodeContext, req: GetInvoiceRequest,
+) -> Result<GetInvoiceResponse, Error> {
+	let amt_msat = req.amt_msat;
+	if amt_msat < INVOICE_MIN_MSAT {
+		return Err(Error::InvalidAmount(format!(
+			"amt_msat cannot be less than {INVOICE_MIN_MSAT}"
+		)));
+	}
+	let rgb_params = parse_rgb_params(req.contract_id.as_deref(), req.amt_rgb)?;
+
+	let mut payments = ctx.inbound_payments.lock().unwrap();
+	let currency = match ctx.network {
+		Network::Bitcoin => Currency::Bitcoin,
+		Network::Testnet => Currency::BitcoinTestnet,
+		Network::Regtest => Currency::Regtest,
+		Network::Signet => Currency::Signet,
+	};
+	let invoice = utils::create_invoice_from_channelmanager(
+		&*ctx.channel_manager,
+		ctx.keys_manager.clone(),
+		ctx.logger.clone(),
+		currency,
+		Some(amt_msat),
+		"ldk-tutorial-node".to_string(),
+		req.expiry_secs,
+		None,
+		rgb_params.map(|(contract_id, _)| contract_id),
+		rgb_params.map(|(_, amt_rgb)| amt_rgb),
+	)
+	.map_err(|e| Error::FailedInvoiceCreation(format!("{:?}", e)))?;
+
+	let payment_hash = PaymentHash(invoice.payment_hash().clone().into_inner());
+	payments.insert(
+		payment_hash,
+		PaymentInfo {
+			preimage: None,
+			secret: Some(invoice.payment_secret().clone()),
+			status: HTLCStatus::Pending,
+			amt_msat: MillisatAmount(Some(amt_msat)),
+			rgb_contract_id: rgb_params.map(|(contract_id, _)| contract_id),
+			rgb_amount: rgb_params.map(|(_, amt_rgb)| amt_rgb),
+		},
+	);
+	disk::persist_payments(
+		Path::new(&format!("{}/{}", ctx.ldk_data_dir, disk::INBOUND_PAYMENTS_FNAME)),
+		&payments,
+	);
+
+	Ok(GetInvoiceResponse { invoice: invoice.to_string() })
+}
+
+pub(crate) fn invoice_status(
+	ctx: &NodeContext, req: InvoiceStatusRequest,
+) -> Result<InvoiceStatusResponse, Error> {
+	let invoice = parse_invoice(&req.invoice)?;
+	let inbound = ctx.inbound_payments.lock().unwrap();
+
+	let payment_hash = PaymentHash(invoice.payment_hash().clone().into_inner());
+	match inbound.get(&payment_hash) {
+		Some(v) => {
+			let status = match v.status {
+				HTLCStatus::Pending if invoice.is_expired() => "expired".to_string(),
+				_ => v.status.to_string(),
+			};
+			Ok(InvoiceStatusResponse { status })
+		}
+		None => Err(Error::UnknownInvoice),
+	}
+}
+
+pub(crate) async fn connect_peer(
+	ctx: &NodeContext, req: ConnectPeerRequest,
+) -> Result<EmptyResponse, Error> {
+	let (pubkey, peer_addr) = parse_peer_info(req.peer_pubkey_and_addr)
+		.map_err(|e| Error::InvalidPeerInfo(e.to_string()))?;
+	connect_peer_if_necessary(pubkey, peer_addr, ctx.peer_manager.clone())
+		.await
+		.map_err(|_| Error::FailedPeerConnection)?;
+	Ok(EmptyResponse {})
+}
+
+pub(crate) fn disconnect_peer(
+	ctx: &NodeContext, req: DisconnectPeerRequest,
+) -> Result<EmptyResponse, Error> {
+	let pubkey = PublicKey::from_str(&req.peer_pubkey).map_err(|_| Error::InvalidPubkey)?;
+
+	//check for open channels with peer
+	for channel in ctx.channel_manager.list_channels() {
+		if channel.counterparty.node_id == pubkey {
+			return Err(Error::FailedPeerDisconnection(
+				"node has an active channel with this peer, close any channels first".to_string(),
+			));
+		}
+	}
+
+	//check the pubkey matches a valid connected peer
+	let peers = ctx.peer_manager.get_peer_node_ids();
+	if !peers.iter().any(|(pk, _)| &pubkey == pk) {
+		return Err(Error::FailedPeerDisconnection(format!("could not find peer {}", pubkey)));
+	}
+
+	ctx.peer_manager.disconnect_by_node_id(pubkey);
+	Ok(EmptyResponse {})
+}
+
+pub(crate) fn list_peers(ctx: &NodeContext) -> Result<ListPeersResponse, Error> {
+	let peers = ctx
+		.peer_manager
+		.get_peer_node_ids()
+		.into_iter()
+		.map(|(pubkey, _)| Peer { pubkey: pubkey.to_string() })
+		.collect();
+	Ok(ListPeersResponse { peers })
+}
+
+pub(crate) fn list_channels(ctx: &NodeContext) -> Result<ListChannelsResponse, Error> {
+	let ldk_data_dir_path = PathBuf::from(&ctx.ldk_data_dir);
+	let mut channels = vec![];
+	for chan_info in ctx.channel_manager.list_channels() {
+		let peer_alias = ctx
+			.network_graph
+			.read_only()
+			.nodes()
+			.get(&NodeId::from_pubkey(&chan_info.counterparty.node_id))
+			.and_then(|node_info| node_info.announcement_info.as_ref())
+			.map(|announcement| announcement.alias.to_string());
+
+		let (rgb_contract_id, rgb_local_amount, rgb_remote_amount) =
+			if is_channel_rgb(&chan_info.channel_id, &ldk_data_dir_path) {
+				let (rgb_info, _) = get_rgb_channel_info(&chan_info.channel_id, &ldk_data_dir_path);
+				(
+					Some(rgb_info.contract_id.to_string()),
+					Some(rgb_info.local_rgb_amount),
+					Some(rgb_info.remote_rgb_amount),
+				)
+			} else {
+				(None, None, None)
+			};
+
+		channels.push(Channel {
+			channel_id: hex_utils::hex_str(&chan_info.channel_id[..]),
+			funding_txid: chan_info.funding_txo.map(|funding_txo| funding_txo.txid.to_string()),
+			peer_pubkey: hex_utils::hex_str(&chan_info.counterparty.node_id.serialize()),
+			peer_alias,
+			short_channel_id: chan_info.short_channel_id,
+			is_channel_ready: chan_info.is_channel_ready,
+			channel_value_satoshis: chan_info.channel_value_satoshis,
+			local_balance_msat: chan_info.balance_msat,
+			available_balance_for_send_msat: if chan_info.is_usable {
+				Some(chan_info.outbound_capacity_msat)
+			} else {
+				None
+			},
+			available_balance_for_recv_msat: if chan_info.is_usable {
+				Some(chan_info.inbound_capacity_msat)
+			} else {
+				None
+			},
+			channel_can_send_payments: chan_info.is_usable,
+			public: chan_info.is_public,
+			rgb_contract_id,
+			rgb_local_amount,
+			rgb_remote_amount,
+		});
+	}
+	Ok(ListChannelsResponse { channels })
+}
+
+pub(crate) fn list_payments(ctx: &NodeContext) -> Result<ListPaymentsResponse, Error> {
+	let inbound = ctx.inbound_payments.lock().unwrap();
+	let outbound = ctx.outbound_payments.lock().unwrap();
+	let mut payments = vec![];
+	for (direction, storage) in [("inbound", &inbound), ("outbound", &outbound)] {
+		for (payment_hash, payment_info) in storage.iter() {
+			payments.push(Payment {
+				amount_msat: payment_info.amt_msat.0,
+				payment_hash: hex_utils::hex_str(&payment_hash.0),
+				htlc_direction: direction.to_string(),
+				htlc_status: payment_info.status.to_string(),
+				rgb_contract_id: payment_info.rgb_contract_id.as_ref().map(|c| c.to_string()),
+				rgb_amount: payment_info.rgb_amount,
+			});
+		}
+	}
+	Ok(ListPaymentsResponse { payments })
+}
+
+pub(crate) fn close_channel(
+	ctx: &NodeContext, req: ChannelIdRequest,
+) -> Result<EmptyResponse, Error> {
+	let channel_id = parse_channel_id(&req.channel_id)?;
+	let peer_pubkey = parse_pubkey(&req.peer_pubkey)?;
+	ctx.channel_manager
+		.close_channel(&channel_id, &peer_pubkey)
+		.map_err(|e| Error::FailedCloseChannel(format!("{:?}", e)))?;
+	Ok(EmptyResponse {})
+}
+
+pub(crate) fn force_close_channel(
+	ctx: &NodeContext, req: ChannelIdRequest,
+) -> Result<EmptyResponse, Error> {
+	let channel_id = parse_channel_id(&req.channel_id)?;
+	let peer_pubkey = parse_pubkey(&req.peer_pubkey)?;
+	ctx.channel_manager
+		.force_close_broadcasting_latest_txn(&channel_id, &peer_pubkey)
+		.map_err(|e| Error::FailedCloseChannel(format!("{:?}", e)))?;
+	Ok(EmptyResponse {})
+}
+
+pub(crate) fn list_pending_channels(
+	ctx: &NodeContext,
+) -> Result<ListPendingChannelsResponse, Error> {
+	let pending_channels = ctx.pending_channels.lock().unwrap();
+	Ok(ListPendingChannelsResponse {
+		pending_channels: pending_channels.values().cloned().collect(),
+	})
+}
+
+fn take_pending_channel(
+	ctx: &NodeContext, temporary_channel_id: &str,
+) -> Result<([u8; 32], PublicKey), Error> {
+	let temporary_channel_id = parse_channel_id(temporary_channel_id)?;
+	let pending_channel = ctx
+		.pending_channels
+		.lock()
+		.unwrap()
+		.remove(&temporary_channel_id)
+		.ok_or(Error::UnknownPendingChannel)?;
+	Ok((temporary_channel_id, parse_pubkey(&pending_channel.peer_pubkey)?))
+}
+
+pub(crate) fn accept_channel(
+	ctx: &NodeContext, req: PendingChannelRequest,
+) -> Result<EmptyResponse, Error> {
+	let (temporary_channel_id, peer_pubkey) = take_pending_channel(ctx, &req.temporary_channel_id)?;
+	ctx.channel_manager
+		.accept_inbound_channel(&temporary_channel_id, &peer_pubkey, 0)
+		.map_err(|e| Error::FailedChannelAcceptance(format!("{:?}", e)))?;
+	Ok(EmptyResponse {})
+}
+
+pub(crate) fn reject_channel(
+	ctx: &NodeContext, req: PendingChannelRequest,
+) -> Result<EmptyResponse, Error> {
+	let (temporary_channel_id, peer_pubkey) = take_pending_channel(ctx, &req.temporary_channel_id)?;
+	ctx.channel_manager
+		.force_close_without_broadcasting_txn(&temporary_channel_id, &peer_pubkey)
+		.map_err(|e| Error::FailedCloseChannel(format!("{:?}", e)))?;
+	Ok(EmptyResponse {})
+}
+
+pub(crate) fn node_info(ctx: &NodeContext) -> Result<NodeInfoResponse, Error> {
+	let chans = ctx.channel_manager.list_channels();
+	Ok(NodeInfoResponse {
+		node_pubkey: ctx.channel_manager.get_our_node_id().to_string(),
+		num_channels: chans.len(),
+		num_usable_channels: chans.iter().filter(|c| c.is_usable).count(),
+		local_balance_msat: chans.iter().map(|c| c.balance_msat).sum::<u64>(),
+		num_peers: ctx.peer_manager.get_peer_node_ids().len(),
+	})
+}
+
+pub(crate) fn sign_message(
+	ctx: &NodeContext, req: SignMessageRequest,
+) -> Result<SignMessageResponse, Error> {
+	if req.message.is_empty() {
+		return Err(Error::InvalidArgument("signmessage requires a message".to_string()));
+	}
+	let signed_message = lightning::util::message_signing::sign(
+		req.message.as_bytes(),
+		&ctx.keys_manager.get_node_secret_key(),
+	)
+	.map_err(|e| Error::InvalidArgument(format!("{:?}", e)))?;
+	Ok(SignMessageResponse { signed_message })
+}
+
+pub(crate) fn send_onion_message(
+	ctx: &NodeContext, req: SendOnionMessageRequest,
+) -> Result<EmptyResponse, Error> {
+	if req.node_ids.is_empty() {
+		return Err(Error::InvalidArgument(
+			"sendonionmessage requires at least one node id for the path".to_string(),
+		));
+	}
+	let mut node_pks = Vec::new();
+	for pk_str in req.node_ids {
+		let node_pubkey_vec = hex_utils::to_vec(&pk_str).ok_or(Error::InvalidPubkey)?;
+		let node_pubkey =
+			PublicKey::from_slice(&node_pubkey_vec).map_err(|_| Error::InvalidPubkey)?;
+		node_pks.push(node_pubkey);
+	}
+	if req.tlv_type < 64 {
+		return Err(Error::InvalidArgument("need an integral message type above 64".to_string()));
+	}
+	let data = hex_utils::to_vec(&req.data)
+		.ok_or_else(|| Error::InvalidArgument("need a hex data string".to_string()))?;
+	let destination_pk = node_pks.pop().unwrap();
+	ctx.onion_messenger
+		.send_onion_message(
+			&node_pks,
+			Destination::Node(destination_pk),
+			OnionMessageContents::Custom(UserOnionMessageContents { tlv_type: req.tlv_type, data }),
+			None,
+		)
+		.map_err(|e| Error::FailedOnionMessage(format!("{:?}", e)))?;
+	Ok(EmptyResponse {})
+}
@@ -1,6 +1,12 @@
 use crate::cli::LdkUserInfo;
+use crate::policy::ChannelPolicy;
+use crate::transport::TransportConfig;
+use crate::REFRESH_MAX_INTERVAL;
 use bitcoin::network::constants::Network;
+use bitcoin::secp256k1::PublicKey;
 use lightning::ln::msgs::NetAddress;
+use rgb::ContractId;
+use serde::Deserialize;
 use std::collections::HashMap;
 use std::env;
 use std::fs;
@@ -8,121 +14,303 @@ use std::net::IpAddr;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-pub(crate) fn parse_startup_args() -> Result<LdkUserInfo, ()> {
-	if env::args().len() < 4 {
-		println!("rgb-lightning-sample requires at least 3 arguments: `cargo run [<bitcoind-rpc-username>:<bitcoind-rpc-password>@]<bitcoind-rpc-host>:<bitcoind-rpc-port> ldk_storage_directory_path rgb-node-port [<ldk-incoming-peer-listening-port>] [bitcoin-network] [announced-node-name announced-listen-addr*]`");
-		return Err(());
-	}
-	let bitcoind_rpc_info = env::args().skip(1).next().unwrap();
-	let bitcoind_rpc_info_parts: Vec<&str> = bitcoind_rpc_info.rsplitn(2, "@").collect();
+/// Startup settings read from the TOML config file. Each key can be overridden by a
+/// `--<key>=<value>` flag, using dashes instead of underscores (e.g. `--rgb-node-port=63963`)
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Config {
+	bitcoind_rpc_host: Option<String>,
+	bitcoind_rpc_port: Option<u16>,
+	bitcoind_rpc_username: Option<String>,
+	bitcoind_rpc_password: Option<String>,
+	ldk_storage_dir_path: Option<String>,
+	rgb_node_port: Option<u16>,
+	ldk_peer_listening_port: Option<u16>,
+	network: Option<String>,
+	allow_mainnet: bool,
+	announced_node_name: Option<String>,
+	announced_listen_addr: Vec<String>,
+	rpc_port: Option<u16>,
+	fee_rate: Option<f32>,
+	utxo_size_sat: Option<u64>,
+	utxo_num: Option<u8>,
+	min_uncolored_utxos: Option<u8>,
+	refresh_interval: Option<u64>,
+	ack_timeout: Option<u64>,
+	electrum_url: Option<String>,
+	electrum_retry: Option<u8>,
+	electrum_timeout: Option<u8>,
+	electrum_stop_gap: Option<usize>,
+	proxy_url: Option<String>,
+	proxy_timeout: Option<u64>,
+	consignment_transport: Option<String>,
+	consignment_dir: Option<String>,
+	manual_accept: bool,
+	accept_peers: Vec<String>,
+	accept_contract_ids: Vec<String>,
+	min_rgb_amount: Option<u64>,
+	max_rgb_amount: Option<u64>,
+	max_channels_per_peer: Option<usize>,
+}
 
-	// Parse rpc auth after getting network for default .cookie location
-	let bitcoind_rpc_path: Vec<&str> = bitcoind_rpc_info_parts[0].split(":").collect();
-	if bitcoind_rpc_path.len() != 2 {
-		println!("ERROR: bad bitcoind RPC path provided");
-		return Err(());
+impl Config {
+	/// Overrides the value of `key` with the one given on the command line
+	fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+		match key {
+			"bitcoind_rpc_host" => self.bitcoind_rpc_host = Some(parse_value(key, value)?),
+			"bitcoind_rpc_port" => self.bitcoind_rpc_port = Some(parse_value(key, value)?),
+			"bitcoind_rpc_username" => self.bitcoind_rpc_username = Some(parse_value(key, value)?),
+			"bitcoind_rpc_password" => self.bitcoind_rpc_password = Some(parse_value(key, value)?),
+			"ldk_storage_dir_path" => self.ldk_storage_dir_path = Some(parse_value(key, value)?),
+			"rgb_node_port" => self.rgb_node_port = Some(parse_value(key, value)?),
+			"ldk_peer_listening_port" => {
+				self.ldk_peer_listening_port = Some(parse_value(key, value)?)
+			}
+			"network" => self.network = Some(parse_value(key, value)?),
+			"allow_mainnet" => {
+				self.allow_mainnet = value.is_empty() || parse_value::<bool>(key, value)?
+			}
+			"announced_node_name" => self.announced_node_name = Some(parse_value(key, value)?),
+			"announced_listen_addr" => self.announced_listen_addr = parse_list(key, value)?,
+			"rpc_port" => self.rpc_port = Some(parse_value(key, value)?),
+			"fee_rate" => self.fee_rate = Some(parse_value(key, value)?),
+			"utxo_size_sat" => self.utxo_size_sat = Some(parse_value(key, value)?),
+			"utxo_num" => self.utxo_num = Some(parse_value(key, value)?),
+			"min_uncolored_utxos" => self.min_uncolored_utxos = Some(parse_value(key, value)?),
+			"refresh_interval" => self.refresh_interval = Some(parse_value(key, value)?),
+			"ack_timeout" => self.ack_timeout = Some(parse_value(key, value)?),
+			"electrum_url" => self.electrum_url = Some(parse_value(key, value)?),
+			"electrum_retry" => self.electrum_retry = Some(parse_value(key, value)?),
+			"electrum_timeout" => self.electrum_timeout = Some(parse_value(key, value)?),
+			"electrum_stop_gap" => self.electrum_stop_gap = Some(parse_value(key, value)?),
+			"proxy_url" => self.proxy_url = Some(parse_value(key, value)?),
+			"proxy_timeout" => self.proxy_timeout = Some(parse_value(key, value)?),
+			"consignment_transport" => self.consignment_transport = Some(parse_value(key, value)?),
+			"consignment_dir" => self.consignment_dir = Some(parse_value(key, value)?),
+			// a bare `--manual-accept` flag turns manual acceptance on
+			"manual_accept" => {
+				self.manual_accept = value.is_empty() || parse_value::<bool>(key, value)?
+			}
+			"accept_peers" => self.accept_peers = parse_list(key, value)?,
+			"accept_contract_ids" => self.accept_contract_ids = parse_list(key, value)?,
+			"min_rgb_amount" => self.min_rgb_amount = Some(parse_value(key, value)?),
+			"max_rgb_amount" => self.max_rgb_amount = Some(parse_value(key, value)?),
+			"max_channels_per_peer" => self.max_channels_per_peer = Some(parse_value(key, value)?),
+			_ => return Err(format!("unknown option --{}", key.replace('_', "-"))),
+		}
+		Ok(())
 	}
-	let bitcoind_rpc_host = bitcoind_rpc_path[0].to_string();
-	let bitcoind_rpc_port = bitcoind_rpc_path[1].parse::<u16>().unwrap();
 
-	let ldk_storage_dir_path = env::args().skip(2).next().unwrap();
+	fn into_user_info(self) -> Result<LdkUserInfo, String> {
+		let network = match self.network.as_deref() {
+			Some("testnet") | None => Network::Testnet,
+			Some("regtest") => Network::Regtest,
+			Some("signet") => Network::Signet,
+			Some("bitcoin") | Some("mainnet") => {
+				if !self.allow_mainnet {
+					return Err("mainnet is disabled, set allow_mainnet to use it".to_string());
+				}
+				Network::Bitcoin
+			}
+			Some(net) => {
+				return Err(format!(
+					"unsupported network {}. Options are: `regtest`, `testnet`, `signet`, and \
+					`bitcoin`",
+					net
+				))
+			}
+		};
+
+		let (bitcoind_rpc_username, bitcoind_rpc_password) =
+			match (self.bitcoind_rpc_username, self.bitcoind_rpc_password) {
+				(Some(username), Some(password)) => (username, password),
+				(None, None) => get_rpc_auth_from_env_vars()
+					.or(get_rpc_auth_from_env_file(None))
+					.or(get_rpc_auth_from_cookie(None, Some(network), None))
+					.map_err(|_| {
+						print_rpc_auth_help();
+						"unable to get bitcoind RPC username and password".to_string()
+					})?,
+				_ => {
+					return Err("bitcoind_rpc_username and bitcoind_rpc_password must be set \
+						together"
+						.to_string())
+				}
+			};
 
-	let rgb_node_port = match env::args().skip(3).next().unwrap().parse::<u16>() {
-		Ok(p) => p,
-		Err(_) => {
-			println!("ERROR: rgb node port must be a number");
-			return Err(());
+		let ldk_announced_node_name = match self.announced_node_name {
+			Some(name) => {
+				if name.len() > 32 {
+					return Err("announced_node_name can not be longer than 32 bytes".to_string());
+				}
+				let mut bytes = [0; 32];
+				bytes[..name.len()].copy_from_slice(name.as_bytes());
+				bytes
+			}
+			None => [0; 32],
+		};
+
+		let ldk_peer_listening_port = self.ldk_peer_listening_port.unwrap_or(9735);
+		let mut ldk_announced_listen_addr = Vec::new();
+		for addr in self.announced_listen_addr {
+			match IpAddr::from_str(&addr) {
+				Ok(IpAddr::V4(a)) => ldk_announced_listen_addr
+					.push(NetAddress::IPv4 { addr: a.octets(), port: ldk_peer_listening_port }),
+				Ok(IpAddr::V6(a)) => ldk_announced_listen_addr
+					.push(NetAddress::IPv6 { addr: a.octets(), port: ldk_peer_listening_port }),
+				Err(_) => {
+					return Err(format!("announced_listen_addr {} is not an IP address", addr))
+				}
+			}
 		}
-	};
 
-	let mut ldk_peer_port_set = true;
-	let ldk_peer_listening_port: u16 = match env::args().skip(4).next().map(|p| p.parse()) {
-		Some(Ok(p)) => p,
-		Some(Err(_)) => {
-			ldk_peer_port_set = false;
-			9735
+		if let Some(fee_rate) = self.fee_rate {
+			if fee_rate.is_nan() || fee_rate < 1.0 {
+				return Err("fee_rate must be at least 1 sat/vB".to_string());
+			}
 		}
-		None => {
-			ldk_peer_port_set = false;
-			9735
+
+		if let Some(ack_timeout) = self.ack_timeout {
+			// recipients may only refresh this often while their transport is unreachable
+			if ack_timeout <= REFRESH_MAX_INTERVAL {
+				return Err(format!(
+					"ack_timeout must be longer than {} seconds",
+					REFRESH_MAX_INTERVAL
+				));
+			}
 		}
-	};
 
-	let mut arg_idx = match ldk_peer_port_set {
-		true => 5,
-		false => 4,
-	};
-	let network: Network = match env::args().skip(arg_idx).next().as_ref().map(String::as_str) {
-		Some("testnet") => Network::Testnet,
-		Some("regtest") => Network::Regtest,
-		Some("signet") => Network::Signet,
-		Some(net) => {
-			panic!("Unsupported network provided. Options are: `regtest`, `testnet`, and `signet`. Got {}", net);
+		let consignment_transport = match self.consignment_transport.as_deref() {
+			Some("proxy") | None => TransportConfig::Proxy,
+			Some("dir") => {
+				let dir = required(self.consignment_dir, "consignment_dir")?;
+				TransportConfig::Dir(PathBuf::from(dir))
+			}
+			Some("peer") => TransportConfig::Peer,
+			Some(transport) => {
+				return Err(format!(
+					"unsupported consignment_transport {}. Options are: `proxy`, `dir` and `peer`",
+					transport
+				))
+			}
+		};
+		// there is no default RGB proxy server on mainnet and signet
+		let has_default_proxy = matches!(network, Network::Regtest | Network::Testnet);
+		if consignment_transport == TransportConfig::Proxy
+			&& self.proxy_url.is_none()
+			&& !has_default_proxy
+		{
+			return Err(format!("proxy_url is required by the proxy transport on {}", network));
 		}
-		None => Network::Testnet,
-	};
 
-	let (bitcoind_rpc_username, bitcoind_rpc_password) = if bitcoind_rpc_info_parts.len() == 1 {
-		get_rpc_auth_from_env_vars()
-			.or(get_rpc_auth_from_env_file(None))
-			.or(get_rpc_auth_from_cookie(None, Some(network), None))
-			.or({
-				println!("ERROR: unable to get bitcoind RPC username and password");
-				print_rpc_auth_help();
-				Err(())
-			})?
-	} else if bitcoind_rpc_info_parts.len() == 2 {
-		parse_rpc_auth(bitcoind_rpc_info_parts[1])?
-	} else {
-		println!("ERROR: bad bitcoind RPC URL provided");
-		return Err(());
-	};
+		let mut channel_policy = ChannelPolicy {
+			manual_review: self.manual_accept,
+			min_rgb_amount: self.min_rgb_amount,
+			max_rgb_amount: self.max_rgb_amount,
+			max_channels_per_peer: self.max_channels_per_peer,
+			..Default::default()
+		};
+		for pubkey in self.accept_peers {
+			let pubkey = PublicKey::from_str(&pubkey)
+				.map_err(|_| format!("accept_peers contains an invalid pubkey: {}", pubkey))?;
+			channel_policy.allowed_peers.push(pubkey);
+		}
+		for contract_id in self.accept_contract_ids {
+			let contract_id = ContractId::from_str(&contract_id).map_err(|_| {
+				format!("accept_contract_ids contains an invalid contract ID: {}", contract_id)
+			})?;
+			channel_policy.allowed_contract_ids.push(contract_id);
+		}
 
-	let ldk_announced_node_name = match env::args().skip(arg_idx + 1).next().as_ref() {
-		Some(s) => {
-			if s.len() > 32 {
-				panic!("Node Alias can not be longer than 32 bytes");
-			}
-			arg_idx += 1;
-			let mut bytes = [0; 32];
-			bytes[..s.len()].copy_from_slice(s.as_bytes());
-			bytes
+		Ok(LdkUserInfo {
+			bitcoind_rpc_username,
+			bitcoind_rpc_password,
+			bitcoind_rpc_host: required(self.bitcoind_rpc_host, "bitcoind_rpc_host")?,
+			bitcoind_rpc_port: required(self.bitcoind_rpc_port, "bitcoind_rpc_port")?,
+			ldk_storage_dir_path: required(self.ldk_storage_dir_path, "ldk_storage_dir_path")?,
+			rgb_node_port: required(self.rgb_node_port, "rgb_node_port")?,
+			ldk_peer_listening_port,
+			ldk_announced_listen_addr,
+			ldk_announced_node_name,
+			network,
+			rpc_listening_port: self.rpc_port,
+			channel_policy,
+			fee_rate: self.fee_rate,
+			utxo_size_sat: self.utxo_size_sat,
+			utxo_num: self.utxo_num,
+			min_uncolored_utxos: self.min_uncolored_utxos,
+			refresh_interval: self.refresh_interval,
+			ack_timeout: self.ack_timeout,
+			electrum_url: self.electrum_url,
+			electrum_retry: self.electrum_retry,
+			electrum_timeout: self.electrum_timeout,
+			electrum_stop_gap: self.electrum_stop_gap,
+			proxy_url: self.proxy_url,
+			proxy_timeout: self.proxy_timeout,
+			consignment_transport,
+		})
+	}
+}
+
+pub(crate) fn parse_startup_args() -> Result<LdkUserInfo, ()> {
+	match load_config(env::args().skip(1)).and_then(Config::into_user_info) {
+		Ok(user_info) => Ok(user_info),
+		Err(e) => {
+			println!("ERROR: {}", e);
+			println!("rgb-lightning-sample usage: `cargo run -- [--config=<path>] [--<key>=<value>]*`. See `config.example.toml` for the available keys.");
+			Err(())
 		}
-		None => [0; 32],
-	};
+	}
+}
 
-	let mut ldk_announced_listen_addr = Vec::new();
-	loop {
-		match env::args().skip(arg_idx + 1).next().as_ref() {
-			Some(s) => match IpAddr::from_str(s) {
-				Ok(IpAddr::V4(a)) => {
-					ldk_announced_listen_addr
-						.push(NetAddress::IPv4 { addr: a.octets(), port: ldk_peer_listening_port });
-					arg_idx += 1;
-				}
-				Ok(IpAddr::V6(a)) => {
-					ldk_announced_listen_addr
-						.push(NetAddress::IPv6 { addr: a.octets(), port: ldk_peer_listening_port });
-					arg_idx += 1;
-				}
-				Err(_) => panic!("Failed to parse announced-listen-addr into an IP address"),
-			},
-			None => break,
+/// Reads the config file given with `--config`, if any, and applies the other flags on top
+fn load_config<I: Iterator<Item = String>>(args: I) -> Result<Config, String> {
+	let mut config_path = None;
+	let mut overrides = Vec::new();
+	for arg in args {
+		let flag = arg.strip_prefix("--").ok_or_else(|| format!("unexpected argument {}", arg))?;
+		let (key, value) = flag.split_once('=').unwrap_or((flag, ""));
+		if key == "config" {
+			config_path = Some(value.to_string());
+		} else {
+			overrides.push((key.replace('-', "_"), value.to_string()));
+		}
+	}
+
+	let mut config = match config_path {
+		Some(path) => {
+			let contents = fs::read_to_string(&path)
+				.map_err(|e| format!("unable to read config file {}: {}", path, e))?;
+			parse_config(&contents).map_err(|e| format!("invalid config file {}: {}", path, e))?
 		}
+		None => Config::default(),
+	};
+	for (key, value) in overrides {
+		config.set(&key, &value)?;
 	}
+	Ok(config)
+}
+
+fn parse_config(contents: &str) -> Result<Config, String> {
+	toml::from_str(contents).map_err(|e| e.to_string())
+}
 
-	Ok(LdkUserInfo {
-		bitcoind_rpc_username,
-		bitcoind_rpc_password,
-		bitcoind_rpc_host,
-		bitcoind_rpc_port,
-		ldk_storage_dir_path,
-		rgb_node_port,
-		ldk_peer_listening_port,
-		ldk_announced_listen_addr,
-		ldk_announced_node_name,
-		network,
-	})
+fn required<T>(value: Option<T>, key: &str) -> Result<T, String> {
+	value.ok_or_else(|| format!("missing required setting {}", key))
+}
+
+fn parse_value<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+	if value.is_empty() {
+		return Err(format!("--{} requires a value", key.replace('_', "-")));
+	}
+	value.parse().map_err(|_| format!("invalid value for --{}: {}", key.replace('_', "-"), value))
+}
+
+fn parse_list(key: &str, value: &str) -> Result<Vec<String>, String> {
+	if value.is_empty() {
+		return Err(format!("--{} requires a value", key.replace('_', "-")));
+	}
+	Ok(value.split(',').map(|v| v.to_string()).collect())
 }
 
 // Default datadir relative to home directory
@@ -148,8 +336,8 @@ fn print_rpc_auth_help() {
 	let data_dir = format!("{}/{}", home_dir, DEFAULT_BITCOIN_DATADIR);
 	println!("To provide the bitcoind RPC username and password, you can either:");
 	println!(
-		"1. Provide the username and password as the first argument to this program in the format: \
-		<bitcoind-rpc-username>:<bitcoind-rpc-password>@<bitcoind-rpc-host>:<bitcoind-rpc-port>"
+		"1. Set bitcoind_rpc_username and bitcoind_rpc_password in the config file or pass them \
+		with the --bitcoind-rpc-username and --bitcoind-rpc-password options"
 	);
 	println!("2. Provide <bitcoind-rpc-username>:<bitcoind-rpc-password> in a .cookie file in the default \
 		bitcoind data directory (automatically created by bitcoind on startup): `{}`", data_dir);
@@ -378,3 +566,179 @@ mod rpc_auth_tests {
 		assert_eq!(password, EXPECTED_PASSWORD);
 	}
 }
+
+#[cfg(test)]
+mod config_tests {
+	use super::*;
+
+	const TEST_CONFIG: &str = r#"
+		bitcoind_rpc_host = "localhost"
+		bitcoind_rpc_port = 18443
+		bitcoind_rpc_username = "user"
+		bitcoind_rpc_password = "password"
+		ldk_storage_dir_path = "dataldk0/"
+		rgb_node_port = 63963
+		network = "regtest"
+		announced_listen_addr = ["127.0.0.1"]
+		manual_accept = true
+	"#;
+
+	fn args(args: &[&str]) -> impl Iterator<Item = String> {
+		args.iter().map(|a| a.to_string()).collect::<Vec<_>>().into_iter()
+	}
+
+	#[test]
+	fn test_parse_config_success() {
+		let user_info = parse_config(TEST_CONFIG).unwrap().into_user_info().unwrap();
+		assert_eq!(user_info.bitcoind_rpc_host, "localhost");
+		assert_eq!(user_info.bitcoind_rpc_port, 18443);
+		assert_eq!(user_info.ldk_storage_dir_path, "dataldk0/");
+		assert_eq!(user_info.rgb_node_port, 63963);
+		assert_eq!(user_info.ldk_peer_listening_port, 9735);
+		assert_eq!(user_info.network, Network::Regtest);
+		assert_eq!(user_info.ldk_announced_listen_addr.len(), 1);
+		assert!(user_info.channel_policy.manual_review);
+		assert!(user_info.fee_rate.is_none());
+	}
+
+	#[test]
+	fn test_parse_example_config() {
+		let contents = fs::read_to_string("config.example.toml").unwrap();
+		assert!(parse_config(&contents).unwrap().into_user_info().is_ok());
+	}
+
+	#[test]
+	fn test_parse_test_config() {
+		let contents = fs::read_to_string("tests/config.toml").unwrap();
+		let user_info = parse_config(&contents).unwrap().into_user_info().unwrap();
+		assert_eq!(user_info.network, Network::Regtest);
+	}
+
+	#[test]
+	fn test_parse_config_unknown_key() {
+		assert!(parse_config("unknown_key = 1").is_err());
+	}
+
+	#[test]
+	fn test_config_overrides() {
+		let mut config = parse_config(TEST_CONFIG).unwrap();
+		for (key, value) in
+			[("rgb_node_port", "63964"), ("fee_rate", "2.5"), ("manual_accept", "false")]
+		{
+			config.set(key, value).unwrap();
+		}
+		let user_info = config.into_user_info().unwrap();
+		assert_eq!(user_info.rgb_node_port, 63964);
+		assert_eq!(user_info.fee_rate, Some(2.5));
+		assert!(!user_info.channel_policy.manual_review);
+	}
+
+	#[test]
+	fn test_load_config_flags_only() {
+		let config = load_config(args(&[
+			"--bitcoind-rpc-host=localhost",
+			"--bitcoind-rpc-port=18443",
+			"--bitcoind-rpc-username=user",
+			"--bitcoind-rpc-password=password",
+			"--ldk-storage-dir-path=dataldk0/",
+			"--rgb-node-port=63963",
+			"--manual-accept",
+		]))
+		.unwrap();
+		let user_info = config.into_user_info().unwrap();
+		assert_eq!(user_info.bitcoind_rpc_username, "user");
+		assert_eq!(user_info.network, Network::Testnet);
+		assert!(user_info.channel_policy.manual_review);
+	}
+
+	#[test]
+	fn test_load_config_fail() {
+		assert!(load_config(args(&["positional"])).is_err());
+		assert!(load_config(args(&["--unknown-option=1"])).is_err());
+		assert!(load_config(args(&["--rgb-node-port=port"])).is_err());
+		assert!(load_config(args(&["--config=nonexistent_file"])).is_err());
+	}
+
+	#[test]
+	fn test_allow_mainnet() {
+		let mut config = parse_config(TEST_CONFIG).unwrap();
+		config.set("network", "bitcoin").unwrap();
+		config.set("allow_mainnet", "").unwrap();
+		assert_eq!(config.into_user_info().unwrap().network, Network::Bitcoin);
+	}
+
+	#[test]
+	fn test_into_user_info_fail() {
+		let mut config = parse_config(TEST_CONFIG).unwrap();
+		config.set("network", "liquid").unwrap();
+		assert!(config.into_user_info().is_err());
+
+		let mut config = parse_config(TEST_CONFIG).unwrap();
+		config.set("network", "bitcoin").unwrap();
+		assert!(config.into_user_info().is_err());
+
+		let mut config = parse_config(TEST_CONFIG).unwrap();
+		config.rgb_node_port = None;
+		assert!(config.into_user_info().is_err());
+
+		let mut config = parse_config(TEST_CONFIG).unwrap();
+		config.set("fee_rate", "0.5").unwrap();
+		assert!(config.into_user_info().is_err());
+
+		let mut config = parse_config(TEST_CONFIG).unwrap();
+		config.set("consignment_transport", "email").unwrap();
+		assert!(config.into_user_info().is_err());
+
+		let mut config = parse_config(TEST_CONFIG).unwrap();
+		config.set("consignment_transport", "dir").unwrap();
+		assert!(config.into_user_info().is_err());
+	}
+
+	#[test]
+	fn test_consignment_transport() {
+		let user_info = parse_config(TEST_CONFIG).unwrap().into_user_info().unwrap();
+		assert_eq!(user_info.consignment_transport, TransportConfig::Proxy);
+
+		let mut config = parse_config(TEST_CONFIG).unwrap();
+		config.set("consignment_transport", "dir").unwrap();
+		config.set("consignment_dir", "consignments/").unwrap();
+		let user_info = config.into_user_info().unwrap();
+		assert_eq!(
+			user_info.consignment_transport,
+			TransportConfig::Dir(PathBuf::from("consignments/"))
+		);
+
+		let mut config = parse_config(TEST_CONFIG).unwrap();
+		config.set("consignment_transport", "peer").unwrap();
+		assert_eq!(config.into_user_info().unwrap().consignment_transport, TransportConfig::Peer);
+	}
+
+	#[test]
+	fn test_ack_timeout() {
+		let mut config = parse_config(TEST_CONFIG).unwrap();
+		config.set("ack_timeout", "600").unwrap();
+		assert!(config.into_user_info().is_err());
+
+		let mut config = parse_config(TEST_CONFIG).unwrap();
+		config.set("ack_timeout", "3600").unwrap();
+		assert_eq!(config.into_user_info().unwrap().ack_timeout, Some(3600));
+	}
+
+	#[test]
+	fn test_proxy_url_required() {
+		let mut config = parse_config(TEST_CONFIG).unwrap();
+		config.set("network", "signet").unwrap();
+		assert!(config.into_user_info().is_err());
+
+		let mut config = parse_config(TEST_CONFIG).unwrap();
+		config.set("network", "signet").unwrap();
+		config.set("proxy_url", "http://127.0.0.1:3000/json-rpc").unwrap();
+		assert!(config.into_user_info().is_ok());
+
+		let mut config = parse_config(TEST_CONFIG).unwrap();
+		config.set("network", "signet").unwrap();
+		config.set("consignment_transport", "peer").unwrap();
+		let user_info = config.into_user_info().unwrap();
+		assert!(user_info.proxy_url.is_none());
+	}
+}
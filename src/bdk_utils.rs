@@ -1,16 +1,22 @@
 use bdk::bitcoin::util::bip32::ExtendedPrivKey;
 use bdk::bitcoin::Network;
-use bdk::blockchain::Blockchain;
+use bdk::blockchain::{Blockchain, GetTx};
 use bdk::blockchain::{ConfigurableBlockchain, ElectrumBlockchain, ElectrumBlockchainConfig};
 use bdk::database::any::SqliteDbConfiguration;
 use bdk::database::{ConfigurableDatabase, SqliteDatabase};
 use bdk::template::P2Wpkh;
-use bdk::{SyncOptions, Wallet};
+use bdk::{FeeRate, SyncOptions, Wallet};
 use bitcoin::secp256k1::SecretKey;
-use bitcoin::{PrivateKey, Transaction};
+use bitcoin::{PrivateKey, Transaction, Txid};
+use lightning::chain::chaininterface::{ConfirmationTarget, FeeEstimator};
+
+use crate::bitcoind_client::BitcoindClient;
+use crate::error::Error;
+use crate::utxo_store::{Reconciliation, RgbUtxoStore};
+use crate::FEE_RATE;
 
 const DERIVATION_PATH_ACCOUNT: u32 = 0;
-const BDK_DB_NAME: &str = "bdk_db";
+pub(crate) const BDK_DB_NAME: &str = "bdk_db";
 
 pub(crate) fn calculate_descriptor_from_xprv(
 	xprv: ExtendedPrivKey, network: Network, change: bool,
@@ -28,6 +34,8 @@ pub(crate) fn calculate_descriptor_from_xprv(
 pub(crate) fn get_bdk_wallet(
 	ldk_data_dir: String, xprv: ExtendedPrivKey, network: Network,
 ) -> Wallet<SqliteDatabase> {
+	// the LDK master key is always created for testnet, use the wallet network instead
+	let xprv = ExtendedPrivKey { network, ..xprv };
 	let descriptor = calculate_descriptor_from_xprv(xprv, network, false);
 	let change_descriptor = calculate_descriptor_from_xprv(xprv, network, true);
 
@@ -51,28 +59,73 @@ pub(crate) fn get_bdk_wallet_seckey(
 	Wallet::new(P2Wpkh(priv_key), None, network, bdk_database).expect("valid bdk wallet")
 }
 
-pub(crate) fn broadcast_tx(tx: &Transaction, electrum_url: String) {
-	let config = ElectrumBlockchainConfig {
-		url: electrum_url,
-		socks5: None,
-		retry: 3,
-		timeout: Some(5),
-		stop_gap: 2000,
-		validate_domain: false,
-	};
-	let blockchain = ElectrumBlockchain::from_config(&config).expect("valid blockchain config");
-	blockchain.broadcast(tx).expect("able to broadcast");
+/// Connection settings for the Electrum server used by the BDK wallet
+#[derive(Clone, Debug)]
+pub(crate) struct ElectrumConfig {
+	pub(crate) url: String,
+	pub(crate) retry: u8,
+	pub(crate) timeout: u8,
+	pub(crate) stop_gap: usize,
 }
 
-pub(crate) fn sync_wallet(wallet: &Wallet<SqliteDatabase>, electrum_url: String) {
+fn get_blockchain(electrum_config: ElectrumConfig) -> ElectrumBlockchain {
 	let config = ElectrumBlockchainConfig {
-		url: electrum_url,
+		url: electrum_config.url,
 		socks5: None,
-		retry: 3,
-		timeout: Some(5),
-		stop_gap: 20,
+		retry: electrum_config.retry,
+		timeout: Some(electrum_config.timeout),
+		stop_gap: electrum_config.stop_gap,
 		validate_domain: false,
 	};
-	let blockchain = ElectrumBlockchain::from_config(&config).expect("valid blockchain config");
+	ElectrumBlockchain::from_config(&config).expect("valid blockchain config")
+}
+
+pub(crate) fn broadcast_tx(tx: &Transaction, electrum_config: ElectrumConfig) -> Result<(), Error> {
+	let blockchain = get_blockchain(electrum_config);
+	blockchain.broadcast(tx).map_err(|e| Error::FailedBroadcast(e.to_string()))
+}
+
+/// The transaction with `txid`, if the Electrum server has seen it
+pub(crate) fn get_tx(
+	txid: &Txid, electrum_config: ElectrumConfig,
+) -> Result<Option<Transaction>, Error> {
+	let blockchain = get_blockchain(electrum_config);
+	blockchain.get_tx(txid).map_err(|e| Error::FailedGetTx(e.to_string()))
+}
+
+pub(crate) fn sync_wallet(wallet: &Wallet<SqliteDatabase>, electrum_config: ElectrumConfig) {
+	let blockchain = get_blockchain(electrum_config);
 	wallet.sync(&blockchain, SyncOptions { progress: None }).expect("successful sync")
 }
+
+/// Syncs the wallet, then reconciles the tracked RGB UTXOs with it
+pub(crate) fn sync_wallet_and_utxos(
+	wallet: &Wallet<SqliteDatabase>, electrum_config: ElectrumConfig, rgb_utxos: &RgbUtxoStore,
+) -> Reconciliation {
+	sync_wallet(wallet, electrum_config);
+	let reconciliation = rgb_utxos.reconcile(wallet);
+	for outpoint in &reconciliation.lost {
+		println!(
+			"WARNING: RGB allocations on {} were lost, the UTXO was spent by a non-RGB transaction",
+			outpoint
+		);
+	}
+	reconciliation
+}
+
+/// Fee rate of wallet transactions: the configured one if set, otherwise the bitcoind estimate
+/// for `target`. Regtest has no estimates, so it falls back to `FEE_RATE` there
+pub(crate) fn get_fee_rate(
+	bitcoind_client: &BitcoindClient, network: Network, fee_rate: Option<f32>,
+	target: ConfirmationTarget,
+) -> FeeRate {
+	if let Some(fee_rate) = fee_rate {
+		return FeeRate::from_sat_per_vb(fee_rate);
+	}
+	if network == Network::Regtest {
+		return FeeRate::from_sat_per_vb(FEE_RATE);
+	}
+	// 1 vbyte is 4 weight units
+	let sat_per_kw = bitcoind_client.get_est_sat_per_1000_weight(target);
+	FeeRate::from_sat_per_vb(sat_per_kw as f32 * 4.0 / 1000.0)
+}
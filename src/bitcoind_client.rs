@@ -16,9 +16,12 @@ use serde_json;
 use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+/// Called with each transaction LDK broadcasts, before it's sent to bitcoind
+pub(crate) type BroadcastHook = Box<dyn Fn(&Transaction) + Send + Sync>;
+
 pub struct BitcoindClient {
 	bitcoind_rpc_client: Arc<RpcClient>,
 	host: String,
@@ -28,6 +31,7 @@ pub struct BitcoindClient {
 	fees: Arc<HashMap<Target, AtomicU32>>,
 	handle: tokio::runtime::Handle,
 	logger: Arc<FilesystemLogger>,
+	broadcast_hook: Mutex<Option<BroadcastHook>>,
 }
 
 #[derive(Clone, Eq, Hash, PartialEq)]
@@ -87,6 +91,7 @@ impl BitcoindClient {
 			fees: Arc::new(fees),
 			handle: handle.clone(),
 			logger,
+			broadcast_hook: Mutex::new(None),
 		};
 		BitcoindClient::poll_for_fee_estimates(
 			client.fees.clone(),
@@ -96,6 +101,11 @@ impl BitcoindClient {
 		Ok(client)
 	}
 
+	/// Sets the hook called with the transactions LDK broadcasts
+	pub(crate) fn set_broadcast_hook(&self, hook: BroadcastHook) {
+		*self.broadcast_hook.lock().unwrap() = Some(hook);
+	}
+
 	fn poll_for_fee_estimates(
 		fees: Arc<HashMap<Target, AtomicU32>>, rpc_client: Arc<RpcClient>,
 		handle: tokio::runtime::Handle,
@@ -215,6 +225,9 @@ impl FeeEstimator for BitcoindClient {
 
 impl BroadcasterInterface for BitcoindClient {
 	fn broadcast_transaction(&self, tx: &Transaction) {
+		if let Some(hook) = &*self.broadcast_hook.lock().unwrap() {
+			hook(tx);
+		}
 		let bitcoind_rpc_client = self.bitcoind_rpc_client.clone();
 		let tx_serialized = encode::serialize_hex(tx);
 		let tx_json = serde_json::json!(tx_serialized);
@@ -1,92 +1,26 @@
-use crate::bdk_utils::sync_wallet;
-use crate::bitcoind_client::BitcoindClient;
-use crate::broadcast_tx;
-use crate::disk;
+use crate::api::{
+	self, AssetBalanceRequest, AssetBalances, AssetInfo, AssetInfoRequest, AssetRecipient,
+	CancelReceiveRequest, ChannelIdRequest, ConnectPeerRequest, CreateUtxosRequest,
+	DisconnectPeerRequest, GetInvoiceRequest, InvoiceStatusRequest, IssueAssetRequest,
+	KeysendRequest, MineRequest, NodeContext, OpenChannelRequest, PendingChannelRequest,
+	ReceiveAssetRequest, SendAssetRequest, SendOnionMessageRequest, SendPaymentRequest,
+	SignMessageRequest,
+};
 use crate::error::Error;
 use crate::hex_utils;
-use crate::proxy::{get_consignment, post_consignment};
-use crate::rgb_utils::get_asset_owned_values;
-use crate::rgb_utils::get_rgb_total_amount;
-use crate::rgb_utils::RgbUtilities;
-use crate::seal::Revealed;
-use crate::{
-	ChannelManager, HTLCStatus, MillisatAmount, NetworkGraph, OnionMessenger, PaymentInfo,
-	PaymentInfoStorage, PeerManager,
-};
-use crate::{FEE_RATE, UTXO_SIZE_SAT};
-use amplify::bmap;
-use bdk::bitcoin::hashes::Hash;
-use bdk::bitcoin::OutPoint;
-use bdk::database::SqliteDatabase;
-use bdk::{FeeRate, SignOptions, Wallet};
-use bitcoin::hashes::sha256::Hash as Sha256;
+use crate::policy::ChannelPolicy;
+use crate::rgb_utils::{format_asset_amount, parse_asset_amount};
+use crate::transport::TransportConfig;
+use crate::{MillisatAmount, PeerManager};
 use bitcoin::network::constants::Network;
 use bitcoin::secp256k1::PublicKey;
-use bp::seals::txout::ExplicitSeal;
-use bp::seals::txout::{blind::ConcealedSeal, CloseMethod};
-use invoice::ConsignmentEndpoint;
-use lightning::chain::keysinterface::{EntropySource, KeysManager};
-use lightning::ln::channelmanager::{PaymentId, RecipientOnionFields, Retry};
 use lightning::ln::msgs::NetAddress;
-use lightning::ln::{PaymentHash, PaymentPreimage};
-use lightning::onion_message::{CustomOnionMessageContents, Destination, OnionMessageContents};
-use lightning::rgb_utils::write_rgb_payment_info_file;
-use lightning::rgb_utils::{
-	get_rgb_channel_info, write_rgb_channel_info, RgbInfo, RgbUtxo, RgbUtxos,
-};
-use lightning::routing::gossip::NodeId;
-use lightning::routing::router::{PaymentParameters, RouteParameters};
-use lightning::util::config::{ChannelHandshakeConfig, ChannelHandshakeLimits, UserConfig};
-use lightning::util::ser::{Writeable, Writer};
-use lightning_invoice::payment::pay_invoice;
-use lightning_invoice::{utils, Currency, Invoice};
-use reqwest::Client as RestClient;
-use rgb::fungible::allocation::AllocatedValue;
-use rgb::Contract;
-use rgb::ContractId;
-use rgb::EndpointValueMap;
-use rgb::SealEndpoint;
-use rgb::{seal, StateTransfer};
-use rgb_rpc::Client;
-use rgb_rpc::ContractValidity;
-use rgb_rpc::Reveal;
-use serde::{Deserialize, Serialize};
-use std::collections::BTreeSet;
-use std::env;
-use std::fs;
+use serde::Serialize;
 use std::io;
 use std::io::Write;
-use std::iter::FromIterator;
 use std::net::{SocketAddr, ToSocketAddrs};
-use std::ops::Deref;
-use std::path::Path;
-use std::path::PathBuf;
-use std::str::FromStr;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 use std::time::Duration;
-use stens::AsciiString;
-use strict_encoding::strict_deserialize;
-use strict_encoding::strict_serialize;
-use strict_encoding::StrictEncode;
-
-const MIN_CREATE_UTXOS_SATS: u64 = 10000;
-const UTXO_NUM: u8 = 10;
-
-const OPENCHANNEL_MIN_SAT: u64 = 5000;
-const OPENCHANNEL_MAX_SAT: u64 = 16777215;
-
-const DUST_LIMIT_MSAT: u64 = 546000;
-
-const HTLC_MIN_MSAT: u64 = 3000000;
-
-const INVOICE_MIN_MSAT: u64 = HTLC_MIN_MSAT;
-
-#[derive(Serialize, Deserialize)]
-struct BlindedInfo {
-	contract_id: Option<ContractId>,
-	seal: seal::Revealed,
-	consumed: bool,
-}
 
 pub(crate) struct LdkUserInfo {
 	pub(crate) bitcoind_rpc_username: String,
@@ -99,39 +33,29 @@ pub(crate) struct LdkUserInfo {
 	pub(crate) ldk_announced_listen_addr: Vec<NetAddress>,
 	pub(crate) ldk_announced_node_name: [u8; 32],
 	pub(crate) network: Network,
+	pub(crate) rpc_listening_port: Option<u16>,
+	pub(crate) channel_policy: ChannelPolicy,
+	pub(crate) fee_rate: Option<f32>,
+	pub(crate) utxo_size_sat: Option<u64>,
+	pub(crate) utxo_num: Option<u8>,
+	pub(crate) min_uncolored_utxos: Option<u8>,
+	pub(crate) refresh_interval: Option<u64>,
+	pub(crate) ack_timeout: Option<u64>,
+	pub(crate) electrum_url: Option<String>,
+	pub(crate) electrum_retry: Option<u8>,
+	pub(crate) electrum_timeout: Option<u8>,
+	pub(crate) electrum_stop_gap: Option<usize>,
+	pub(crate) proxy_url: Option<String>,
+	pub(crate) proxy_timeout: Option<u64>,
+	pub(crate) consignment_transport: TransportConfig,
 }
 
-struct UserOnionMessageContents {
-	tlv_type: u64,
-	data: Vec<u8>,
-}
-
-impl CustomOnionMessageContents for UserOnionMessageContents {
-	fn tlv_type(&self) -> u64 {
-		self.tlv_type
-	}
-}
-
-impl Writeable for UserOnionMessageContents {
-	fn write<W: Writer>(&self, w: &mut W) -> Result<(), std::io::Error> {
-		w.write_all(&self.data)
-	}
-}
-
-pub(crate) async fn poll_for_user_input(
-	peer_manager: Arc<PeerManager>, channel_manager: Arc<ChannelManager>,
-	keys_manager: Arc<KeysManager>, network_graph: Arc<NetworkGraph>,
-	onion_messenger: Arc<OnionMessenger>, inbound_payments: PaymentInfoStorage,
-	outbound_payments: PaymentInfoStorage, ldk_data_dir: String, network: Network,
-	logger: Arc<disk::FilesystemLogger>, bitcoind_client: Arc<BitcoindClient>,
-	rgb_node_client: Arc<Mutex<Client>>, proxy_client: Arc<RestClient>, proxy_url: &str,
-	wallet_arc: Arc<Mutex<Wallet<SqliteDatabase>>>, electrum_url: String,
-) {
+pub(crate) async fn poll_for_user_input(ctx: Arc<NodeContext>) {
 	println!(
 		"LDK startup successful. Enter \"help\" to view available commands. Press Ctrl-D to quit."
 	);
 	println!("LDK logs are available at <your-supplied-ldk-data-dir-path>/.ldk/logs");
-	println!("Local Node ID is {}", channel_manager.get_our_node_id());
+	println!("Local Node ID is {}", ctx.channel_manager.get_our_node_id());
 	loop {
 		print!("> ");
 		io::stdout().flush().unwrap(); // Without flushing, the `>` doesn't print
@@ -150,10 +74,6 @@ pub(crate) async fn poll_for_user_input(
 			match word {
 				"help" => help(),
 				"mine" => {
-					if network != Network::Regtest {
-						println!("ERROR: mine command is available only on regtest");
-						continue;
-					}
 					let num_blocks = words.next();
 
 					if num_blocks.is_none() {
@@ -167,88 +87,85 @@ pub(crate) async fn poll_for_user_input(
 						continue;
 					}
 
-					mine(&bitcoind_client, num_blocks.unwrap()).await;
-				}
-				"listunspent" => {
-					let wallet = wallet_arc.lock().unwrap();
-					let unspents = wallet.list_unspent().expect("unspents");
-					println!("Unspents:");
-					for unspent in unspents {
-						println!(" - {unspent:?}");
+					if let Err(e) =
+						api::mine(&ctx, MineRequest { num_blocks: num_blocks.unwrap() }).await
+					{
+						println!("{e}");
 					}
 				}
-				"getaddress" => {
-					let wallet = wallet_arc.lock().unwrap();
-					let address = wallet
-						.get_address(bdk::wallet::AddressIndex::New)
-						.expect("valid address")
-						.address;
-					println!("Address: {address}");
-				}
+				"listunspent" => match api::list_unspent(&ctx) {
+					Ok(res) => {
+						println!("Unspents:");
+						for unspent in res.unspents {
+							println!(
+								" - outpoint: {}, amount: {} sat, spent: {}",
+								unspent.outpoint, unspent.amount_sat, unspent.is_spent
+							);
+						}
+					}
+					Err(e) => println!("{e}"),
+				},
+				"listrgbutxos" => match api::list_rgb_utxos(&ctx) {
+					Ok(res) if words.next() == Some("--json") => print_json(&res),
+					Ok(res) => {
+						println!("RGB UTXOs:");
+						for rgb_utxo in res.rgb_utxos {
+							println!(
+								" - outpoint: {}, status: {}",
+								rgb_utxo.outpoint, rgb_utxo.status
+							);
+						}
+					}
+					Err(e) => println!("{e}"),
+				},
+				"checkutxos" => match api::check_utxos(&ctx) {
+					Ok(res) if res.removed.is_empty() => println!("RGB UTXOs are up to date"),
+					Ok(res) => {
+						println!("Removed RGB UTXOs:");
+						for outpoint in res.removed {
+							let lost = res.lost.contains(&outpoint);
+							println!(" - outpoint: {}, RGB allocations lost: {}", outpoint, lost);
+						}
+					}
+					Err(e) => println!("{e}"),
+				},
+				"getaddress" => match api::get_address(&ctx) {
+					Ok(res) => println!("Address: {}", res.address),
+					Err(e) => println!("{e}"),
+				},
 				"createutxos" => {
-					let wallet = wallet_arc.lock().unwrap();
-					sync_wallet(&wallet, electrum_url.clone());
-
-					let rgb_utxos_path = format!("{}/rgb_utxos", ldk_data_dir.clone());
-					let serialized_utxos =
-						fs::read_to_string(&rgb_utxos_path).expect("able to read rgb utxos file");
-					let mut rgb_utxos: RgbUtxos =
-						serde_json::from_str(&serialized_utxos).expect("valid rgb utxos");
-					let unspendable_utxos: Vec<OutPoint> =
-						rgb_utxos.utxos.iter().map(|u| u.outpoint).collect();
-
-					let unspendable_amt: u64 = wallet
-						.list_unspent()
-						.expect("unspents")
-						.iter()
-						.filter(|u| unspendable_utxos.contains(&u.outpoint))
-						.map(|u| u.txout.value)
-						.sum();
-					let available =
-						wallet.get_balance().expect("wallet balance").get_total() - unspendable_amt;
-					if available < MIN_CREATE_UTXOS_SATS {
-						println!(
-							"ERROR: not enough funds, call getaddress and send {} satoshis",
-							MIN_CREATE_UTXOS_SATS - available
-						);
-						continue;
+					let mut req = CreateUtxosRequest::default();
+					let mut args = vec![];
+					for arg in words {
+						match arg {
+							"--up-to" => req.up_to = true,
+							_ => args.push(arg),
+						}
 					}
 
-					let mut tx_builder = wallet.build_tx();
-					tx_builder
-						.unspendable(unspendable_utxos)
-						.fee_rate(FeeRate::from_sat_per_vb(FEE_RATE))
-						.ordering(bdk::wallet::tx_builder::TxOrdering::Untouched);
-					for _i in 0..UTXO_NUM {
-						tx_builder.add_recipient(
-							wallet
-								.get_address(bdk::wallet::AddressIndex::New)
-								.expect("address")
-								.script_pubkey(),
-							UTXO_SIZE_SAT,
-						);
+					if let Some(num) = args.first() {
+						match num.parse() {
+							Ok(num) => req.num = Some(num),
+							Err(_) => {
+								println!("ERROR: num must be a number between 1 and 255");
+								continue;
+							}
+						}
 					}
-					let (mut psbt, _details) =
-						tx_builder.finish().expect("successful psbt creation");
-
-					wallet.sign(&mut psbt, SignOptions::default()).expect("successful sign");
-
-					let tx = psbt.extract_tx();
-					broadcast_tx(&tx, electrum_url.clone());
-
-					for i in 0..UTXO_NUM {
-						rgb_utxos.utxos.push(RgbUtxo {
-							outpoint: OutPoint { txid: tx.txid(), vout: i as u32 },
-							colored: false,
-						});
+					if let Some(size_sat) = args.get(1) {
+						match size_sat.parse() {
+							Ok(size_sat) => req.size_sat = Some(size_sat),
+							Err(_) => {
+								println!("ERROR: size_sat must be a number");
+								continue;
+							}
+						}
 					}
-					let serialized_utxos =
-						serde_json::to_string(&rgb_utxos).expect("valid rgb utxos");
-					fs::write(rgb_utxos_path, serialized_utxos)
-						.expect("able to write rgb utxos file");
 
-					sync_wallet(&wallet, electrum_url.clone());
-					println!("UTXO creation complete");
+					match api::create_utxos(&ctx, req) {
+						Ok(_) => println!("UTXO creation complete"),
+						Err(e) => println!("{e}"),
+					}
 				}
 				"issueasset" => {
 					let amount = words.next();
@@ -262,459 +179,264 @@ pub(crate) async fn poll_for_user_input(
 						continue;
 					}
 
-					let amount: Result<u64, _> = amount.unwrap().parse();
-					if amount.is_err() {
-						println!("ERROR: amount must be a number");
-						continue;
-					}
-
-					let ticker = AsciiString::from_str(ticker.unwrap());
-					if ticker.is_err() {
-						println!("ERROR: ticker must be an ASCII string");
-						continue;
-					}
-
-					let name = AsciiString::from_str(name.unwrap());
-					if name.is_err() {
-						println!("ERROR: name must be an ASCII string");
-						continue;
-					}
-
 					let precision: Result<u8, _> = precision.unwrap().parse();
 					if precision.is_err() {
 						println!("ERROR: precision must be a number");
 						continue;
 					}
+					let precision = precision.unwrap();
 
-					match check_uncolored_utxos(&ldk_data_dir).await {
-						Ok(_) => {}
+					// in the asset's units, like the amounts of the other commands
+					let amount = match parse_asset_amount(amount.unwrap(), precision) {
+						Ok(amount) => amount,
 						Err(e) => {
 							println!("{e}");
 							continue;
 						}
-					}
-					let outpoint = get_utxo(&ldk_data_dir).await.outpoint;
-					let rgb_utxos_path = format!("{}/rgb_utxos", ldk_data_dir.clone());
-					let serialized_utxos =
-						fs::read_to_string(&rgb_utxos_path).expect("able to read rgb utxos file");
-					let mut rgb_utxos: RgbUtxos =
-						serde_json::from_str(&serialized_utxos).expect("valid rgb utxos");
-					rgb_utxos
-						.utxos
-						.iter_mut()
-						.find(|u| u.outpoint == outpoint)
-						.expect("UTXO found")
-						.colored = true;
-					let serialized_utxos =
-						serde_json::to_string(&rgb_utxos).expect("valid rgb utxos");
-					fs::write(rgb_utxos_path, serialized_utxos)
-						.expect("able to write rgb utxos file");
+					};
 
-					let contract_id = rgb_node_client.lock().unwrap().issue_contract(
-						amount.unwrap(),
-						outpoint,
-						ticker.unwrap(),
-						name.unwrap(),
-						precision.unwrap(),
-					);
-					println!("Asset ID: {contract_id}");
+					let req = IssueAssetRequest {
+						amount,
+						ticker: ticker.unwrap().to_string(),
+						name: name.unwrap().to_string(),
+						precision,
+					};
+					match api::issue_asset(&ctx, req) {
+						Ok(res) => println!("Asset ID: {}", res.contract_id),
+						Err(e) => println!("{e}"),
+					}
 				}
 				"assetbalance" => {
 					let assetbalance_cmd = "`assetbalance <contract_id>`";
 					let contract_id = words.next();
 
 					if contract_id.is_none() {
-						println!("ERROR: sendasset has 1 required argument: `{assetbalance_cmd}`");
-						continue;
-					}
-
-					let contract_id = ContractId::from_str(contract_id.unwrap());
-					if contract_id.is_err() {
-						println!("ERROR: contract_id must be a valid RGB asset ID");
+						println!("ERROR: assetbalance has 1 required argument: {assetbalance_cmd}");
 						continue;
 					}
 
-					let total_rgb_amount = match get_rgb_total_amount(
-						contract_id.unwrap(),
-						rgb_node_client.clone(),
-						wallet_arc.clone(),
-						electrum_url.clone(),
-					) {
-						Ok(a) => a,
-						Err(e) => {
-							println!("{e}");
-							continue;
+					let req = AssetBalanceRequest { contract_id: contract_id.unwrap().to_string() };
+					match api::asset_balance(&ctx, req) {
+						Ok(res) => {
+							let precision = match api::asset_precision(&ctx, contract_id.unwrap()) {
+								Ok(precision) => precision,
+								Err(e) => {
+									println!("{e}");
+									continue;
+								}
+							};
+							println!(
+								"Asset balance: {}",
+								format_asset_amount(res.amount, precision)
+							);
+							println!("\t{{");
+							print_asset_balances(&res.balance, precision);
+							println!("\t}},");
 						}
-					};
-
-					println!("Asset balance: {total_rgb_amount}");
+						Err(e) => println!("{e}"),
+					}
 				}
-				"sendasset" => {
-					let sendasset_cmd = "`sendasset <contract_id> <amt_rgb> <blinded_utxo>`";
+				"listassets" => list_assets(&ctx, words.next() == Some("--json")),
+				"assetinfo" => {
+					let assetinfo_cmd = "`assetinfo <contract_id> [--json]`";
 					let contract_id = words.next();
-					let amt_rgb_str = words.next();
-					let blinded_utxo = words.next();
 
-					if contract_id.is_none() || amt_rgb_str.is_none() || blinded_utxo.is_none() {
-						println!("ERROR: sendasset has 3 required arguments: `{sendasset_cmd}`");
+					if contract_id.is_none() {
+						println!("ERROR: assetinfo has 1 required argument: {assetinfo_cmd}");
 						continue;
 					}
 
-					let contract_id = ContractId::from_str(contract_id.unwrap());
-					if contract_id.is_err() {
-						println!("ERROR: contract_id must be a valid RGB asset ID");
-						continue;
+					let req = AssetInfoRequest { contract_id: contract_id.unwrap().to_string() };
+					match api::asset_info(&ctx, req) {
+						Ok(res) if words.next() == Some("--json") => print_json(&res),
+						Ok(res) => {
+							println!("[");
+							print_asset_info(&res);
+							println!("]");
+						}
+						Err(e) => println!("{e}"),
 					}
-					let contract_id = contract_id.unwrap();
+				}
+				"sendasset" => {
+					let sendasset_cmd = "`sendasset <contract_id> <amt_rgb> <recipient>[@<node_id>]` or `sendasset <contract_id> <recipient>[@<node_id>]:<amt_rgb> [<recipient>[@<node_id>]:<amt_rgb> ...]`";
+					let contract_id = words.next();
+					let args: Vec<&str> = words.by_ref().collect();
 
-					let amt_rgb: u64 = match amt_rgb_str.unwrap().parse() {
-						Ok(amt) => amt,
-						Err(e) => {
-							println!("ERROR: couldn't parse amt_rgb: {e}");
-							continue;
+					let recipient_args: Option<Vec<(&str, &str)>> = match args.as_slice() {
+						[] => None,
+						[amt_rgb, recipient] if !amt_rgb.contains(':') => {
+							Some(vec![(*recipient, *amt_rgb)])
 						}
+						// witness recipients contain colons too, the amount is the last field
+						pairs => pairs.iter().map(|p| p.rsplit_once(':')).collect(),
 					};
+					if contract_id.is_none() || recipient_args.is_none() {
+						println!("ERROR: sendasset requires a contract ID and at least one recipient: {sendasset_cmd}");
+						continue;
+					}
 
-					let total_rgb_amount = match get_rgb_total_amount(
-						contract_id,
-						rgb_node_client.clone(),
-						wallet_arc.clone(),
-						electrum_url.clone(),
-					) {
-						Ok(a) => a,
+					let recipients: Result<Vec<AssetRecipient>, _> = recipient_args
+						.unwrap()
+						.into_iter()
+						.map(|(recipient, amt_rgb)| {
+							// the peer transport needs the recipient's node: <recipient>@<node_id>
+							let (recipient, node_id) = match recipient.split_once('@') {
+								Some((recipient, node_id)) => {
+									(recipient, Some(node_id.to_string()))
+								}
+								None => (recipient, None),
+							};
+							parse_rgb_amount(&ctx, contract_id.unwrap(), amt_rgb).map(|amount| {
+								AssetRecipient { recipient: recipient.to_string(), amount, node_id }
+							})
+						})
+						.collect();
+					let recipients = match recipients {
+						Ok(recipients) => recipients,
 						Err(e) => {
 							println!("{e}");
 							continue;
 						}
 					};
 
-					if amt_rgb > total_rgb_amount {
-						println!("ERROR: do not have enough RGB assets");
-						continue;
+					let req = SendAssetRequest {
+						contract_id: contract_id.unwrap().to_string(),
+						recipients,
+					};
+					match api::send_asset(&ctx, req).await {
+						Ok(res) => println!("RGB send complete, txid: {}", res.txid),
+						Err(e) => println!("{e}"),
 					}
-
-					let asset_owned_values = get_asset_owned_values(
-						contract_id,
-						rgb_node_client.clone(),
-						wallet_arc.clone(),
-						electrum_url.clone(),
-					)
-					.expect("known contract");
-					let mut rgb_inputs: Vec<OutPoint> = vec![];
-					let mut input_amount: u64 = 0;
-					for owned_value in asset_owned_values {
-						if input_amount >= amt_rgb {
-							break;
+				}
+				"receiveasset" => {
+					let receiveasset_cmd = "`receiveasset [--witness] [--contract-id=<contract_id> [--amount=<amt_rgb>]] [--expiry-secs=<secs>]`";
+					let mut req = ReceiveAssetRequest::default();
+					let mut amt_rgb = None;
+					let mut valid = true;
+					for arg in words.by_ref() {
+						match arg.split_once('=') {
+							None if arg == "--witness" => req.witness = true,
+							Some(("--contract-id", contract_id)) => {
+								req.contract_id = Some(contract_id.to_string())
+							}
+							Some(("--amount", amt)) => amt_rgb = Some(amt),
+							Some(("--expiry-secs", secs)) if secs.parse::<u64>().is_ok() => {
+								req.expiry_secs = secs.parse().ok()
+							}
+							_ => valid = false,
 						}
-						let outpoint =
-							OutPoint { txid: owned_value.seal.txid, vout: owned_value.seal.vout };
-						rgb_inputs.push(outpoint);
-						input_amount += owned_value.state.value
 					}
-
-					let wallet = wallet_arc.lock().unwrap();
-
-					let rgb_change_amount = input_amount - amt_rgb;
-					let rgb_change: Vec<AllocatedValue> = if rgb_change_amount > 0 {
-						match check_uncolored_utxos(&ldk_data_dir).await {
-							Ok(_) => {}
+					if !valid || (amt_rgb.is_some() && req.contract_id.is_none()) {
+						println!("ERROR: invalid receiveasset arguments: {receiveasset_cmd}");
+						continue;
+					}
+					if let (Some(contract_id), Some(amt)) = (&req.contract_id, amt_rgb) {
+						match parse_rgb_amount(&ctx, contract_id, amt) {
+							Ok(amt) => req.amount = Some(amt),
 							Err(e) => {
 								println!("{e}");
 								continue;
 							}
 						}
-						let rgb_change_outpoint = get_utxo(&ldk_data_dir).await.outpoint;
-						let rgb_utxos_path = format!("{}/rgb_utxos", ldk_data_dir.clone());
-						let serialized_utxos = fs::read_to_string(&rgb_utxos_path)
-							.expect("able to read rgb utxos file");
-						let mut rgb_utxos: RgbUtxos =
-							serde_json::from_str(&serialized_utxos).expect("valid rgb utxos");
-						rgb_utxos
-							.utxos
-							.iter_mut()
-							.find(|u| u.outpoint == rgb_change_outpoint)
-							.expect("UTXO found")
-							.colored = true;
-						let serialized_utxos =
-							serde_json::to_string(&rgb_utxos).expect("valid rgb utxos");
-						fs::write(rgb_utxos_path, serialized_utxos)
-							.expect("able to write rgb utxos file");
-						vec![AllocatedValue {
-							value: rgb_change_amount,
-							seal: ExplicitSeal::from_str(&format!(
-								"opret1st:{rgb_change_outpoint}"
-							))
-							.expect("valid explicit seal"),
-						}]
-					} else {
-						vec![]
-					};
-
-					let btc_outpoints =
-						rgb_inputs.iter().map(|o| OutPoint { txid: o.txid, vout: o.vout });
-					let inputs: BTreeSet<OutPoint> = FromIterator::from_iter(btc_outpoints);
-
-					let mut builder = wallet.build_tx();
-					let address = wallet
-						.get_address(bdk::wallet::AddressIndex::New)
-						.expect("valid address")
-						.address;
-					builder
-						.add_utxos(&rgb_inputs)
-						.expect("valid utxos")
-						.fee_rate(FeeRate::from_sat_per_vb(FEE_RATE))
-						.manually_selected_only()
-						.drain_to(address.script_pubkey());
-					let psbt = builder.finish().expect("valid psbt finish").0;
-
-					let blinded_utxo = blinded_utxo.unwrap();
-					let concealed_seal = ConcealedSeal::from_str(blinded_utxo);
-					if concealed_seal.is_err() {
-						println!("ERROR: blinded_utxo must be a valid RGB blinded UTXO");
-						continue;
 					}
-					let concealed_seal = concealed_seal.unwrap();
-					let beneficiaries: EndpointValueMap = bmap![
-						SealEndpoint::ConcealedUtxo(concealed_seal) => amt_rgb
-					];
-
-					let mut rgb_client = rgb_node_client.lock().unwrap();
-
-					let (mut psbt, consignment) =
-						rgb_client.send_rgb(contract_id, psbt, inputs, beneficiaries, rgb_change);
-
-					let consignment_path = format!("{}/consignment", ldk_data_dir.clone());
-					consignment
-						.strict_file_save(consignment_path.clone())
-						.expect("consignment save ok");
-
-					let proxy_ref = (*proxy_client).clone();
-					let res = post_consignment(
-						proxy_ref,
-						proxy_url,
-						blinded_utxo.to_string(),
-						consignment_path.into(),
-					)
-					.await;
-					if res.is_err() || res.unwrap().result.is_none() {
-						println!("ERROR: unable to post consignment");
-						continue;
+					let witness = req.witness;
+					match api::receive_asset(&ctx, req) {
+						Ok(res) if witness => println!("Witness recipient: {}", res.recipient),
+						Ok(res) => println!("Blinded UTXO: {}", res.recipient),
+						Err(e) => println!("{e}"),
 					}
-
-					wallet.sign(&mut psbt, SignOptions::default()).expect("able to sign");
-					let tx = psbt.extract_tx();
-					broadcast_tx(&tx, electrum_url.clone());
-
-					let _status = rgb_client
-						.consume_transfer(consignment, true, None, |_| ())
-						.expect("valid consume tranfer");
-
-					drop(rgb_client);
-					sync_wallet(&wallet, electrum_url.clone());
-					println!("RGB send complete, txid: {}", tx.txid().to_string());
 				}
-				"receiveasset" => {
-					match check_uncolored_utxos(&ldk_data_dir).await {
-						Ok(_) => {}
-						Err(e) => {
-							println!("{e}");
+				"listreceives" => list_receives(&ctx, words.next() == Some("--json")),
+				"cancelreceive" => {
+					let recipient = match words.next() {
+						Some(recipient) => recipient,
+						None => {
+							println!("ERROR: cancelreceive has 1 required argument: `cancelreceive <recipient>`");
 							continue;
 						}
+					};
+					let req = CancelReceiveRequest { recipient: recipient.to_string() };
+					match api::cancel_receive(&ctx, req).await {
+						Ok(_) => println!("Receive request cancelled"),
+						Err(e) => println!("{e}"),
 					}
-					let outpoint = get_utxo(&ldk_data_dir).await.outpoint;
-					let rgb_utxos_path = format!("{}/rgb_utxos", ldk_data_dir.clone());
-					let serialized_utxos =
-						fs::read_to_string(&rgb_utxos_path).expect("able to read rgb utxos file");
-					let mut rgb_utxos: RgbUtxos =
-						serde_json::from_str(&serialized_utxos).expect("valid rgb utxos");
-					rgb_utxos
-						.utxos
-						.iter_mut()
-						.find(|u| u.outpoint == outpoint)
-						.expect("UTXO found")
-						.colored = true;
-					let serialized_utxos =
-						serde_json::to_string(&rgb_utxos).expect("valid rgb utxos");
-					fs::write(rgb_utxos_path, serialized_utxos)
-						.expect("able to write rgb utxos file");
-
-					let seal = Revealed::new(CloseMethod::OpretFirst, outpoint);
-					let concealed_seal = seal.to_concealed_seal();
-					let blinded_utxo = concealed_seal.to_string();
-
-					let blinded_dir = PathBuf::from_str(&ldk_data_dir)
-						.expect("valid data dir")
-						.join("blinded_utxos");
-					let blinded_path = blinded_dir.join(&blinded_utxo);
-					let blinded_info = BlindedInfo { contract_id: None, seal, consumed: false };
-					let serialized_info =
-						serde_json::to_string(&blinded_info).expect("valid rgb info");
-					fs::write(blinded_path, serialized_info).expect("successful file write");
-
-					println!("Blinded UTXO: {blinded_utxo}");
 				}
-				"refresh" => {
-					let blinded_dir = PathBuf::from_str(&ldk_data_dir)
-						.expect("valid data dir")
-						.join("blinded_utxos");
-					let blinded_files = fs::read_dir(blinded_dir).expect("successfult dir read");
-
-					for bf in blinded_files {
-						let serialized_info = fs::read_to_string(bf.as_ref().unwrap().path())
-							.expect("valid blinded info file");
-						let blinded_info: BlindedInfo =
-							serde_json::from_str(&serialized_info).expect("valid blinded data");
-						if blinded_info.consumed {
-							continue;
+				"refresh" => match api::refresh(&ctx).await {
+					Ok(res) => {
+						for transfer in res.received {
+							println!(
+								"Received {} {}",
+								display_rgb_amount(&ctx, &transfer.contract_id, transfer.amount),
+								transfer.ticker
+							);
 						}
-
-						let blinded_utxo = blinded_info.seal.to_concealed_seal().to_string();
-
-						let proxy_ref = (*proxy_client).clone();
-						let res = get_consignment(proxy_ref, proxy_url, blinded_utxo).await;
-						if res.is_err() || res.as_ref().unwrap().result.is_none() {
-							println!("WARNING: unable to get consignment");
-							continue;
+						for recipient in res.accepted {
+							println!(
+								"Accepted transfer to {recipient}, waiting for the sender to broadcast it"
+							);
 						}
-						let consignment = res.unwrap().result.unwrap();
-						let consignment_bytes =
-							base64::decode(consignment).expect("valid consignment");
-						let consignment: StateTransfer =
-							strict_deserialize(consignment_bytes).expect("valid consignment");
-
-						let ser_cons = strict_serialize(&consignment).expect("valid consignment");
-						let contract_consignment: Contract =
-							strict_deserialize(ser_cons).expect("valid serialized consignment");
-
-						let mut rgb_client = rgb_node_client.lock().unwrap();
-						rgb_client
-							.register_contract(contract_consignment, true, |_| ())
-							.expect("valid contract");
-
-						let reveal = Reveal {
-							blinding_factor: blinded_info.seal.blinding,
-							outpoint: OutPoint {
-								txid: blinded_info.seal.txid.unwrap(),
-								vout: blinded_info.seal.vout,
-							},
-							close_method: CloseMethod::OpretFirst,
-							witness_vout: false,
-						};
-						let status = rgb_client
-							.consume_transfer(consignment.clone(), true, Some(reveal), |_| ())
-							.expect("valid consume tranfer");
-						if !matches!(status, ContractValidity::Valid) {
-							println!("WARNING: error consuming transfer");
-							continue;
+						for transfer in res.rejected {
+							println!(
+								"WARNING: rejected transfer to {}: {}",
+								transfer.recipient, transfer.reason
+							);
+						}
+						match res.transport_error {
+							Some(e) => println!("WARNING: unable to fetch consignments: {e}"),
+							None => println!("Refresh complete"),
 						}
-
-						let wallet = wallet_arc.lock().unwrap();
-						sync_wallet(&wallet, electrum_url.clone());
-
-						fs::remove_file(bf.unwrap().path()).expect("successful file remove");
 					}
-
-					println!("Refresh complete");
-				}
+					Err(e) => println!("{e}"),
+				},
 				"openchannel" => {
 					let peer_pubkey_and_ip_addr = words.next();
 					let channel_value_sat = words.next();
 					let push_value_msat = words.next();
-					let contract_id = words.next();
-					let channel_value_rgb = words.next();
 					if peer_pubkey_and_ip_addr.is_none()
 						|| channel_value_sat.is_none()
 						|| push_value_msat.is_none()
-						|| contract_id.is_none() || channel_value_rgb.is_none()
 					{
-						println!("ERROR: openchannel has 5 required arguments: `openchannel pubkey@host:port chan_amt_satoshis push_amt_msatoshis rgb_contract_id chan_amt_rgb` [--public]");
+						println!("ERROR: openchannel has 3 required arguments: `openchannel pubkey@host:port chan_amt_satoshis push_amt_msatoshis [rgb_contract_id chan_amt_rgb]` [--public]");
 						continue;
 					}
-					let peer_pubkey_and_ip_addr = peer_pubkey_and_ip_addr.unwrap();
-					let (pubkey, peer_addr) =
-						match parse_peer_info(peer_pubkey_and_ip_addr.to_string()) {
-							Ok(info) => info,
-							Err(e) => {
-								println!("{:?}", e.into_inner().unwrap());
-								continue;
-							}
-						};
 
 					let chan_amt_sat: Result<u64, _> = channel_value_sat.unwrap().parse();
 					if chan_amt_sat.is_err() {
 						println!("ERROR: channel amount must be a number");
 						continue;
 					}
-					let chan_amt_sat = chan_amt_sat.unwrap();
-					if chan_amt_sat < OPENCHANNEL_MIN_SAT {
-						println!(
-							"ERROR: channel amount must be equal or higher than {}",
-							OPENCHANNEL_MIN_SAT
-						);
-						continue;
-					}
-					if chan_amt_sat > OPENCHANNEL_MAX_SAT {
-						println!(
-							"ERROR: channel amount must be equal or less than {}",
-							OPENCHANNEL_MAX_SAT
-						);
-						continue;
-					}
 
 					let push_amt_msat: Result<u64, _> = push_value_msat.unwrap().parse();
 					if push_amt_msat.is_err() {
 						println!("ERROR: push amount must be a number");
 						continue;
 					}
-					let push_amt_msat = push_amt_msat.unwrap();
-					if push_amt_msat < DUST_LIMIT_MSAT {
-						println!(
-							"ERROR: push amount must be equal or higher than the dust limit ({})",
-							DUST_LIMIT_MSAT
-						);
-						continue;
-					}
-
-					let contract_id = ContractId::from_str(contract_id.unwrap());
-					if contract_id.is_err() {
-						println!("ERROR: contract_id must be a valid RGB asset ID");
-						continue;
-					}
-					let contract_id = contract_id.unwrap();
 
-					let chan_amt_rgb: Result<u64, _> = channel_value_rgb.unwrap().parse();
-					if chan_amt_rgb.is_err() {
-						println!("ERROR: channel RGB amount must be a number");
-						continue;
-					}
-					let chan_amt_rgb = chan_amt_rgb.unwrap();
-
-					let total_rgb_amount = match get_rgb_total_amount(
-						contract_id,
-						rgb_node_client.clone(),
-						wallet_arc.clone(),
-						electrum_url.clone(),
-					) {
-						Ok(a) => a,
-						Err(e) => {
-							println!("{e}");
-							continue;
-						}
-					};
-
-					if chan_amt_rgb > total_rgb_amount {
-						println!("ERROR: do not have enough RGB assets");
-						continue;
-					}
-
-					if connect_peer_if_necessary(pubkey, peer_addr, peer_manager.clone())
-						.await
-						.is_err()
-					{
-						continue;
-					};
+					let mut extra_arg = words.next();
+					let (contract_id, chan_amt_rgb) =
+						match extra_arg.filter(|arg| !arg.starts_with("--")) {
+							Some(contract_id) => {
+								let chan_amt_rgb = match words
+									.next()
+									.map(|a| parse_rgb_amount(&ctx, contract_id, a))
+								{
+									Some(Ok(amt)) => amt,
+									Some(Err(e)) => {
+										println!("{e}");
+										continue;
+									}
+									None => {
+										println!("ERROR: openchannel requires a channel RGB amount when a contract ID is given");
+										continue;
+									}
+								};
+								extra_arg = words.next();
+								(Some(contract_id.to_string()), Some(chan_amt_rgb))
+							}
+							None => (None, None),
+						};
 
-					let announce_channel = match words.next() {
+					let announce_channel = match extra_arg {
 						Some("--public") | Some("--public=true") => true,
 						Some("--public=false") => false,
 						Some(_) => {
@@ -724,33 +446,21 @@ pub(crate) async fn poll_for_user_input(
 						None => false,
 					};
 
-					let open_channel_result = open_channel(
-						pubkey,
-						chan_amt_sat,
-						push_amt_msat,
-						announce_channel,
-						channel_manager.clone(),
-						proxy_url,
-					);
-					if open_channel_result.is_err() {
-						continue;
-					}
-
-					let peer_data_path = format!("{}/channel_peer_data", ldk_data_dir.clone());
-					let _ = disk::persist_channel_peer(
-						Path::new(&peer_data_path),
-						peer_pubkey_and_ip_addr,
-					);
-
-					let temporary_channel_id = open_channel_result.unwrap();
-					let channel_rgb_info_path =
-						format!("{}/{}", ldk_data_dir.clone(), hex::encode(&temporary_channel_id));
-					let rgb_info = RgbInfo {
+					let req = OpenChannelRequest {
+						peer_pubkey_and_addr: peer_pubkey_and_ip_addr.unwrap().to_string(),
+						capacity_sat: chan_amt_sat.unwrap(),
+						push_msat: push_amt_msat.unwrap(),
 						contract_id,
-						local_rgb_amount: chan_amt_rgb,
-						remote_rgb_amount: 0,
+						asset_amount: chan_amt_rgb,
+						public: announce_channel,
 					};
-					write_rgb_channel_info(&PathBuf::from(&channel_rgb_info_path), &rgb_info);
+					match api::open_channel(&ctx, req).await {
+						Ok(_) => println!(
+							"EVENT: initiated channel with peer {}. ",
+							peer_pubkey_and_ip_addr.unwrap().split('@').next().unwrap()
+						),
+						Err(e) => println!("{e}"),
+					}
 				}
 				"sendpayment" => {
 					let invoice_str = words.next();
@@ -759,41 +469,21 @@ pub(crate) async fn poll_for_user_input(
 						continue;
 					}
 
-					let invoice = match Invoice::from_str(invoice_str.unwrap()) {
-						Ok(inv) => inv,
-						Err(e) => {
-							println!("ERROR: invalid invoice: {:?}", e);
-							continue;
-						}
-					};
-
-					if let Some(amt_msat) = invoice.amount_milli_satoshis() {
-						if amt_msat < INVOICE_MIN_MSAT {
-							println!("ERROR: msat amount in invoice cannot be less than {INVOICE_MIN_MSAT}");
-							continue;
-						}
-					} else {
-						println!("ERROR: msat amount missing in invoice");
-						continue;
+					let req = SendPaymentRequest { invoice: invoice_str.unwrap().to_string() };
+					match api::send_payment(&ctx, req) {
+						Ok(res) => println!(
+							"EVENT: initiated sending {} msats to {}",
+							res.amt_msat, res.payee_pubkey
+						),
+						Err(e) => println!("{e}"),
 					}
-
-					send_payment(
-						&*channel_manager,
-						&invoice,
-						outbound_payments.clone(),
-						PathBuf::from(&ldk_data_dir),
-					);
+					print!("> ");
 				}
 				"keysend" => {
-					let keysend_cmd = "`keysend <dest_pubkey> <amt_msat> <contract_id> <amt_rgb>`";
+					let keysend_cmd =
+						"`keysend <dest_pubkey> <amt_msat> [<contract_id> <amt_rgb>]`";
 					let dest_pubkey = match words.next() {
-						Some(dest) => match hex_utils::to_compressed_pubkey(dest) {
-							Some(pk) => pk,
-							None => {
-								println!("ERROR: couldn't parse destination pubkey");
-								continue;
-							}
-						},
+						Some(dest) => dest,
 						None => {
 							println!("ERROR: keysend requires a destination pubkey: {keysend_cmd}");
 							continue;
@@ -814,62 +504,45 @@ pub(crate) async fn poll_for_user_input(
 							continue;
 						}
 					};
-					if amt_msat < HTLC_MIN_MSAT {
-						println!("ERROR: amount_msat cannot be less than {HTLC_MIN_MSAT}");
-						continue;
-					}
-					let contract_id = match words.next() {
-						Some(contract_id_str) => match ContractId::from_str(contract_id_str) {
-							Ok(cid) => cid,
-							Err(_) => {
-								println!("ERROR: invalid contract ID: {contract_id_str}");
-								continue;
+					let contract_id = words.next();
+					let amt_rgb = match (contract_id, words.next()) {
+						(Some(contract_id), Some(amt)) => {
+							match parse_rgb_amount(&ctx, contract_id, amt) {
+								Ok(amt) => Some(amt),
+								Err(e) => {
+									println!("{e}");
+									continue;
+								}
 							}
-						},
-						None => {
-							println!("ERROR: keysend requires a contract ID: {keysend_cmd}");
-							continue;
-						}
-					};
-					let amt_rgb_str = match words.next() {
-						Some(amt) => amt,
-						None => {
-							println!("ERROR: keysend requires an RGB amount: {keysend_cmd}");
-							continue;
 						}
+						_ => None,
 					};
-					let amt_rgb: u64 = match amt_rgb_str.parse() {
-						Ok(amt) => amt,
-						Err(e) => {
-							println!("ERROR: couldn't parse amt_rgb: {e}");
-							continue;
-						}
-					};
-					keysend(
-						&*channel_manager,
-						dest_pubkey,
+
+					let req = KeysendRequest {
+						dest_pubkey: dest_pubkey.to_string(),
 						amt_msat,
-						&*keys_manager,
-						outbound_payments.clone(),
-						contract_id,
+						contract_id: contract_id.map(|c| c.to_string()),
 						amt_rgb,
-						PathBuf::from(&ldk_data_dir),
-					);
+					};
+					match api::keysend(&ctx, req) {
+						Ok(res) => println!(
+							"EVENT: initiated sending {} msats to {}",
+							res.amt_msat, res.payee_pubkey
+						),
+						Err(e) => println!("{e}"),
+					}
+					print!("> ");
 				}
 				"getinvoice" => {
 					let getinvoice_cmd =
-						"`getinvoice <amt_msats> <expiry_secs> <rgb_contract_id> <amt_rgb>`";
+						"`getinvoice <amt_msats> <expiry_secs> [<rgb_contract_id> <amt_rgb>]`";
 					let amt_str = words.next();
 					let expiry_secs_str = words.next();
 					let contract_id_str = words.next();
 					let amt_rgb_str = words.next();
 
-					if amt_str.is_none()
-						|| expiry_secs_str.is_none()
-						|| contract_id_str.is_none()
-						|| amt_rgb_str.is_none()
-					{
-						println!("ERROR: getinvoice has 4 required arguments: {getinvoice_cmd}");
+					if amt_str.is_none() || expiry_secs_str.is_none() {
+						println!("ERROR: getinvoice has 2 required arguments: {getinvoice_cmd}");
 						continue;
 					}
 
@@ -878,11 +551,6 @@ pub(crate) async fn poll_for_user_input(
 						println!("ERROR: getinvoice provided payment amount was not a number");
 						continue;
 					}
-					let amt_msat = amt_msat.unwrap();
-					if amt_msat < INVOICE_MIN_MSAT {
-						println!("ERROR: amt_msat cannot be less than {INVOICE_MIN_MSAT}");
-						continue;
-					}
 
 					let expiry_secs: Result<u32, _> = expiry_secs_str.unwrap().parse();
 					if expiry_secs.is_err() {
@@ -890,34 +558,29 @@ pub(crate) async fn poll_for_user_input(
 						continue;
 					}
 
-					let contract_id_str = contract_id_str.unwrap();
-					let contract_id = match ContractId::from_str(contract_id_str) {
-						Ok(cid) => cid,
-						Err(_) => {
-							println!("ERROR: invalid contract ID: {contract_id_str}");
-							continue;
-						}
-					};
-
-					let amt_rgb: u64 = match amt_rgb_str.unwrap().parse() {
-						Ok(amt) => amt,
-						Err(e) => {
-							println!("ERROR: couldn't parse amt_rgb: {e}");
-							continue;
+					let amt_rgb = match (contract_id_str, amt_rgb_str) {
+						(Some(contract_id), Some(amt)) => {
+							match parse_rgb_amount(&ctx, contract_id, amt) {
+								Ok(amt) => Some(amt),
+								Err(e) => {
+									println!("{e}");
+									continue;
+								}
+							}
 						}
+						_ => None,
 					};
 
-					get_invoice(
-						amt_msat,
-						Arc::clone(&inbound_payments),
-						&*channel_manager,
-						Arc::clone(&keys_manager),
-						network,
-						expiry_secs.unwrap(),
-						Arc::clone(&logger),
-						contract_id,
+					let req = GetInvoiceRequest {
+						amt_msat: amt_msat.unwrap(),
+						expiry_secs: expiry_secs.unwrap(),
+						contract_id: contract_id_str.map(|c| c.to_string()),
 						amt_rgb,
-					);
+					};
+					match api::get_invoice(&ctx, req) {
+						Ok(res) => println!("SUCCESS: generated invoice: {}", res.invoice),
+						Err(e) => println!("{e}"),
+					}
 				}
 				"connectpeer" => {
 					let peer_pubkey_and_ip_addr = words.next();
@@ -925,19 +588,14 @@ pub(crate) async fn poll_for_user_input(
 						println!("ERROR: connectpeer requires peer connection info: `connectpeer pubkey@host:port`");
 						continue;
 					}
-					let (pubkey, peer_addr) =
-						match parse_peer_info(peer_pubkey_and_ip_addr.unwrap().to_string()) {
-							Ok(info) => info,
-							Err(e) => {
-								println!("{:?}", e.into_inner().unwrap());
-								continue;
-							}
-						};
-					if connect_peer_if_necessary(pubkey, peer_addr, peer_manager.clone())
-						.await
-						.is_ok()
+					let peer_pubkey_and_addr = peer_pubkey_and_ip_addr.unwrap().to_string();
+					match api::connect_peer(&ctx, ConnectPeerRequest { peer_pubkey_and_addr }).await
 					{
-						println!("SUCCESS: connected to peer {}", pubkey);
+						Ok(_) => println!(
+							"SUCCESS: connected to peer {}",
+							peer_pubkey_and_ip_addr.unwrap().split('@').next().unwrap()
+						),
+						Err(e) => println!("{e}"),
 					}
 				}
 				"disconnectpeer" => {
@@ -947,31 +605,41 @@ pub(crate) async fn poll_for_user_input(
 						continue;
 					}
 
-					let peer_pubkey =
-						match bitcoin::secp256k1::PublicKey::from_str(peer_pubkey.unwrap()) {
-							Ok(pubkey) => pubkey,
-							Err(e) => {
-								println!("ERROR: {}", e.to_string());
-								continue;
-							}
-						};
-
-					if do_disconnect_peer(
-						peer_pubkey,
-						peer_manager.clone(),
-						channel_manager.clone(),
-					)
-					.is_ok()
-					{
-						println!("SUCCESS: disconnected from peer {}", peer_pubkey);
+					let req =
+						DisconnectPeerRequest { peer_pubkey: peer_pubkey.unwrap().to_string() };
+					match api::disconnect_peer(&ctx, req) {
+						Ok(_) => {
+							println!("SUCCESS: disconnected from peer {}", peer_pubkey.unwrap())
+						}
+						Err(e) => println!("{e}"),
 					}
 				}
-				"listchannels" => {
-					list_channels(&channel_manager, &network_graph, ldk_data_dir.clone())
+				"listchannels" => list_channels(&ctx, words.next() == Some("--json")),
+				"listpendingchannels" => {
+					list_pending_channels(&ctx, words.next() == Some("--json"))
 				}
-				"listpayments" => {
-					list_payments(inbound_payments.clone(), outbound_payments.clone())
+				"acceptchannel" | "rejectchannel" => {
+					let temporary_channel_id = match words.next() {
+						Some(id) => id.to_string(),
+						None => {
+							println!("ERROR: {word} requires a temporary channel ID: `{word} <temporary_channel_id>`");
+							continue;
+						}
+					};
+					let req = PendingChannelRequest { temporary_channel_id };
+					if word == "acceptchannel" {
+						match api::accept_channel(&ctx, req) {
+							Ok(_) => println!("SUCCESS: accepted channel"),
+							Err(e) => println!("{e}"),
+						}
+					} else {
+						match api::reject_channel(&ctx, req) {
+							Ok(_) => println!("SUCCESS: rejected channel"),
+							Err(e) => println!("{e}"),
+						}
+					}
 				}
+				"listpayments" => list_payments(&ctx, words.next() == Some("--json")),
 				"invoicestatus" => {
 					let invoice = words.next();
 					if invoice.is_none() {
@@ -980,15 +648,12 @@ pub(crate) async fn poll_for_user_input(
 						);
 						continue;
 					};
-					let invoice = match Invoice::from_str(invoice.unwrap()) {
-						Err(e) => {
-							println!("ERROR: invalid invoice: {:?}", e);
-							continue;
-						}
-						Ok(v) => v,
-					};
 
-					invoice_status(inbound_payments.clone(), invoice)
+					let req = InvoiceStatusRequest { invoice: invoice.unwrap().to_string() };
+					match api::invoice_status(&ctx, req) {
+						Ok(res) => println!("{}", res.status),
+						Err(e) => println!("{e}"),
+					}
 				}
 				"closechannel" => {
 					let channel_id_str = words.next();
@@ -996,35 +661,21 @@ pub(crate) async fn poll_for_user_input(
 						println!("ERROR: closechannel requires a channel ID: `closechannel <channel_id> <peer_pubkey>`");
 						continue;
 					}
-					let channel_id_vec = hex_utils::to_vec(channel_id_str.unwrap());
-					if channel_id_vec.is_none() || channel_id_vec.as_ref().unwrap().len() != 32 {
-						println!("ERROR: couldn't parse channel_id");
-						continue;
-					}
-					let mut channel_id = [0; 32];
-					channel_id.copy_from_slice(&channel_id_vec.unwrap());
 
 					let peer_pubkey_str = words.next();
 					if peer_pubkey_str.is_none() {
 						println!("ERROR: closechannel requires a peer pubkey: `closechannel <channel_id> <peer_pubkey>`");
 						continue;
 					}
-					let peer_pubkey_vec = match hex_utils::to_vec(peer_pubkey_str.unwrap()) {
-						Some(peer_pubkey_vec) => peer_pubkey_vec,
-						None => {
-							println!("ERROR: couldn't parse peer_pubkey");
-							continue;
-						}
-					};
-					let peer_pubkey = match PublicKey::from_slice(&peer_pubkey_vec) {
-						Ok(peer_pubkey) => peer_pubkey,
-						Err(_) => {
-							println!("ERROR: couldn't parse peer_pubkey");
-							continue;
-						}
-					};
 
-					close_channel(channel_id, peer_pubkey, channel_manager.clone());
+					let req = ChannelIdRequest {
+						channel_id: channel_id_str.unwrap().to_string(),
+						peer_pubkey: peer_pubkey_str.unwrap().to_string(),
+					};
+					match api::close_channel(&ctx, req) {
+						Ok(_) => println!("EVENT: initiating channel close"),
+						Err(e) => println!("{e}"),
+					}
 				}
 				"forceclosechannel" => {
 					let channel_id_str = words.next();
@@ -1032,51 +683,35 @@ pub(crate) async fn poll_for_user_input(
 						println!("ERROR: forceclosechannel requires a channel ID: `forceclosechannel <channel_id> <peer_pubkey>`");
 						continue;
 					}
-					let channel_id_vec = hex_utils::to_vec(channel_id_str.unwrap());
-					if channel_id_vec.is_none() || channel_id_vec.as_ref().unwrap().len() != 32 {
-						println!("ERROR: couldn't parse channel_id");
-						continue;
-					}
-					let mut channel_id = [0; 32];
-					channel_id.copy_from_slice(&channel_id_vec.unwrap());
 
 					let peer_pubkey_str = words.next();
 					if peer_pubkey_str.is_none() {
 						println!("ERROR: forceclosechannel requires a peer pubkey: `forceclosechannel <channel_id> <peer_pubkey>`");
 						continue;
 					}
-					let peer_pubkey_vec = match hex_utils::to_vec(peer_pubkey_str.unwrap()) {
-						Some(peer_pubkey_vec) => peer_pubkey_vec,
-						None => {
-							println!("ERROR: couldn't parse peer_pubkey");
-							continue;
-						}
-					};
-					let peer_pubkey = match PublicKey::from_slice(&peer_pubkey_vec) {
-						Ok(peer_pubkey) => peer_pubkey,
-						Err(_) => {
-							println!("ERROR: couldn't parse peer_pubkey");
-							continue;
-						}
-					};
 
-					force_close_channel(channel_id, peer_pubkey, channel_manager.clone());
+					let req = ChannelIdRequest {
+						channel_id: channel_id_str.unwrap().to_string(),
+						peer_pubkey: peer_pubkey_str.unwrap().to_string(),
+					};
+					match api::force_close_channel(&ctx, req) {
+						Ok(_) => println!("EVENT: initiating channel force-close"),
+						Err(e) => println!("{e}"),
+					}
 				}
-				"nodeinfo" => node_info(&channel_manager, &peer_manager),
-				"listpeers" => list_peers(peer_manager.clone()),
+				"nodeinfo" => node_info(&ctx, words.next() == Some("--json")),
+				"listpeers" => list_peers(&ctx, words.next() == Some("--json")),
 				"signmessage" => {
 					const MSG_STARTPOS: usize = "signmessage".len() + 1;
 					if line.as_bytes().len() <= MSG_STARTPOS {
 						println!("ERROR: signmsg requires a message");
 						continue;
 					}
-					println!(
-						"{:?}",
-						lightning::util::message_signing::sign(
-							&line.as_bytes()[MSG_STARTPOS..],
-							&keys_manager.get_node_secret_key()
-						)
-					);
+					let req = SignMessageRequest { message: line[MSG_STARTPOS..].to_string() };
+					match api::sign_message(&ctx, req) {
+						Ok(res) => println!("{}", res.signed_message),
+						Err(e) => println!("{e}"),
+					}
 				}
 				"sendonionmessage" => {
 					let path_pks_str = words.next();
@@ -1086,53 +721,25 @@ pub(crate) async fn poll_for_user_input(
 						);
 						continue;
 					}
-					let mut node_pks = Vec::new();
-					let mut errored = false;
-					for pk_str in path_pks_str.unwrap().split(",") {
-						let node_pubkey_vec = match hex_utils::to_vec(pk_str) {
-							Some(peer_pubkey_vec) => peer_pubkey_vec,
-							None => {
-								println!("ERROR: couldn't parse peer_pubkey");
-								errored = true;
-								break;
-							}
-						};
-						let node_pubkey = match PublicKey::from_slice(&node_pubkey_vec) {
-							Ok(peer_pubkey) => peer_pubkey,
-							Err(_) => {
-								println!("ERROR: couldn't parse peer_pubkey");
-								errored = true;
-								break;
-							}
-						};
-						node_pks.push(node_pubkey);
-					}
-					if errored {
-						continue;
-					}
+					let node_ids = path_pks_str.unwrap().split(",").map(String::from).collect();
 					let tlv_type = match words.next().map(|ty_str| ty_str.parse()) {
-						Some(Ok(ty)) if ty >= 64 => ty,
+						Some(Ok(ty)) => ty,
 						_ => {
 							println!("Need an integral message type above 64");
 							continue;
 						}
 					};
-					let data = match words.next().map(|s| hex_utils::to_vec(s)) {
-						Some(Some(data)) => data,
+					let data = match words.next() {
+						Some(data) => data.to_string(),
 						_ => {
 							println!("Need a hex data string");
 							continue;
 						}
 					};
-					let destination_pk = node_pks.pop().unwrap();
-					match onion_messenger.send_onion_message(
-						&node_pks,
-						Destination::Node(destination_pk),
-						OnionMessageContents::Custom(UserOnionMessageContents { tlv_type, data }),
-						None,
-					) {
-						Ok(()) => println!("SUCCESS: forwarded onion message to first hop"),
-						Err(e) => println!("ERROR: failed to send onion message: {:?}", e),
+					let req = SendOnionMessageRequest { node_ids, tlv_type, data };
+					match api::send_onion_message(&ctx, req) {
+						Ok(_) => println!("SUCCESS: forwarded onion message to first hop"),
+						Err(e) => println!("{e}"),
 					}
 				}
 				"quit" | "exit" => break,
@@ -1153,30 +760,40 @@ fn help() {
 	println!("  help\tShows a list of commands.");
 	println!("  quit\tClose the application.");
 	println!("\n  Channels:");
-	println!("      openchannel pubkey@host:port <chan_amt_satoshis> <push_amt_msatoshis> <rgb_contract_id> <chan_amt_rgb> [--public]");
+	println!("      openchannel pubkey@host:port <chan_amt_satoshis> <push_amt_msatoshis> [<rgb_contract_id> <chan_amt_rgb>] [--public]");
 	println!("      closechannel <channel_id> <peer_pubkey>");
 	println!("      forceclosechannel <channel_id> <peer_pubkey>");
-	println!("      listchannels");
+	println!("      listchannels [--json]");
+	println!("      listpendingchannels [--json]");
+	println!("      acceptchannel <temporary_channel_id>");
+	println!("      rejectchannel <temporary_channel_id>");
 	println!("\n  Peers:");
 	println!("      connectpeer pubkey@host:port");
 	println!("      disconnectpeer <peer_pubkey>");
-	println!("      listpeers");
+	println!("      listpeers [--json]");
 	println!("\n  Payments:");
 	println!("      sendpayment <invoice>");
-	println!("      keysend <dest_pubkey> <amt_msats> <rgb_contract_id> <amt_rgb>");
-	println!("      listpayments");
+	println!("      keysend <dest_pubkey> <amt_msats> [<rgb_contract_id> <amt_rgb>]");
+	println!("      listpayments [--json]");
 	println!("\n  Invoices:");
-	println!("      getinvoice <amt_msats> <expiry_secs> <rgb_contract_id> <amt_rgb>");
+	println!("      getinvoice <amt_msats> <expiry_secs> [<rgb_contract_id> <amt_rgb>]");
 	println!("      invoicestatus <invoice>");
 	println!("\n  Onchain:");
 	println!("      getaddress");
 	println!("      listunspent");
 	println!("\n  RGB:");
-	println!("      createutxos");
+	println!("      createutxos [<num>] [<size_sat>] [--up-to]");
+	println!("      listrgbutxos [--json]");
+	println!("      checkutxos");
 	println!("      issueasset <supply> <ticker> <name> <precision>");
+	println!("      listassets [--json]");
+	println!("      assetinfo <contract_id> [--json]");
 	println!("      assetbalance <contract_id>");
-	println!("      sendasset <rgb_contract_id> <amt_rgb>");
-	println!("      receiveasset");
+	println!("      sendasset <rgb_contract_id> <amt_rgb> <recipient>[@<node_id>]");
+	println!("      sendasset <rgb_contract_id> <recipient>[@<node_id>]:<amt_rgb> [<recipient>[@<node_id>]:<amt_rgb> ...]");
+	println!("      receiveasset [--witness] [--contract-id=<contract_id> [--amount=<amt_rgb>]] [--expiry-secs=<secs>]");
+	println!("      listreceives [--json]");
+	println!("      cancelreceive <recipient>");
 	println!("      refresh");
 	println!("\n  Other:");
 	println!("      mine <num_blocks>");
@@ -1184,53 +801,61 @@ fn help() {
 	println!(
 		"      sendonionmessage <node_id_1,node_id_2,..,destination_node_id> <type> <hex_bytes>"
 	);
-	println!("      nodeinfo");
+	println!("      nodeinfo [--json]");
 }
 
-fn node_info(channel_manager: &Arc<ChannelManager>, peer_manager: &Arc<PeerManager>) {
+fn node_info(ctx: &NodeContext, json: bool) {
+	let info = match api::node_info(ctx) {
+		Ok(info) => info,
+		Err(e) => return println!("{e}"),
+	};
+	if json {
+		return print_json(&info);
+	}
 	println!("\t{{");
-	println!("\t\t node_pubkey: {}", channel_manager.get_our_node_id());
-	let chans = channel_manager.list_channels();
-	println!("\t\t num_channels: {}", chans.len());
-	println!("\t\t num_usable_channels: {}", chans.iter().filter(|c| c.is_usable).count());
-	let local_balance_msat = chans.iter().map(|c| c.balance_msat).sum::<u64>();
-	println!("\t\t local_balance_msat: {}", local_balance_msat);
-	println!("\t\t num_peers: {}", peer_manager.get_peer_node_ids().len());
+	println!("\t\t node_pubkey: {}", info.node_pubkey);
+	println!("\t\t num_channels: {}", info.num_channels);
+	println!("\t\t num_usable_channels: {}", info.num_usable_channels);
+	println!("\t\t local_balance_msat: {}", info.local_balance_msat);
+	println!("\t\t num_peers: {}", info.num_peers);
 	println!("\t}},");
 }
 
-fn list_peers(peer_manager: Arc<PeerManager>) {
+fn list_peers(ctx: &NodeContext, json: bool) {
+	let res = match api::list_peers(ctx) {
+		Ok(res) => res,
+		Err(e) => return println!("{e}"),
+	};
+	if json {
+		return print_json(&res);
+	}
 	println!("\t{{");
-	for (pubkey, _) in peer_manager.get_peer_node_ids() {
-		println!("\t\t pubkey: {}", pubkey);
+	for peer in res.peers {
+		println!("\t\t pubkey: {}", peer.pubkey);
 	}
 	println!("\t}},");
 }
 
-fn list_channels(
-	channel_manager: &Arc<ChannelManager>, network_graph: &Arc<NetworkGraph>, ldk_data_dir: String,
-) {
+fn list_channels(ctx: &NodeContext, json: bool) {
+	let res = match api::list_channels(ctx) {
+		Ok(res) => res,
+		Err(e) => return println!("{e}"),
+	};
+	if json {
+		return print_json(&res);
+	}
 	print!("[");
-	for chan_info in channel_manager.list_channels() {
+	for chan_info in res.channels {
 		println!("");
 		println!("\t{{");
-		println!("\t\tchannel_id: {},", hex_utils::hex_str(&chan_info.channel_id[..]));
-		if let Some(funding_txo) = chan_info.funding_txo {
-			println!("\t\tfunding_txid: {},", funding_txo.txid);
+		println!("\t\tchannel_id: {},", chan_info.channel_id);
+		if let Some(funding_txid) = chan_info.funding_txid {
+			println!("\t\tfunding_txid: {},", funding_txid);
 		}
 
-		println!(
-			"\t\tpeer_pubkey: {},",
-			hex_utils::hex_str(&chan_info.counterparty.node_id.serialize())
-		);
-		if let Some(node_info) = network_graph
-			.read_only()
-			.nodes()
-			.get(&NodeId::from_pubkey(&chan_info.counterparty.node_id))
-		{
-			if let Some(announcement) = &node_info.announcement_info {
-				println!("\t\tpeer_alias: {}", announcement.alias);
-			}
+		println!("\t\tpeer_pubkey: {},", chan_info.peer_pubkey);
+		if let Some(alias) = chan_info.peer_alias {
+			println!("\t\tpeer_alias: {}", alias);
 		}
 
 		if let Some(id) = chan_info.short_channel_id {
@@ -1238,97 +863,204 @@ fn list_channels(
 		}
 		println!("\t\tis_channel_ready: {},", chan_info.is_channel_ready);
 		println!("\t\tchannel_value_satoshis: {},", chan_info.channel_value_satoshis);
-		println!("\t\tlocal_balance_msat: {},", chan_info.balance_msat);
-		if chan_info.is_usable {
-			println!("\t\tavailable_balance_for_send_msat: {},", chan_info.outbound_capacity_msat);
-			println!("\t\tavailable_balance_for_recv_msat: {},", chan_info.inbound_capacity_msat);
+		println!("\t\tlocal_balance_msat: {},", chan_info.local_balance_msat);
+		if let Some(send_msat) = chan_info.available_balance_for_send_msat {
+			println!("\t\tavailable_balance_for_send_msat: {},", send_msat);
+		}
+		if let Some(recv_msat) = chan_info.available_balance_for_recv_msat {
+			println!("\t\tavailable_balance_for_recv_msat: {},", recv_msat);
+		}
+		println!("\t\tchannel_can_send_payments: {},", chan_info.channel_can_send_payments);
+		println!("\t\tpublic: {},", chan_info.public);
+
+		match (chan_info.rgb_contract_id, chan_info.rgb_local_amount, chan_info.rgb_remote_amount) {
+			(Some(contract_id), Some(local_amount), Some(remote_amount)) => {
+				println!("\t\trgb_contract_id: {},", contract_id);
+				println!(
+					"\t\trgb_local_amount: {},",
+					display_rgb_amount(ctx, &contract_id, local_amount)
+				);
+				println!(
+					"\t\trgb_remote_amount: {},",
+					display_rgb_amount(ctx, &contract_id, remote_amount)
+				);
+			}
+			_ => {
+				println!("\t\trgb_contract_id: N/A,");
+				println!("\t\trgb_local_amount: N/A,");
+				println!("\t\trgb_remote_amount: N/A,");
+			}
 		}
-		println!("\t\tchannel_can_send_payments: {},", chan_info.is_usable);
-		println!("\t\tpublic: {},", chan_info.is_public);
-
-		let ldk_data_dir_path = PathBuf::from(ldk_data_dir.clone());
-		let info_file_path = ldk_data_dir_path.join(hex::encode(chan_info.channel_id));
-		let (contract_id, local_rgb_amount, remote_rgb_amount) = if info_file_path.exists() {
-			let (rgb_info, _) = get_rgb_channel_info(&chan_info.channel_id, &ldk_data_dir_path);
-			(
-				rgb_info.contract_id.to_string(),
-				rgb_info.local_rgb_amount.to_string(),
-				rgb_info.remote_rgb_amount.to_string(),
-			)
-		} else {
-			let not_available = "N/A".to_string();
-			(not_available.clone(), not_available.clone(), not_available)
-		};
-		println!("\t\trgb_contract_id: {contract_id},");
-		println!("\t\trgb_local_amount: {local_rgb_amount},");
-		println!("\t\trgb_remote_amount: {remote_rgb_amount},");
 		println!("\t}},");
 	}
 	println!("]");
 }
 
-fn invoice_status(inbound_payments: PaymentInfoStorage, invoice: Invoice) {
-	let inbound = inbound_payments.lock().unwrap();
-
-	let payment_hash = PaymentHash(invoice.payment_hash().clone().into_inner());
-	match inbound.get(&payment_hash) {
-		Some(v) => {
-			let status_str = match v.status {
-				HTLCStatus::Pending if invoice.is_expired() => "expired",
-				HTLCStatus::Pending => "pending",
-				HTLCStatus::Succeeded => "succeeded",
-				HTLCStatus::Failed => "failed",
-			};
-			println!("{}", status_str);
-		}
-		None => {
-			println!("ERROR: unknown invoice");
-		}
+fn list_pending_channels(ctx: &NodeContext, json: bool) {
+	let res = match api::list_pending_channels(ctx) {
+		Ok(res) => res,
+		Err(e) => return println!("{e}"),
 	};
+	if json {
+		return print_json(&res);
+	}
+	print!("[");
+	for pending_channel in res.pending_channels {
+		println!("");
+		println!("\t{{");
+		println!("\t\ttemporary_channel_id: {},", pending_channel.temporary_channel_id);
+		println!("\t\tpeer_pubkey: {},", pending_channel.peer_pubkey);
+		println!("\t\tfunding_satoshis: {},", pending_channel.funding_satoshis);
+		println!("\t\tpush_msat: {},", pending_channel.push_msat);
+		match (pending_channel.rgb_contract_id, pending_channel.rgb_amount) {
+			(Some(contract_id), Some(amount)) => {
+				println!("\t\trgb_contract_id: {},", contract_id);
+				println!("\t\trgb_amount: {},", display_rgb_amount(ctx, &contract_id, amount));
+			}
+			_ => {
+				println!("\t\trgb_contract_id: N/A,");
+				println!("\t\trgb_amount: N/A,");
+			}
+		}
+		println!("\t}},");
+	}
+	println!("]");
 }
 
-fn list_payments(inbound_payments: PaymentInfoStorage, outbound_payments: PaymentInfoStorage) {
-	let inbound = inbound_payments.lock().unwrap();
-	let outbound = outbound_payments.lock().unwrap();
+fn list_payments(ctx: &NodeContext, json: bool) {
+	let res = match api::list_payments(ctx) {
+		Ok(res) => res,
+		Err(e) => return println!("{e}"),
+	};
+	if json {
+		return print_json(&res);
+	}
 	print!("[");
-	for (payment_hash, payment_info) in inbound.deref() {
+	for payment in res.payments {
 		println!("");
 		println!("\t{{");
-		println!("\t\tamount_millisatoshis: {},", payment_info.amt_msat);
-		println!("\t\tpayment_hash: {},", hex_utils::hex_str(&payment_hash.0));
-		println!("\t\thtlc_direction: inbound,");
-		println!(
-			"\t\thtlc_status: {},",
-			match payment_info.status {
-				HTLCStatus::Pending => "pending",
-				HTLCStatus::Succeeded => "succeeded",
-				HTLCStatus::Failed => "failed",
+		println!("\t\tamount_millisatoshis: {},", MillisatAmount(payment.amount_msat));
+		println!("\t\tpayment_hash: {},", payment.payment_hash);
+		println!("\t\thtlc_direction: {},", payment.htlc_direction);
+		println!("\t\thtlc_status: {},", payment.htlc_status);
+		match (payment.rgb_contract_id, payment.rgb_amount) {
+			(Some(contract_id), Some(amount)) => {
+				println!("\t\trgb_contract_id: {},", contract_id);
+				println!("\t\trgb_amount: {},", display_rgb_amount(ctx, &contract_id, amount));
 			}
-		);
+			_ => {
+				println!("\t\trgb_contract_id: N/A,");
+				println!("\t\trgb_amount: N/A,");
+			}
+		}
 
 		println!("\t}},");
 	}
+	println!("]");
+}
 
-	for (payment_hash, payment_info) in outbound.deref() {
+fn list_receives(ctx: &NodeContext, json: bool) {
+	let res = match api::list_receives(ctx) {
+		Ok(res) => res,
+		Err(e) => return println!("{e}"),
+	};
+	if json {
+		return print_json(&res);
+	}
+	print!("[");
+	for receive in res.receives {
 		println!("");
 		println!("\t{{");
-		println!("\t\tamount_millisatoshis: {},", payment_info.amt_msat);
-		println!("\t\tpayment_hash: {},", hex_utils::hex_str(&payment_hash.0));
-		println!("\t\thtlc_direction: outbound,");
-		println!(
-			"\t\thtlc_status: {},",
-			match payment_info.status {
-				HTLCStatus::Pending => "pending",
-				HTLCStatus::Succeeded => "succeeded",
-				HTLCStatus::Failed => "failed",
+		println!("\t\trecipient: {},", receive.recipient);
+		match (receive.contract_id, receive.amount) {
+			(Some(contract_id), amount) => {
+				println!("\t\tcontract_id: {},", contract_id);
+				println!(
+					"\t\tamount: {},",
+					amount
+						.map(|a| display_rgb_amount(ctx, &contract_id, a))
+						.unwrap_or_else(|| "N/A".to_string())
+				);
+			}
+			(None, _) => {
+				println!("\t\tcontract_id: N/A,");
+				println!("\t\tamount: N/A,");
 			}
+		}
+		println!(
+			"\t\texpires_at: {},",
+			receive.expires_at.map(|e| e.to_string()).unwrap_or_else(|| "never".to_string())
 		);
-
+		println!("\t\texpired: {},", receive.expired);
+		if let Some(reason) = receive.rejected {
+			println!("\t\trejected: {},", reason);
+		}
 		println!("\t}},");
 	}
 	println!("]");
 }
 
+fn list_assets(ctx: &NodeContext, json: bool) {
+	let res = match api::list_assets(ctx) {
+		Ok(res) => res,
+		Err(e) => return println!("{e}"),
+	};
+	if json {
+		return print_json(&res);
+	}
+	print!("[");
+	for asset in res.assets {
+		println!("");
+		print_asset_info(&asset);
+	}
+	println!("]");
+}
+
+fn print_asset_info(asset: &AssetInfo) {
+	println!("\t{{");
+	println!("\t\tcontract_id: {},", asset.contract_id);
+	println!("\t\tticker: {},", asset.ticker);
+	println!("\t\tname: {},", asset.name);
+	println!("\t\tprecision: {},", asset.precision);
+	println!("\t\tissued_supply: {},", format_asset_amount(asset.issued_supply, asset.precision));
+	print_asset_balances(&asset.balance, asset.precision);
+	println!("\t}},");
+}
+
+fn print_asset_balances(balance: &AssetBalances, precision: u8) {
+	println!("\t\tsettled: {},", format_asset_amount(balance.settled, precision));
+	println!("\t\tunconfirmed: {},", format_asset_amount(balance.unconfirmed, precision));
+	println!("\t\tpending_receives: {},", format_asset_amount(balance.pending_receives, precision));
+	println!("\t\tunpinned_receives: {},", balance.unpinned_receives);
+	println!("\t\tin_channels: {},", format_asset_amount(balance.in_channels, precision));
+	println!(
+		"\t\tclaimable_after_close: {},",
+		format_asset_amount(balance.claimable_after_close, precision)
+	);
+}
+
+/// Parses an amount of the given asset, expressed in the asset's units
+fn parse_rgb_amount(ctx: &NodeContext, contract_id: &str, amount: &str) -> Result<u64, Error> {
+	let precision = api::asset_precision(ctx, contract_id)?;
+	parse_asset_amount(amount, precision)
+}
+
+/// Shows an amount of the given asset in the asset's units, or labeled as base units if its
+/// precision is unknown
+pub(crate) fn display_rgb_amount(ctx: &NodeContext, contract_id: &str, amount: u64) -> String {
+	match api::asset_precision(ctx, contract_id) {
+		Ok(precision) => format_asset_amount(amount, precision),
+		Err(_) => format!("{amount} base units"),
+	}
+}
+
+fn print_json<T: Serialize>(value: &T) {
+	match serde_json::to_string_pretty(value) {
+		Ok(json) => println!("{}", json),
+		Err(e) => println!("ERROR: failed to serialize output: {}", e),
+	}
+}
+
 pub(crate) async fn connect_peer_if_necessary(
 	pubkey: PublicKey, peer_addr: SocketAddr, peer_manager: Arc<PeerManager>,
 ) -> Result<(), ()> {
@@ -1337,11 +1069,7 @@ pub(crate) async fn connect_peer_if_necessary(
 			return Ok(());
 		}
 	}
-	let res = do_connect_peer(pubkey, peer_addr, peer_manager).await;
-	if res.is_err() {
-		println!("ERROR: failed to connect to peer");
-	}
-	res
+	do_connect_peer(pubkey, peer_addr, peer_manager).await
 }
 
 pub(crate) async fn do_connect_peer(
@@ -1365,216 +1093,6 @@ pub(crate) async fn do_connect_peer(
 	}
 }
 
-fn do_disconnect_peer(
-	pubkey: bitcoin::secp256k1::PublicKey, peer_manager: Arc<PeerManager>,
-	channel_manager: Arc<ChannelManager>,
-) -> Result<(), ()> {
-	//check for open channels with peer
-	for channel in channel_manager.list_channels() {
-		if channel.counterparty.node_id == pubkey {
-			println!("Error: Node has an active channel with this peer, close any channels first");
-			return Err(());
-		}
-	}
-
-	//check the pubkey matches a valid connected peer
-	let peers = peer_manager.get_peer_node_ids();
-	if !peers.iter().any(|(pk, _)| &pubkey == pk) {
-		println!("Error: Could not find peer {}", pubkey);
-		return Err(());
-	}
-
-	peer_manager.disconnect_by_node_id(pubkey);
-	Ok(())
-}
-
-fn open_channel(
-	peer_pubkey: PublicKey, channel_amt_sat: u64, push_amt_msat: u64, announced_channel: bool,
-	channel_manager: Arc<ChannelManager>, proxy_url: &str,
-) -> Result<[u8; 32], ()> {
-	let config = UserConfig {
-		channel_handshake_limits: ChannelHandshakeLimits {
-			// lnd's max to_self_delay is 2016, so we want to be compatible.
-			their_to_self_delay: 2016,
-			..Default::default()
-		},
-		channel_handshake_config: ChannelHandshakeConfig {
-			announced_channel,
-			our_htlc_minimum_msat: HTLC_MIN_MSAT,
-			..Default::default()
-		},
-		..Default::default()
-	};
-
-	let consignment_endpoint =
-		ConsignmentEndpoint::from_str(&format!("rgbhttpjsonrpc:{}", proxy_url)).unwrap();
-	match channel_manager.create_channel(
-		peer_pubkey,
-		channel_amt_sat,
-		push_amt_msat,
-		0,
-		Some(config),
-		consignment_endpoint,
-	) {
-		Ok(temporary_channel_id) => {
-			println!("EVENT: initiated channel with peer {}. ", peer_pubkey);
-			return Ok(temporary_channel_id);
-		}
-		Err(e) => {
-			println!("ERROR: failed to open channel: {:?}", e);
-			return Err(());
-		}
-	}
-}
-
-fn send_payment(
-	channel_manager: &ChannelManager, invoice: &Invoice, payment_storage: PaymentInfoStorage,
-	ldk_data_dir: PathBuf,
-) {
-	let payment_hash = PaymentHash(invoice.payment_hash().clone().into_inner());
-	write_rgb_payment_info_file(
-		&ldk_data_dir,
-		&payment_hash,
-		invoice.rgb_contract_id().unwrap(),
-		invoice.rgb_amount().unwrap(),
-	);
-	let status =
-		match pay_invoice(invoice, Retry::Timeout(Duration::from_secs(10)), channel_manager) {
-			Ok(_payment_id) => {
-				let payee_pubkey = invoice.recover_payee_pub_key();
-				let amt_msat = invoice.amount_milli_satoshis().unwrap();
-				println!("EVENT: initiated sending {} msats to {}", amt_msat, payee_pubkey);
-				print!("> ");
-				HTLCStatus::Pending
-			}
-			Err(e) => {
-				println!("ERROR: failed to send payment: {:?}", e);
-				print!("> ");
-				HTLCStatus::Failed
-			}
-		};
-	let payment_secret = Some(invoice.payment_secret().clone());
-
-	let mut payments = payment_storage.lock().unwrap();
-	payments.insert(
-		payment_hash,
-		PaymentInfo {
-			preimage: None,
-			secret: payment_secret,
-			status,
-			amt_msat: MillisatAmount(invoice.amount_milli_satoshis()),
-		},
-	);
-}
-
-fn keysend<E: EntropySource>(
-	channel_manager: &ChannelManager, payee_pubkey: PublicKey, amt_msat: u64, entropy_source: &E,
-	payment_storage: PaymentInfoStorage, contract_id: ContractId, amt_rgb: u64,
-	ldk_data_dir: PathBuf,
-) {
-	let payment_preimage = PaymentPreimage(entropy_source.get_secure_random_bytes());
-	let payment_hash = PaymentHash(Sha256::hash(&payment_preimage.0[..]).into_inner());
-	write_rgb_payment_info_file(&ldk_data_dir, &payment_hash, contract_id, amt_rgb);
-
-	let route_params = RouteParameters {
-		payment_params: PaymentParameters::for_keysend(payee_pubkey, 40),
-		final_value_msat: amt_msat,
-	};
-	let status = match channel_manager.send_spontaneous_payment_with_retry(
-		Some(payment_preimage),
-		RecipientOnionFields::spontaneous_empty(),
-		PaymentId(payment_hash.0),
-		route_params,
-		Retry::Timeout(Duration::from_secs(10)),
-	) {
-		Ok(_payment_hash) => {
-			println!("EVENT: initiated sending {} msats to {}", amt_msat, payee_pubkey);
-			print!("> ");
-			HTLCStatus::Pending
-		}
-		Err(e) => {
-			println!("ERROR: failed to send payment: {:?}", e);
-			print!("> ");
-			HTLCStatus::Failed
-		}
-	};
-
-	let mut payments = payment_storage.lock().unwrap();
-	payments.insert(
-		payment_hash,
-		PaymentInfo {
-			preimage: None,
-			secret: None,
-			status,
-			amt_msat: MillisatAmount(Some(amt_msat)),
-		},
-	);
-}
-
-fn get_invoice(
-	amt_msat: u64, payment_storage: PaymentInfoStorage, channel_manager: &ChannelManager,
-	keys_manager: Arc<KeysManager>, network: Network, expiry_secs: u32,
-	logger: Arc<disk::FilesystemLogger>, contract_id: ContractId, amt_rgb: u64,
-) {
-	let mut payments = payment_storage.lock().unwrap();
-	let currency = match network {
-		Network::Bitcoin => Currency::Bitcoin,
-		Network::Testnet => Currency::BitcoinTestnet,
-		Network::Regtest => Currency::Regtest,
-		Network::Signet => Currency::Signet,
-	};
-	let invoice = match utils::create_invoice_from_channelmanager(
-		channel_manager,
-		keys_manager,
-		logger,
-		currency,
-		Some(amt_msat),
-		"ldk-tutorial-node".to_string(),
-		expiry_secs,
-		None,
-		Some(contract_id),
-		Some(amt_rgb),
-	) {
-		Ok(inv) => {
-			println!("SUCCESS: generated invoice: {}", inv);
-			inv
-		}
-		Err(e) => {
-			println!("ERROR: failed to create invoice: {:?}", e);
-			return;
-		}
-	};
-
-	let payment_hash = PaymentHash(invoice.payment_hash().clone().into_inner());
-	payments.insert(
-		payment_hash,
-		PaymentInfo {
-			preimage: None,
-			secret: Some(invoice.payment_secret().clone()),
-			status: HTLCStatus::Pending,
-			amt_msat: MillisatAmount(Some(amt_msat)),
-		},
-	);
-}
-
-fn close_channel(
-	channel_id: [u8; 32], counterparty_node_id: PublicKey, channel_manager: Arc<ChannelManager>,
-) {
-	match channel_manager.close_channel(&channel_id, &counterparty_node_id) {
-		Ok(()) => println!("EVENT: initiating channel close"),
-		Err(e) => println!("ERROR: failed to close channel: {:?}", e),
-	}
-}
-
-fn force_close_channel(
-	channel_id: [u8; 32], counterparty_node_id: PublicKey, channel_manager: Arc<ChannelManager>,
-) {
-	match channel_manager.force_close_broadcasting_latest_txn(&channel_id, &counterparty_node_id) {
-		Ok(()) => println!("EVENT: initiating channel force-close"),
-		Err(e) => println!("ERROR: failed to force-close channel: {:?}", e),
-	}
-}
-
 pub(crate) fn parse_peer_info(
 	peer_pubkey_and_ip_addr: String,
 ) -> Result<(PublicKey, SocketAddr), std::io::Error> {
@@ -1606,26 +1124,3 @@ pub(crate) fn parse_peer_info(
 
 	Ok((pubkey.unwrap(), peer_addr.unwrap().unwrap()))
 }
-
-pub(crate) async fn check_uncolored_utxos(ldk_data_dir: &str) -> Result<(), Error> {
-	let rgb_utxos_path = format!("{}/rgb_utxos", ldk_data_dir);
-	let serialized_utxos = fs::read_to_string(rgb_utxos_path).expect("able to read rgb utxos file");
-	let rgb_utxos: RgbUtxos = serde_json::from_str(&serialized_utxos).expect("valid rgb utxos");
-	let utxo = rgb_utxos.utxos.iter().find(|u| !u.colored);
-	match utxo {
-		Some(_) => Ok(()),
-		None => Err(Error::NoAvailableUtxos),
-	}
-}
-
-pub(crate) async fn get_utxo(ldk_data_dir: &str) -> RgbUtxo {
-	let rgb_utxos_path = format!("{}/rgb_utxos", ldk_data_dir);
-	let serialized_utxos = fs::read_to_string(rgb_utxos_path).expect("able to read rgb utxos file");
-	let rgb_utxos: RgbUtxos = serde_json::from_str(&serialized_utxos).expect("valid rgb utxos");
-	rgb_utxos.utxos.into_iter().find(|u| !u.colored).expect("at least one unlocked UTXO")
-}
-
-pub(crate) async fn mine(bitcoind_client: &BitcoindClient, num_blocks: u16) {
-	let address = bitcoind_client.get_new_address().await.to_string();
-	bitcoind_client.generate_to_adress(num_blocks, address).await;
-}
@@ -1,18 +1,27 @@
-use crate::{cli, NetworkGraph};
+use crate::hex_utils;
+use crate::{cli, HTLCStatus, MillisatAmount, NetworkGraph, PaymentInfo};
 use bitcoin::secp256k1::PublicKey;
 use bitcoin::Network;
 use chrono::Utc;
+use lightning::ln::{PaymentHash, PaymentPreimage, PaymentSecret};
 use lightning::routing::scoring::{ProbabilisticScorer, ProbabilisticScoringParameters};
 use lightning::util::logger::{Logger, Record};
 use lightning::util::ser::{ReadableArgs, Writer};
+use rgb::ContractId;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::convert::TryInto;
 use std::fs;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader};
 use std::net::SocketAddr;
 use std::path::Path;
+use std::str::FromStr;
 use std::sync::Arc;
 
+pub(crate) const INBOUND_PAYMENTS_FNAME: &str = "inbound_payments";
+pub(crate) const OUTBOUND_PAYMENTS_FNAME: &str = "outbound_payments";
+
 pub(crate) struct FilesystemLogger {
 	data_dir: String,
 }
@@ -95,3 +104,81 @@ pub(crate) fn read_scorer(
 	}
 	ProbabilisticScorer::new(params, graph, logger)
 }
+
+#[derive(Deserialize, Serialize)]
+struct PaymentRecord {
+	payment_hash: String,
+	preimage: Option<String>,
+	secret: Option<String>,
+	status: HTLCStatus,
+	amt_msat: Option<u64>,
+	rgb_contract_id: Option<String>,
+	rgb_amount: Option<u64>,
+}
+
+fn hex_to_32_bytes(hex: &str) -> Result<[u8; 32], String> {
+	hex_utils::to_vec(hex)
+		.and_then(|v| v.try_into().ok())
+		.ok_or_else(|| format!("invalid 32-byte hex string {}", hex))
+}
+
+pub(crate) fn persist_payments(path: &Path, payments: &HashMap<PaymentHash, PaymentInfo>) {
+	let records: Vec<PaymentRecord> = payments
+		.iter()
+		.map(|(payment_hash, info)| PaymentRecord {
+			payment_hash: hex_utils::hex_str(&payment_hash.0),
+			preimage: info.preimage.map(|p| hex_utils::hex_str(&p.0)),
+			secret: info.secret.map(|s| hex_utils::hex_str(&s.0)),
+			status: info.status,
+			amt_msat: info.amt_msat.0,
+			rgb_contract_id: info.rgb_contract_id.as_ref().map(|c| c.to_string()),
+			rgb_amount: info.rgb_amount,
+		})
+		.collect();
+	let serialized = serde_json::to_string(&records).expect("valid payments");
+	// Write to a temporary file first so a crash never leaves a truncated payments file
+	let tmp_path = path.with_extension("tmp");
+	fs::write(&tmp_path, serialized).expect("able to write payments file");
+	fs::rename(&tmp_path, path).expect("able to rename payments file");
+}
+
+/// Reads the payments persisted at `path`, none if the file doesn't exist yet. A corrupt file is
+/// an error rather than an empty list, as the next persist would overwrite it
+pub(crate) fn read_payments(path: &Path) -> Result<HashMap<PaymentHash, PaymentInfo>, String> {
+	let mut payments = HashMap::new();
+	let serialized = match fs::read_to_string(path) {
+		Ok(serialized) => serialized,
+		Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(payments),
+		Err(e) => return Err(format!("failed to read {}: {}", path.display(), e)),
+	};
+	let records: Vec<PaymentRecord> = serde_json::from_str(&serialized)
+		.map_err(|e| format!("invalid payments file {}: {}", path.display(), e))?;
+	for record in records {
+		let preimage = match record.preimage {
+			Some(p) => Some(PaymentPreimage(hex_to_32_bytes(&p)?)),
+			None => None,
+		};
+		let secret = match record.secret {
+			Some(s) => Some(PaymentSecret(hex_to_32_bytes(&s)?)),
+			None => None,
+		};
+		let rgb_contract_id = match record.rgb_contract_id {
+			Some(c) => {
+				Some(ContractId::from_str(&c).map_err(|_| format!("invalid contract ID {}", c))?)
+			}
+			None => None,
+		};
+		payments.insert(
+			PaymentHash(hex_to_32_bytes(&record.payment_hash)?),
+			PaymentInfo {
+				preimage,
+				secret,
+				status: record.status,
+				amt_msat: MillisatAmount(record.amt_msat),
+				rgb_contract_id,
+				rgb_amount: record.rgb_amount,
+			},
+		);
+	}
+	Ok(payments)
+}
@@ -7,9 +7,124 @@ pub enum Error {
 	#[error("Proxy error: {0}")]
 	Proxy(#[from] reqwest::Error),
 
+	#[error(
+		"ERROR: recipient {0} didn't accept the transfer in time (hint: it needs to call refresh)"
+	)]
+	AckTimeout(String),
+
+	#[error("ERROR: failed to broadcast transaction: {0}")]
+	FailedBroadcast(String),
+
+	#[error("ERROR: failed to accept channel: {0}")]
+	FailedChannelAcceptance(String),
+
+	#[error("ERROR: failed to close channel: {0}")]
+	FailedCloseChannel(String),
+
+	#[error("ERROR: failed to create invoice: {0}")]
+	FailedInvoiceCreation(String),
+
+	#[error("ERROR: failed to get transaction: {0}")]
+	FailedGetTx(String),
+
+	#[error("ERROR: failed to send onion message: {0}")]
+	FailedOnionMessage(String),
+
+	#[error("ERROR: failed to open channel: {0}")]
+	FailedOpenChannel(String),
+
+	#[error("ERROR: failed to send payment: {0}")]
+	FailedPayment(String),
+
+	#[error("ERROR: failed to connect to peer")]
+	FailedPeerConnection,
+
+	#[error("ERROR: failed to disconnect from peer: {0}")]
+	FailedPeerDisconnection(String),
+
+	#[error("ERROR: failed to exchange consignment with peers: {0}")]
+	FailedPeerTransfer(String),
+
+	#[error("ERROR: unable to post ACK: {0}")]
+	FailedPostAck(String),
+
+	#[error("ERROR: unable to post consignment")]
+	FailedPostConsignment,
+
+	#[error("ERROR: unable to create transaction: {0}")]
+	FailedTxCreation(String),
+
+	#[error("ERROR: do not have enough RGB assets")]
+	InsufficientAssets,
+
+	#[error("ERROR: not enough funds, call getaddress and send {0} satoshis")]
+	InsufficientFunds(u64),
+
+	#[error("ERROR: invalid amount: {0}")]
+	InvalidAmount(String),
+
+	#[error("ERROR: {0}")]
+	InvalidArgument(String),
+
+	#[error("ERROR: blinded_utxo must be a valid RGB blinded UTXO")]
+	InvalidBlindedUtxo,
+
+	#[error("ERROR: couldn't parse channel_id")]
+	InvalidChannelId,
+
+	#[error("ERROR: contract_id must be a valid RGB asset ID")]
+	InvalidContractId,
+
+	#[error("ERROR: invalid invoice: {0}")]
+	InvalidInvoice(String),
+
+	#[error("ERROR: name must be an ASCII string")]
+	InvalidName,
+
+	#[error("{0}")]
+	InvalidPeerInfo(String),
+
+	#[error("ERROR: couldn't parse pubkey")]
+	InvalidPubkey,
+
+	#[error("ERROR: ticker must be an ASCII string")]
+	InvalidTicker,
+
+	#[error("ERROR: RGB UTXO {0} can't become {1}")]
+	InvalidUtxoStatus(String, String),
+
+	#[error("ERROR: witness recipient must be wvout:<address>:<blinding>, with an address of the node's network")]
+	InvalidWitnessRecipient,
+
 	#[error("ERROR: no uncolored UTXOs are available (hint: call createutxos)")]
 	NoAvailableUtxos,
 
+	#[error("ERROR: the consignment transport has no endpoint for RGB channels, set proxy_url")]
+	NoConsignmentEndpoint,
+
+	#[error(
+		"ERROR: a transfer has already been sent to this receive request (hint: call refresh)"
+	)]
+	ReceiveAlreadyFulfilled,
+
+	#[error("ERROR: mine command is available only on regtest")]
+	RegtestOnly,
+
+	#[error("ERROR: recipient {0} rejected the transfer")]
+	TransferRejected(String),
+
+	#[error("ERROR: unknown invoice")]
+	UnknownInvoice,
+
+	#[error("ERROR: unknown pending channel")]
+	UnknownPendingChannel,
+
+	#[error("ERROR: unknown receive request")]
+	UnknownReceive,
+
 	#[error("ERROR: unknown RGB contract ID")]
 	UnknownContractId,
+
+	#[error("ERROR: {0} uncolored UTXOs are already available")]
+	UtxosAlreadyAvailable(usize),
 }
@@ -1,3 +1,4 @@
+mod api;
 #[allow(deprecated)]
 mod args;
 mod bdk_utils;
@@ -7,27 +8,42 @@ mod convert;
 mod disk;
 mod error;
 mod hex_utils;
+mod p2p;
+mod policy;
 mod proxy;
 mod rgb_utils;
-
-use crate::bdk_utils::{broadcast_tx, get_bdk_wallet, get_bdk_wallet_seckey, sync_wallet};
+mod rpc;
+mod transport;
+mod utxo_store;
+
+use crate::api::{NodeContext, PendingChannel, PendingChannelStorage};
+use crate::bdk_utils::{
+	broadcast_tx, get_bdk_wallet, get_bdk_wallet_seckey, get_fee_rate, sync_wallet,
+	sync_wallet_and_utxos, ElectrumConfig,
+};
 use crate::bitcoind_client::BitcoindClient;
 use crate::disk::FilesystemLogger;
-use crate::proxy::post_consignment;
-use crate::rgb_utils::get_rgb_node_client;
+use crate::p2p::{save_channel_consignment, ConsignmentMessageHandler};
+use crate::policy::ChannelPolicy;
+use crate::proxy::{post_consignment, JsonRpcResponse};
+use crate::rgb_utils::is_channel_rgb;
 use crate::rgb_utils::{get_asset_owned_values, RgbUtilities};
+use crate::rgb_utils::{get_rgb_network, get_rgb_node_client};
+use crate::transport::{
+	ConsignmentTransport, DirTransport, PeerTransport, ProxyTransport, TransportConfig,
+};
+use crate::utxo_store::RgbUtxoStore;
 use amplify::bmap;
 use bdk::bitcoin::OutPoint;
 use bdk::database::SqliteDatabase;
+use bdk::SignOptions;
 use bdk::Wallet;
-use bdk::{FeeRate, SignOptions};
 use bitcoin::blockdata::transaction::Transaction;
 use bitcoin::hashes::hex::FromHex;
-use bitcoin::hashes::Hash;
 use bitcoin::network::constants::Network;
-use bitcoin::secp256k1::Secp256k1;
+use bitcoin::secp256k1::{PublicKey, Secp256k1};
 use bitcoin::util::bip32::{ChildNumber, ExtendedPrivKey};
-use bitcoin::{BlockHash, PackedLockTime, Script, Sequence, TxIn, Witness};
+use bitcoin::{BlockHash, PackedLockTime, Script, Sequence, TxIn, Txid, Witness};
 use bitcoin_bech32::WitnessProgram;
 use bp::seals::txout::CloseMethod;
 use lightning::chain;
@@ -43,10 +59,10 @@ use lightning::ln::channelmanager;
 use lightning::ln::channelmanager::{
 	ChainParameters, ChannelManagerReadArgs, SimpleArcChannelManager,
 };
-use lightning::ln::peer_handler::{IgnoringMessageHandler, MessageHandler, SimpleArcPeerManager};
+use lightning::ln::peer_handler::{self, IgnoringMessageHandler, MessageHandler};
 use lightning::ln::{PaymentHash, PaymentPreimage, PaymentSecret};
 use lightning::onion_message::SimpleArcOnionMessenger;
-use lightning::rgb_utils::{get_rgb_channel_info, RgbUtxo, RgbUtxos};
+use lightning::rgb_utils::get_rgb_channel_info;
 use lightning::routing::gossip;
 use lightning::routing::gossip::{NodeId, P2PGossipSync};
 use lightning::routing::router::DefaultRouter;
@@ -59,18 +75,18 @@ use lightning_block_sync::SpvClient;
 use lightning_block_sync::UnboundedCache;
 use lightning_net_tokio::SocketDescriptor;
 use lightning_persister::FilesystemPersister;
-use lnpbp::chain::{Chain, GENESIS_HASH_REGTEST};
 use psbt::{Psbt, PsbtVersion};
 use rand::{thread_rng, Rng};
 use reqwest::Client as RestClient;
-use rgb::Node;
 use rgb::{
 	seal, Assignment, Consignment, EndpointValueMap, PedersenStrategy, SealEndpoint, StateTransfer,
 	TypedAssignments,
 };
+use rgb::{ContractId, Node};
 use rgb_rpc::{Client, ContractValidity, Reveal};
+use serde::{Deserialize, Serialize};
 use std::collections::hash_map::Entry;
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::convert::TryInto;
 use std::fmt;
 use std::fs;
@@ -82,21 +98,51 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime};
 use strict_encoding::{StrictDecode, StrictEncode};
+use tokio::runtime::Handle;
+use tokio::sync::broadcast;
 
-const FEE_RATE: f32 = 1.5;
+pub(crate) const FEE_RATE: f32 = 1.5;
 const ELECTRUM_URL_REGTEST: &str = "127.0.0.1:50001";
 const ELECTRUM_URL_TESTNET: &str = "ssl://electrum.iriswallet.com:50013";
+const ELECTRUM_URL_SIGNET: &str = "ssl://mempool.space:60602";
+const ELECTRUM_URL_MAINNET: &str = "ssl://electrum.blockstream.info:50002";
 const PROXY_URL_REGTEST: &str = "http://127.0.0.1:3000/json-rpc";
 const PROXY_URL_TESTNET: &str = "https://proxy.iriswallet.com/json-rpc";
-const PROXY_TIMEOUT: u8 = 90;
+const PROXY_TIMEOUT: u64 = 90;
+const ELECTRUM_RETRY: u8 = 3;
+const ELECTRUM_TIMEOUT: u8 = 5;
+const ELECTRUM_STOP_GAP: usize = 20;
 const UTXO_SIZE_SAT: u64 = 1000;
-
+const UTXO_NUM: u8 = 10;
+const UTXO_TOP_UP_INTERVAL: u64 = 60;
+const REFRESH_INTERVAL: u64 = 30;
+pub(crate) const REFRESH_MAX_INTERVAL: u64 = 600;
+// longer than REFRESH_MAX_INTERVAL, so that recipients refresh at least once in the meantime
+const ACK_TIMEOUT: u64 = 1800;
+// transfer events kept for a subscriber that doesn't fetch them, older ones are dropped
+const TRANSFER_EVENTS_CAPACITY: usize = 100;
+// how long a node without the consignment of a channel closing transaction waits for it
+const CLOSING_CONSIGNMENT_POLLS: u32 = 60;
+const CLOSING_CONSIGNMENT_POLL_SECS: u64 = 10;
+
+#[derive(Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub(crate) enum HTLCStatus {
 	Pending,
 	Succeeded,
 	Failed,
 }
 
+impl fmt::Display for HTLCStatus {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			HTLCStatus::Pending => write!(f, "pending"),
+			HTLCStatus::Succeeded => write!(f, "succeeded"),
+			HTLCStatus::Failed => write!(f, "failed"),
+		}
+	}
+}
+
 pub(crate) struct MillisatAmount(Option<u64>);
 
 impl fmt::Display for MillisatAmount {
@@ -113,6 +159,8 @@ pub(crate) struct PaymentInfo {
 	secret: Option<PaymentSecret>,
 	status: HTLCStatus,
 	amt_msat: MillisatAmount,
+	rgb_contract_id: Option<ContractId>,
+	rgb_amount: Option<u64>,
 }
 
 pub(crate) type PaymentInfoStorage = Arc<Mutex<HashMap<PaymentHash, PaymentInfo>>>;
@@ -126,13 +174,14 @@ type ChainMonitor = chainmonitor::ChainMonitor<
 	Arc<FilesystemPersister>,
 >;
 
-pub(crate) type PeerManager = SimpleArcPeerManager<
+pub(crate) type PeerManager = peer_handler::PeerManager<
 	SocketDescriptor,
-	ChainMonitor,
-	BitcoindClient,
-	BitcoindClient,
-	BitcoindClient,
-	FilesystemLogger,
+	Arc<ChannelManager>,
+	Arc<P2PGossipSync<Arc<NetworkGraph>, Arc<BitcoindClient>, Arc<FilesystemLogger>>>,
+	Arc<OnionMessenger>,
+	Arc<FilesystemLogger>,
+	Arc<ConsignmentMessageHandler>,
+	Arc<KeysManager>,
 >;
 
 pub(crate) type ChannelManager =
@@ -142,13 +191,67 @@ pub(crate) type NetworkGraph = gossip::NetworkGraph<Arc<FilesystemLogger>>;
 
 type OnionMessenger = SimpleArcOnionMessenger<FilesystemLogger>;
 
+/// Drops a channel whose funding transaction could not be built, LDK has nothing to broadcast yet
+fn abandon_funding(
+	channel_manager: &ChannelManager, temporary_channel_id: &[u8; 32],
+	counterparty_node_id: &PublicKey, err: &str,
+) {
+	println!("\nERROR: unable to fund channel, closing it: {}", err);
+	let _ = channel_manager
+		.force_close_without_broadcasting_txn(temporary_channel_id, counterparty_node_id);
+	print!("> ");
+	io::stdout().flush().unwrap();
+}
+
+/// Sends the consignment of a transaction spending a channel's funding output, like a cooperative
+/// close, to the counterparty through the consignment transport. A node that doesn't have it
+/// waits for the counterparty to send it instead
+async fn exchange_closing_consignment(
+	transport: Arc<dyn ConsignmentTransport>, ldk_data_dir: String, counterparty: PublicKey,
+	txid: Txid,
+) {
+	let consignment_path = PathBuf::from(format!("{ldk_data_dir}/consignment_{txid}"));
+	if consignment_path.exists() {
+		if let Err(e) =
+			transport.post_channel_consignment(counterparty, txid, &consignment_path).await
+		{
+			println!("\nWARNING: consignment of channel transaction {txid} not sent. {e}");
+			print!("> ");
+			io::stdout().flush().unwrap();
+		}
+		return;
+	}
+	for _ in 0..CLOSING_CONSIGNMENT_POLLS {
+		tokio::time::sleep(Duration::from_secs(CLOSING_CONSIGNMENT_POLL_SECS)).await;
+		// the peer transport saves it as soon as it's complete
+		if consignment_path.exists() {
+			return;
+		}
+		if let Ok(Some(consignment)) = transport.get_channel_consignment(txid).await {
+			if let Err(e) = save_channel_consignment(&ldk_data_dir, txid, consignment) {
+				println!("\nWARNING: {e}");
+				print!("> ");
+				io::stdout().flush().unwrap();
+			}
+			return;
+		}
+	}
+	println!("\nWARNING: consignment of channel transaction {txid} not received");
+	print!("> ");
+	io::stdout().flush().unwrap();
+}
+
 async fn handle_ldk_events(
 	channel_manager: &Arc<ChannelManager>, bitcoind_client: &BitcoindClient,
 	network_graph: &NetworkGraph, keys_manager: &KeysManager,
 	inbound_payments: &PaymentInfoStorage, outbound_payments: &PaymentInfoStorage,
 	network: Network, event: Event, ldk_data_dir: String, rgb_node_client: Arc<Mutex<Client>>,
-	proxy_client: Arc<RestClient>, proxy_url: String,
-	wallet_arc: Arc<Mutex<Wallet<SqliteDatabase>>>, electrum_url: String,
+	proxy_client: Arc<RestClient>, proxy_url: Option<String>,
+	wallet_arc: Arc<Mutex<Wallet<SqliteDatabase>>>, electrum_config: ElectrumConfig,
+	channel_policy: &ChannelPolicy, pending_channels: &PendingChannelStorage,
+	rgb_utxos: &Arc<RgbUtxoStore>, fee_rate: Option<f32>,
+	consignment_msg_handler: &Arc<ConsignmentMessageHandler>,
+	transport: &Arc<dyn ConsignmentTransport>,
 ) {
 	match event {
 		Event::FundingGenerationReady {
@@ -172,16 +275,74 @@ async fn handle_ldk_events(
 			let script = Script::from_byte_iter(addr.clone().into_iter().map(|b| Ok(b)))
 				.expect("valid script");
 
+			if !is_channel_rgb(&temporary_channel_id, &PathBuf::from(&ldk_data_dir)) {
+				// Bitcoin-only channel: fund it with uncolored UTXOs, no consignment needed
+				let wallet = wallet_arc.lock().unwrap();
+				let unspendable_utxos = rgb_utxos.unspendable(&wallet);
+				let mut builder = wallet.build_tx();
+				builder
+					.unspendable(unspendable_utxos)
+					.fee_rate(get_fee_rate(
+						bitcoind_client,
+						network,
+						fee_rate,
+						ConfirmationTarget::Normal,
+					))
+					.add_recipient(script, channel_value_satoshis);
+				let mut psbt = match builder.finish() {
+					Ok((psbt, _)) => psbt,
+					Err(e) => {
+						abandon_funding(
+							channel_manager,
+							&temporary_channel_id,
+							&counterparty_node_id,
+							&e.to_string(),
+						);
+						return;
+					}
+				};
+				wallet.sign(&mut psbt, SignOptions::default()).expect("able to sign");
+				let funding_tx = psbt.extract_tx();
+
+				if channel_manager
+					.funding_transaction_generated(
+						&temporary_channel_id,
+						&counterparty_node_id,
+						funding_tx,
+					)
+					.is_err()
+				{
+					println!(
+						"\nERROR: Channel went away before we could fund it. The peer disconnected or refused the channel.");
+				} else {
+					println!("FUNDING COMPLETED");
+				}
+				print!("> ");
+				io::stdout().flush().unwrap();
+				return;
+			}
+
 			let (rgb_info, _) =
 				get_rgb_channel_info(&temporary_channel_id, &PathBuf::from(&ldk_data_dir.clone()));
 			let channel_rgb_amount: u64 = rgb_info.local_rgb_amount;
-			let asset_owned_values = get_asset_owned_values(
+			let asset_owned_values = match get_asset_owned_values(
 				rgb_info.contract_id,
 				rgb_node_client.clone(),
 				wallet_arc.clone(),
-				electrum_url.clone(),
-			)
-			.expect("known contract");
+				electrum_config.clone(),
+				rgb_utxos,
+			) {
+				Ok(asset_owned_values) => asset_owned_values,
+				Err(e) => {
+					abandon_funding(
+						channel_manager,
+						&temporary_channel_id,
+						&counterparty_node_id,
+						&e.to_string(),
+					);
+					return;
+				}
+			};
 			let mut rgb_inputs: Vec<OutPoint> = vec![];
 			let mut input_amount: u64 = 0;
 			for owned_value in asset_owned_values {
@@ -195,24 +356,23 @@ async fn handle_ldk_events(
 			}
 			let rgb_change_amount = input_amount - channel_rgb_amount;
 
-			let rgb_utxos_path = format!("{}/rgb_utxos", ldk_data_dir);
-			let serialized_utxos =
-				fs::read_to_string(&rgb_utxos_path).expect("able to read rgb utxos file");
-			let mut rgb_utxos: RgbUtxos =
-				serde_json::from_str(&serialized_utxos).expect("valid rgb utxos");
+			let wallet = wallet_arc.lock().unwrap();
 			let unspendable_utxos: Vec<OutPoint> = rgb_utxos
-				.utxos
-				.iter()
-				.filter(|u| !rgb_inputs.contains(&u.outpoint))
-				.map(|u| u.outpoint)
+				.unspendable(&wallet)
+				.into_iter()
+				.filter(|o| !rgb_inputs.contains(o))
 				.collect();
-			let wallet = wallet_arc.lock().unwrap();
 			let mut builder = wallet.build_tx();
 			builder
 				.add_utxos(&rgb_inputs)
 				.expect("valid utxos")
 				.unspendable(unspendable_utxos)
-				.fee_rate(FeeRate::from_sat_per_vb(FEE_RATE))
+				.fee_rate(get_fee_rate(
+					bitcoind_client,
+					network,
+					fee_rate,
+					ConfirmationTarget::Normal,
+				))
 				.ordering(bdk::wallet::tx_builder::TxOrdering::Untouched)
 				.add_recipient(script, channel_value_satoshis);
 			let mut beneficiaries: EndpointValueMap = bmap![
@@ -232,7 +392,18 @@ async fn handle_ldk_events(
 					rgb_change_amount,
 				);
 			}
-			let psbt = builder.finish().expect("valid psbt finish").0;
+			let psbt = match builder.finish() {
+				Ok((psbt, _)) => psbt,
+				Err(e) => {
+					abandon_funding(
+						channel_manager,
+						&temporary_channel_id,
+						&counterparty_node_id,
+						&e.to_string(),
+					);
+					return;
+				}
+			};
 			let input_outpoints_bt: BTreeSet<OutPoint> = rgb_inputs.clone().into_iter().collect();
 			let mut rgb_client = rgb_node_client.lock().unwrap();
 			let (mut psbt, consignment) = rgb_client.send_rgb(
@@ -251,12 +422,9 @@ async fn handle_ldk_events(
 			let consignment_path = format!("{}/consignment_{funding_txid}", ldk_data_dir);
 			consignment.strict_file_save(consignment_path.clone()).expect("consignment save ok");
 
+			rgb_utxos.spend(&rgb_inputs);
 			if rgb_change_amount > 0 {
-				let rgb_change_utxo =
-					RgbUtxo { outpoint: OutPoint { txid: funding_txid, vout: 2 }, colored: true };
-				rgb_utxos.utxos.push(rgb_change_utxo);
-				let serialized_utxos = serde_json::to_string(&rgb_utxos).expect("valid rgb utxos");
-				fs::write(rgb_utxos_path, serialized_utxos).expect("able to write rgb utxos file");
+				rgb_utxos.add(OutPoint { txid: funding_txid, vout: 2 }, true);
 
 				let funding_consignment_path =
 					format!("{}/consignment_{}", ldk_data_dir, hex::encode(&temporary_channel_id));
@@ -266,19 +434,46 @@ async fn handle_ldk_events(
 			}
 
 			drop(rgb_client);
-			let proxy_ref = (*proxy_client).clone();
-			let proxy_url_copy = proxy_url.clone();
+			// the counterparty's LDK fork fetches the consignment from the endpoint given when
+			// opening the channel, which is the proxy when the transport has none
+			let proxy = match transport.consignment_endpoint() {
+				Some(_) => None,
+				None => proxy_url.map(|url| ((*proxy_client).clone(), url)),
+			};
+			let transport = Arc::clone(transport);
 			let channel_manager_copy = channel_manager.clone();
+			let rgb_utxos_copy = Arc::clone(rgb_utxos);
 			tokio::spawn(async move {
-				let res = post_consignment(
-					proxy_ref,
-					&proxy_url_copy,
-					funding_txid.to_string(),
-					consignment_path.into(),
-				)
-				.await;
-				if res.is_err() || res.unwrap().result.is_none() {
-					println!("ERROR: unable to post consignment");
+				if let Some((proxy_client, proxy_url)) = proxy {
+					let res = post_consignment(
+						proxy_client,
+						&proxy_url,
+						funding_txid.to_string(),
+						consignment_path.clone().into(),
+					)
+					.await;
+					if !matches!(res, Ok(JsonRpcResponse { result: Some(_), .. })) {
+						println!("\nWARNING: funding consignment not posted to the proxy");
+					}
+				}
+				let res = transport
+					.post_channel_consignment(
+						counterparty_node_id,
+						funding_txid,
+						Path::new(&consignment_path),
+					)
+					.await;
+				if let Err(e) = res {
+					println!(
+						"\nERROR: unable to send the funding consignment, closing channel: {e}"
+					);
+					rgb_utxos_copy.discard_tx(&funding_tx);
+					let _ = channel_manager_copy.force_close_without_broadcasting_txn(
+						&temporary_channel_id,
+						&counterparty_node_id,
+					);
+					print!("> ");
+					io::stdout().flush().unwrap();
 					return;
 				}
 
@@ -365,9 +560,15 @@ async fn handle_ldk_events(
 						secret: payment_secret,
 						status: HTLCStatus::Succeeded,
 						amt_msat: MillisatAmount(Some(amount_msat)),
+						rgb_contract_id: None,
+						rgb_amount: None,
 					});
 				}
 			}
+			disk::persist_payments(
+				Path::new(&format!("{}/{}", ldk_data_dir, disk::INBOUND_PAYMENTS_FNAME)),
+				&payments,
+			);
 			println!("Event::PaymentClaimed end");
 		}
 		Event::PaymentSent { payment_preimage, payment_hash, fee_paid_msat, .. } => {
@@ -392,10 +593,86 @@ async fn handle_ldk_events(
 					io::stdout().flush().unwrap();
 				}
 			}
+			disk::persist_payments(
+				Path::new(&format!("{}/{}", ldk_data_dir, disk::OUTBOUND_PAYMENTS_FNAME)),
+				&payments,
+			);
 			print!("> ");
 		}
-		Event::OpenChannelRequest { .. } => {
-			// Unreachable, we don't set manually_accept_inbound_channels
+		Event::OpenChannelRequest {
+			temporary_channel_id,
+			counterparty_node_id,
+			funding_satoshis,
+			push_msat,
+			..
+		} => {
+			let peer_pubkey = hex_utils::hex_str(&counterparty_node_id.serialize());
+			let mut pending = pending_channels.lock().unwrap();
+			let num_open_channels = channel_manager
+				.list_channels()
+				.iter()
+				.filter(|c| {
+					c.counterparty.node_id == counterparty_node_id
+						&& c.channel_id != temporary_channel_id
+				})
+				.count();
+			let num_pending_channels =
+				pending.values().filter(|p| p.peer_pubkey == peer_pubkey).count();
+			let num_peer_channels = num_open_channels + num_pending_channels;
+
+			// the funding consignment is fetched with open_channel, so the channel's RGB info is
+			// already known and checked before accepting
+			let ldk_data_dir_path = PathBuf::from(&ldk_data_dir);
+			let rgb_info = if is_channel_rgb(&temporary_channel_id, &ldk_data_dir_path) {
+				Some(get_rgb_channel_info(&temporary_channel_id, &ldk_data_dir_path).0)
+			} else {
+				None
+			};
+			let policy_check = channel_policy
+				.check_peer(&counterparty_node_id, num_peer_channels)
+				.and_then(|_| match &rgb_info {
+					Some(rgb_info) => {
+						channel_policy.check_rgb(&rgb_info.contract_id, rgb_info.remote_rgb_amount)
+					}
+					None => Ok(()),
+				});
+			if let Err(reason) = policy_check {
+				println!(
+					"\nEVENT: rejected channel {} from peer {}: {}",
+					hex_utils::hex_str(&temporary_channel_id),
+					peer_pubkey,
+					reason
+				);
+				let _ = channel_manager.force_close_without_broadcasting_txn(
+					&temporary_channel_id,
+					&counterparty_node_id,
+				);
+			} else if channel_policy.manual_review {
+				println!(
+					"\nEVENT: channel {} from peer {} awaiting review (see listpendingchannels)",
+					hex_utils::hex_str(&temporary_channel_id),
+					peer_pubkey
+				);
+				pending.insert(
+					temporary_channel_id,
+					PendingChannel {
+						temporary_channel_id: hex_utils::hex_str(&temporary_channel_id),
+						peer_pubkey,
+						funding_satoshis,
+						push_msat,
+						rgb_contract_id: rgb_info.as_ref().map(|i| i.contract_id.to_string()),
+						rgb_amount: rgb_info.as_ref().map(|i| i.remote_rgb_amount),
+					},
+				);
+			} else if let Err(e) = channel_manager.accept_inbound_channel(
+				&temporary_channel_id,
+				&counterparty_node_id,
+				0,
+			) {
+				println!("\nERROR: failed to accept inbound channel: {:?}", e);
+			}
+			print!("> ");
+			io::stdout().flush().unwrap();
 		}
 		Event::PaymentPathSuccessful { .. } => {}
 		Event::PaymentPathFailed { .. } => {}
@@ -414,6 +691,10 @@ async fn handle_ldk_events(
 			if payments.contains_key(&payment_hash) {
 				let payment = payments.get_mut(&payment_hash).unwrap();
 				payment.status = HTLCStatus::Failed;
+				disk::persist_payments(
+					Path::new(&format!("{}/{}", ldk_data_dir, disk::OUTBOUND_PAYMENTS_FNAME)),
+					&payments,
+				);
 			}
 		}
 		Event::PaymentForwarded {
@@ -494,6 +775,7 @@ async fn handle_ldk_events(
 			let tx_feerate =
 				bitcoind_client.get_est_sat_per_1000_weight(ConfirmationTarget::Normal);
 			let mut rgb_client = rgb_node_client.lock().unwrap();
+			let mut btc_outputs = vec![];
 
 			for outp in output_descriptors {
 				let outpoint = match outp {
@@ -509,6 +791,11 @@ async fn handle_ldk_events(
 				};
 
 				let consignment_path = format!("{ldk_data_dir}/consignment_{}", outpoint.txid);
+				if !PathBuf::from(&consignment_path).exists() {
+					// Output of a Bitcoin-only channel, swept below without RGB transitions
+					btc_outputs.push(*outp);
+					continue;
+				}
 				let consignment = StateTransfer::strict_file_load(&consignment_path).expect("ok");
 
 				let reveal = Reveal {
@@ -570,15 +857,26 @@ async fn handle_ldk_events(
 							network,
 							signer.payment_key,
 						);
-						sync_wallet(&intermediate_wallet, electrum_url.clone());
+						sync_wallet(&intermediate_wallet, electrum_config.clone());
 						let mut builder = intermediate_wallet.build_tx();
 						builder
 							.add_utxos(&rgb_inputs)
 							.expect("valid utxos")
-							.fee_rate(FeeRate::from_sat_per_vb(FEE_RATE))
+							.fee_rate(get_fee_rate(
+								bitcoind_client,
+								network,
+								fee_rate,
+								ConfirmationTarget::Background,
+							))
 							.manually_selected_only()
 							.drain_to(address.script_pubkey());
-						let psbt = builder.finish().expect("valid psbt finish").0;
+						let psbt = match builder.finish() {
+							Ok((psbt, _)) => psbt,
+							Err(e) => {
+								println!("ERROR: unable to sweep output {}: {}", outpoint, e);
+								continue;
+							}
+						};
 
 						let (mut psbt, consignment) = rgb_client.send_rgb(
 							contract_id,
@@ -658,15 +956,26 @@ async fn handle_ldk_events(
 							network,
 							secret.private_key,
 						);
-						sync_wallet(&intermediate_wallet, electrum_url.clone());
+						sync_wallet(&intermediate_wallet, electrum_config.clone());
 						let mut builder = intermediate_wallet.build_tx();
 						builder
 							.add_utxos(&rgb_inputs)
 							.expect("valid utxos")
-							.fee_rate(FeeRate::from_sat_per_vb(FEE_RATE))
+							.fee_rate(get_fee_rate(
+								bitcoind_client,
+								network,
+								fee_rate,
+								ConfirmationTarget::Background,
+							))
 							.manually_selected_only()
 							.drain_to(address.script_pubkey());
-						let psbt = builder.finish().expect("valid psbt finish").0;
+						let psbt = match builder.finish() {
+							Ok((psbt, _)) => psbt,
+							Err(e) => {
+								println!("ERROR: unable to sweep output {}: {}", outpoint, e);
+								continue;
+							}
+						};
 
 						let (mut psbt, consignment) = rgb_client.send_rgb(
 							contract_id,
@@ -684,8 +993,11 @@ async fn handle_ldk_events(
 					}
 				};
 
-				broadcast_tx(&tx, electrum_url.clone());
-				sync_wallet(&wallet, electrum_url.clone());
+				if let Err(e) = broadcast_tx(&tx, electrum_config.clone()) {
+					println!("{e}");
+					continue;
+				}
+				sync_wallet_and_utxos(&wallet, electrum_config.clone(), rgb_utxos);
 
 				let reveal = Reveal {
 					blinding_factor: 777,
@@ -700,9 +1012,33 @@ async fn handle_ldk_events(
 
 			drop(rgb_client);
 
+			if !btc_outputs.is_empty() {
+				let wallet = wallet_arc.lock().unwrap();
+				let address = wallet
+					.get_address(bdk::wallet::AddressIndex::New)
+					.expect("valid address")
+					.address;
+				match keys_manager.spend_spendable_outputs(
+					&btc_outputs,
+					Vec::new(),
+					address.script_pubkey(),
+					tx_feerate,
+					&secp_ctx,
+				) {
+					Ok(spending_tx) => {
+						if let Err(e) = broadcast_tx(&spending_tx, electrum_config.clone()) {
+							println!("{e}");
+						}
+						sync_wallet_and_utxos(&wallet, electrum_config.clone(), rgb_utxos);
+					}
+					Err(()) => println!("ERROR: unable to sweep {} outputs", btc_outputs.len()),
+				}
+			}
+
 			println!("Event::SpendableOutputs complete");
 		}
-		Event::ChannelPending { channel_id, counterparty_node_id, .. } => {
+		Event::ChannelPending { channel_id, counterparty_node_id, funding_txo, .. } => {
+			consignment_msg_handler.track_channel(funding_txo, counterparty_node_id);
 			println!(
 				"\nEVENT: Channel {} with peer {} is pending awaiting funding lock-in!",
 				hex_utils::hex_str(&channel_id),
@@ -757,9 +1093,9 @@ async fn handle_ldk_events(
 			print!("> ");
 			io::stdout().flush().unwrap();
 		}
-		Event::DiscardFunding { .. } => {
-			// A "real" node should probably "lock" the UTXOs spent in funding transactions until
-			// the funding transaction either confirms, or this event is generated.
+		Event::DiscardFunding { transaction, .. } => {
+			// the funding transaction will never be broadcast, make its RGB UTXOs usable again
+			rgb_utxos.discard_tx(&transaction);
 		}
 		Event::HTLCIntercepted { .. } => {}
 	}
@@ -818,26 +1154,30 @@ async fn start_ldk() {
 	}
 
 	// RGB setup
-	let (electrum_url, proxy_url, rgb_network) = match args.network {
-		bitcoin::Network::Testnet => (ELECTRUM_URL_TESTNET, PROXY_URL_TESTNET, Chain::Testnet3),
-		bitcoin::Network::Regtest => (
-			ELECTRUM_URL_REGTEST,
-			PROXY_URL_REGTEST,
-			Chain::Regtest(BlockHash::from_slice(GENESIS_HASH_REGTEST).unwrap()),
-		),
-		_ => {
-			println!("ERROR: PoC does not support selected network");
-			return;
-		}
+	let (default_electrum_url, default_proxy_url) = match args.network {
+		bitcoin::Network::Bitcoin => (ELECTRUM_URL_MAINNET, None),
+		bitcoin::Network::Testnet => (ELECTRUM_URL_TESTNET, Some(PROXY_URL_TESTNET)),
+		bitcoin::Network::Signet => (ELECTRUM_URL_SIGNET, None),
+		bitcoin::Network::Regtest => (ELECTRUM_URL_REGTEST, Some(PROXY_URL_REGTEST)),
 	};
+	// only optional with a transport other than the proxy, see `Config::into_user_info`
+	let proxy_url = args.proxy_url.clone().or(default_proxy_url.map(String::from));
+	let rgb_network = get_rgb_network(args.network);
 	let rgb_node_client =
 		Arc::new(Mutex::new(get_rgb_node_client(args.rgb_node_port, rgb_network.clone())));
 	fs::write(format!("{ldk_data_dir}/rgb_node_port"), args.rgb_node_port.to_string())
 		.expect("able to write");
 	fs::write(format!("{ldk_data_dir}/rgb_node_network"), rgb_network.to_string())
 		.expect("able to write");
+	let electrum_config = ElectrumConfig {
+		url: args.electrum_url.clone().unwrap_or_else(|| default_electrum_url.to_string()),
+		retry: args.electrum_retry.unwrap_or(ELECTRUM_RETRY),
+		timeout: args.electrum_timeout.unwrap_or(ELECTRUM_TIMEOUT),
+		stop_gap: args.electrum_stop_gap.unwrap_or(ELECTRUM_STOP_GAP),
+	};
+	let fee_rate = args.fee_rate;
 	let rest_client = RestClient::builder()
-		.timeout(Duration::from_secs(PROXY_TIMEOUT as u64))
+		.timeout(Duration::from_secs(args.proxy_timeout.unwrap_or(PROXY_TIMEOUT)))
 		.build()
 		.expect("valid proxy");
 	let proxy_client = Arc::new(rest_client);
@@ -943,6 +1283,7 @@ async fn start_ldk() {
 	// Step 11: Initialize the ChannelManager
 	let mut user_config = UserConfig::default();
 	user_config.channel_handshake_limits.force_announced_channel_preference = false;
+	user_config.manually_accept_inbound_channels = true;
 	let mut restarting_node = true;
 	let (channel_manager_blockhash, channel_manager) = {
 		if let Ok(mut f) = fs::File::open(format!("{}/manager", ldk_data_dir.clone())) {
@@ -989,14 +1330,6 @@ async fn start_ldk() {
 		}
 	};
 
-	// initialize RGB UTXOs file on first run
-	if !restarting_node {
-		let rgb_utxos = RgbUtxos { utxos: vec![] };
-		let rgb_utxos_path = format!("{}/rgb_utxos", ldk_data_dir);
-		let serialized_utxos = serde_json::to_string(&rgb_utxos).expect("valid rgb utxos");
-		fs::write(rgb_utxos_path, serialized_utxos).expect("able to write rgb utxos file");
-	}
-
 	// Step 12: Sync ChannelMonitors and ChannelManager to chain tip
 	let mut chain_listener_channel_monitors = Vec::new();
 	let mut cache = UnboundedCache::new();
@@ -1067,15 +1400,66 @@ async fn start_ldk() {
 		route_handler: gossip_sync.clone(),
 		onion_message_handler: onion_messenger.clone(),
 	};
+	let consignment_msg_handler = Arc::new(ConsignmentMessageHandler::new(
+		ldk_data_dir.clone(),
+		Arc::clone(&channel_manager),
+	));
 	let peer_manager: Arc<PeerManager> = Arc::new(PeerManager::new(
 		lightning_msg_handler,
 		current_time.try_into().unwrap(),
 		&ephemeral_bytes,
 		logger.clone(),
-		IgnoringMessageHandler {},
+		Arc::clone(&consignment_msg_handler),
 		Arc::clone(&keys_manager),
 	));
 
+	let transport: Arc<dyn ConsignmentTransport> = match &args.consignment_transport {
+		TransportConfig::Proxy => Arc::new(ProxyTransport {
+			client: proxy_client.clone(),
+			url: proxy_url.clone().expect("proxy_url set for the proxy transport"),
+		}),
+		TransportConfig::Dir(dir) => {
+			fs::create_dir_all(dir).expect("able to create consignment dir");
+			Arc::new(DirTransport { dir: dir.clone() })
+		}
+		TransportConfig::Peer => Arc::new(PeerTransport {
+			handler: Arc::clone(&consignment_msg_handler),
+			peer_manager: Arc::clone(&peer_manager),
+		}),
+	};
+
+	// the LDK fork only saves the consignments of channel closing transactions locally
+	for channel in channel_manager.list_channels() {
+		if let Some(funding_txo) = channel.funding_txo {
+			consignment_msg_handler
+				.track_channel(funding_txo.into_bitcoin_outpoint(), channel.counterparty.node_id);
+		}
+	}
+	let exchanged_closing_txids = Mutex::new(HashSet::new());
+	let closing_handler = Arc::clone(&consignment_msg_handler);
+	let closing_transport = Arc::clone(&transport);
+	let closing_ldk_data_dir = ldk_data_dir.clone();
+	let handle = Handle::current();
+	bitcoind_client.set_broadcast_hook(Box::new(move |tx| {
+		let counterparty = match closing_handler.channel_counterparty(tx) {
+			Some(counterparty) => counterparty,
+			None => return,
+		};
+		// LDK broadcasts the same transaction again until it confirms
+		if !exchanged_closing_txids.lock().unwrap().insert(tx.txid()) {
+			return;
+		}
+		// in a cooperative close the counterparty pushes the consignment after its closing_signed,
+		// which makes this node broadcast the transaction first
+		closing_handler.expect_closing_consignment(tx.txid(), counterparty);
+		handle.spawn(exchange_closing_consignment(
+			Arc::clone(&closing_transport),
+			closing_ldk_data_dir.clone(),
+			counterparty,
+			tx.txid(),
+		));
+	}));
+
 	// ## Running LDK
 	// Step 16: Initialize networking
 
@@ -1118,9 +1502,30 @@ async fn start_ldk() {
 		}
 	});
 
-	// TODO: persist payment info to disk
-	let inbound_payments: PaymentInfoStorage = Arc::new(Mutex::new(HashMap::new()));
-	let outbound_payments: PaymentInfoStorage = Arc::new(Mutex::new(HashMap::new()));
+	let inbound_payments = match disk::read_payments(Path::new(&format!(
+		"{}/{}",
+		ldk_data_dir,
+		disk::INBOUND_PAYMENTS_FNAME
+	))) {
+		Ok(payments) => payments,
+		Err(e) => {
+			println!("ERROR: {}", e);
+			return;
+		}
+	};
+	let outbound_payments = match disk::read_payments(Path::new(&format!(
+		"{}/{}",
+		ldk_data_dir,
+		disk::OUTBOUND_PAYMENTS_FNAME
+	))) {
+		Ok(payments) => payments,
+		Err(e) => {
+			println!("ERROR: {}", e);
+			return;
+		}
+	};
+	let inbound_payments: PaymentInfoStorage = Arc::new(Mutex::new(inbound_payments));
+	let outbound_payments: PaymentInfoStorage = Arc::new(Mutex::new(outbound_payments));
 
 	// Step 18: Handle LDK Events
 	let channel_manager_event_listener = Arc::clone(&channel_manager);
@@ -1134,6 +1539,15 @@ async fn start_ldk() {
 	let rgb_node_client_copy = rgb_node_client.clone();
 	let proxy_client_copy = proxy_client.clone();
 	let wallet_copy = wallet.clone();
+	let proxy_url_copy = proxy_url.clone();
+	let electrum_config_copy = electrum_config.clone();
+	let channel_policy = args.channel_policy.clone();
+	let pending_channels: PendingChannelStorage = Arc::new(Mutex::new(HashMap::new()));
+	let pending_channels_event_listener = Arc::clone(&pending_channels);
+	let rgb_utxos = Arc::new(RgbUtxoStore::open(&ldk_data_dir));
+	let rgb_utxos_event_listener = Arc::clone(&rgb_utxos);
+	let consignment_msg_handler_event_listener = Arc::clone(&consignment_msg_handler);
+	let transport_event_listener = Arc::clone(&transport);
 	let event_handler = move |event: Event| {
 		let channel_manager_event_listener = Arc::clone(&channel_manager_event_listener);
 		let bitcoind_client_event_listener = Arc::clone(&bitcoind_client_event_listener);
@@ -1145,6 +1559,14 @@ async fn start_ldk() {
 		let rgb_node_client_copy = rgb_node_client_copy.clone();
 		let proxy_client_copy = proxy_client_copy.clone();
 		let wallet_copy = wallet_copy.clone();
+		let proxy_url_copy = proxy_url_copy.clone();
+		let electrum_config_copy = electrum_config_copy.clone();
+		let channel_policy = channel_policy.clone();
+		let pending_channels_event_listener = Arc::clone(&pending_channels_event_listener);
+		let rgb_utxos_event_listener = Arc::clone(&rgb_utxos_event_listener);
+		let consignment_msg_handler_event_listener =
+			Arc::clone(&consignment_msg_handler_event_listener);
+		let transport_event_listener = Arc::clone(&transport_event_listener);
 		async move {
 			handle_ldk_events(
 				&channel_manager_event_listener,
@@ -1158,9 +1580,15 @@ async fn start_ldk() {
 				ldk_data_dir_copy,
 				rgb_node_client_copy,
 				proxy_client_copy,
-				proxy_url.to_string(),
+				proxy_url_copy,
 				wallet_copy,
-				electrum_url.to_string(),
+				electrum_config_copy,
+				&channel_policy,
+				&pending_channels_event_listener,
+				&rgb_utxos_event_listener,
+				fee_rate,
+				&consignment_msg_handler_event_listener,
+				&transport_event_listener,
 			)
 			.await;
 		}
@@ -1236,6 +1664,7 @@ async fn start_ldk() {
 	// to avoid churn in the global network graph.
 	let peer_man = Arc::clone(&peer_manager);
 	let network = args.network;
+	let rpc_listening_port = args.rpc_listening_port;
 	if !args.ldk_announced_listen_addr.is_empty() {
 		tokio::spawn(async move {
 			let mut interval = tokio::time::interval(Duration::from_secs(60));
@@ -1250,26 +1679,125 @@ async fn start_ldk() {
 		});
 	}
 
-	// Start the CLI.
-	cli::poll_for_user_input(
-		Arc::clone(&peer_manager),
-		Arc::clone(&channel_manager),
-		Arc::clone(&keys_manager),
-		Arc::clone(&network_graph),
-		Arc::clone(&onion_messenger),
+	let ctx = Arc::new(NodeContext {
+		peer_manager: Arc::clone(&peer_manager),
+		channel_manager: Arc::clone(&channel_manager),
+		chain_monitor: Arc::clone(&chain_monitor),
+		keys_manager: Arc::clone(&keys_manager),
+		network_graph: Arc::clone(&network_graph),
+		onion_messenger: Arc::clone(&onion_messenger),
 		inbound_payments,
 		outbound_payments,
-		ldk_data_dir.clone(),
+		ldk_data_dir: ldk_data_dir.clone(),
 		network,
-		Arc::clone(&logger),
-		Arc::clone(&bitcoind_client),
-		Arc::clone(&rgb_node_client),
-		proxy_client.clone(),
+		logger: Arc::clone(&logger),
+		bitcoind_client: Arc::clone(&bitcoind_client),
+		rgb_node_client: Arc::clone(&rgb_node_client),
+		proxy_client: proxy_client.clone(),
 		proxy_url,
-		wallet.clone(),
-		electrum_url.to_string(),
-	)
-	.await;
+		transport,
+		wallet: wallet.clone(),
+		electrum_config,
+		pending_channels,
+		rgb_utxos,
+		fee_rate,
+		utxo_size_sat: args.utxo_size_sat.unwrap_or(UTXO_SIZE_SAT),
+		utxo_num: args.utxo_num.unwrap_or(UTXO_NUM),
+		min_uncolored_utxos: args.min_uncolored_utxos,
+		ack_timeout: args.ack_timeout.unwrap_or(ACK_TIMEOUT),
+		refresh_lock: tokio::sync::Mutex::new(()),
+		transfer_events: broadcast::channel(TRANSFER_EVENTS_CAPACITY).0,
+	});
+
+	// Keep enough uncolored UTXOs available, if requested.
+	if ctx.min_uncolored_utxos.is_some() {
+		let ctx = Arc::clone(&ctx);
+		tokio::spawn(async move {
+			let mut interval = tokio::time::interval(Duration::from_secs(UTXO_TOP_UP_INTERVAL));
+			let mut last_error = None;
+			loop {
+				interval.tick().await;
+				let ctx = Arc::clone(&ctx);
+				let res = tokio::task::spawn_blocking(move || api::top_up_utxos(&ctx))
+					.await
+					.expect("top-up task doesn't panic");
+				// only report an error once, until the top-up succeeds again
+				let error = res.err().map(|e| e.to_string());
+				if error.is_some() && error != last_error {
+					let error = error.as_ref().unwrap();
+					let error = error.strip_prefix("ERROR: ").unwrap_or(error);
+					println!("\nWARNING: unable to top up RGB UTXOs: {}", error);
+					print!("> ");
+					io::stdout().flush().unwrap();
+				}
+				last_error = error;
+			}
+		});
+	}
+
+	// Accept incoming RGB transfers in the background, unless disabled. Polling backs off while
+	// the proxy is unreachable.
+	let refresh_interval = args.refresh_interval.unwrap_or(REFRESH_INTERVAL);
+	if refresh_interval > 0 {
+		let ctx = Arc::clone(&ctx);
+		tokio::spawn(async move {
+			let mut delay = refresh_interval;
+			let mut last_error = None;
+			loop {
+				tokio::time::sleep(Duration::from_secs(delay)).await;
+				// refreshing syncs the wallet and calls the RGB node, which block
+				let refresh_ctx = Arc::clone(&ctx);
+				let res = tokio::task::spawn_blocking(move || {
+					Handle::current().block_on(api::refresh(&refresh_ctx))
+				})
+				.await
+				.expect("refresh task doesn't panic");
+				let (received, rejected, error) = match res {
+					Ok(res) => (res.received, res.rejected, res.transport_error),
+					Err(e) => (vec![], vec![], Some(e.to_string())),
+				};
+				let mut printed = false;
+				for transfer in received {
+					println!(
+						"\nEVENT: received {} {}",
+						cli::display_rgb_amount(&ctx, &transfer.contract_id, transfer.amount),
+						transfer.ticker
+					);
+					printed = true;
+				}
+				for transfer in rejected {
+					println!(
+						"\nEVENT: rejected transfer to {}: {}",
+						transfer.recipient, transfer.reason
+					);
+					printed = true;
+				}
+				// only report an error once, until refreshing succeeds again
+				if error.is_some() && error != last_error {
+					println!("\nWARNING: unable to refresh transfers: {}", error.as_ref().unwrap());
+					printed = true;
+				}
+				if printed {
+					print!("> ");
+					io::stdout().flush().unwrap();
+				}
+				delay = match error {
+					Some(_) => (delay * 2).min(REFRESH_MAX_INTERVAL.max(refresh_interval)),
+					None => refresh_interval,
+				};
+				last_error = error;
+			}
+		});
+	}
+
+	// Start the JSON-RPC server, if requested.
+	if let Some(rpc_port) = rpc_listening_port {
+		let transfer_events = ctx.transfer_events.subscribe();
+		tokio::spawn(rpc::start_rpc_server(rpc_port, Arc::clone(&ctx), transfer_events));
+	}
+
+	// Start the CLI.
+	cli::poll_for_user_input(ctx).await;
 
 	// Disconnect our peers and stop accepting new connections. This ensures we don't continue
 	// updating our channel data after we've stopped the background processor.
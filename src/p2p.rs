@@ -0,0 +1,652 @@
+use crate::error::Error;
+use crate::ChannelManager;
+use amplify::s;
+use bitcoin::secp256k1::PublicKey;
+use bitcoin::{OutPoint, Transaction, Txid};
+use lightning::ln::features::{InitFeatures, NodeFeatures};
+use lightning::ln::msgs::{DecodeError, ErrorAction, LightningError};
+use lightning::ln::peer_handler::CustomMessageHandler;
+use lightning::ln::wire::{CustomMessageReader, Type};
+use lightning::util::ser::{Readable, Writeable, Writer};
+use rgb::StateTransfer;
+use strict_encoding::strict_deserialize;
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// odd types, so that peers not knowing them ignore the messages
+const CONSIGNMENT_REQUEST_TYPE: u16 = 40101;
+const CONSIGNMENT_CHUNK_TYPE: u16 = 40103;
+const CONSIGNMENT_ACK_TYPE: u16 = 40105;
+const CHANNEL_CONSIGNMENT_CHUNK_TYPE: u16 = 40107;
+
+// messages are limited to 65535 bytes, leaving room for the chunk's header
+const CHUNK_LEN: usize = 60000;
+const MAX_CHUNKS: u16 = 256;
+
+// largest consignment that can be streamed to a peer
+const MAX_CONSIGNMENT_LEN: usize = CHUNK_LEN * MAX_CHUNKS as usize;
+
+// chunks handed to the PeerManager each time it processes events, so that a big consignment
+// doesn't hold up the other messages
+const CHUNKS_PER_ROUND: usize = 4;
+
+// offers, requests and received consignments are dropped after a day, streams that stalled sooner
+const EXPIRY: Duration = Duration::from_secs(24 * 3600);
+const PARTIAL_EXPIRY: Duration = Duration::from_secs(600);
+
+// consignments a peer can be streaming at once, each one taking up to MAX_CONSIGNMENT_LEN
+const MAX_PARTIALS_PER_PEER: usize = 4;
+
+/// A piece of a consignment, which is complete once all the `total` chunks have been received
+#[derive(Debug)]
+pub(crate) struct Chunk {
+	index: u16,
+	total: u16,
+	data: Vec<u8>,
+}
+
+impl Writeable for Chunk {
+	fn write<W: Writer>(&self, w: &mut W) -> Result<(), io::Error> {
+		self.index.write(w)?;
+		self.total.write(w)?;
+		self.data.write(w)
+	}
+}
+
+impl Readable for Chunk {
+	fn read<R: io::Read>(r: &mut R) -> Result<Self, DecodeError> {
+		let chunk = Chunk {
+			index: Readable::read(r)?,
+			total: Readable::read(r)?,
+			data: Readable::read(r)?,
+		};
+		if chunk.total == 0 || chunk.total > MAX_CHUNKS || chunk.index >= chunk.total {
+			return Err(DecodeError::InvalidValue);
+		}
+		Ok(chunk)
+	}
+}
+
+fn into_chunks(consignment: &[u8]) -> Vec<Chunk> {
+	let total = ((consignment.len().max(1) + CHUNK_LEN - 1) / CHUNK_LEN) as u16;
+	(0..total)
+		.map(|index| {
+			let start = index as usize * CHUNK_LEN;
+			let end = (start + CHUNK_LEN).min(consignment.len());
+			Chunk { index, total, data: consignment[start..end].to_vec() }
+		})
+		.collect()
+}
+
+/// Messages exchanged with peers to move consignments over the Lightning connection
+#[derive(Debug)]
+pub(crate) enum ConsignmentMessage {
+	/// Asks a peer for the consignment it has for a recipient
+	Request { recipient_id: String },
+	/// Part of the consignment sent to a recipient, in answer to a request
+	Chunk { recipient_id: String, chunk: Chunk },
+	/// Tells the sender whether the recipient accepted the consignment
+	Ack { recipient_id: String, ack: bool },
+	/// Part of the consignment of a channel transaction, sent to the channel counterparty
+	ChannelChunk { txid: Txid, chunk: Chunk },
+}
+
+impl Type for ConsignmentMessage {
+	fn type_id(&self) -> u16 {
+		match self {
+			ConsignmentMessage::Request { .. } => CONSIGNMENT_REQUEST_TYPE,
+			ConsignmentMessage::Chunk { .. } => CONSIGNMENT_CHUNK_TYPE,
+			ConsignmentMessage::Ack { .. } => CONSIGNMENT_ACK_TYPE,
+			ConsignmentMessage::ChannelChunk { .. } => CHANNEL_CONSIGNMENT_CHUNK_TYPE,
+		}
+	}
+}
+
+impl Writeable for ConsignmentMessage {
+	fn write<W: Writer>(&self, w: &mut W) -> Result<(), io::Error> {
+		match self {
+			ConsignmentMessage::Request { recipient_id } => {
+				recipient_id.as_bytes().to_vec().write(w)
+			}
+			ConsignmentMessage::Chunk { recipient_id, chunk } => {
+				recipient_id.as_bytes().to_vec().write(w)?;
+				chunk.write(w)
+			}
+			ConsignmentMessage::Ack { recipient_id, ack } => {
+				recipient_id.as_bytes().to_vec().write(w)?;
+				ack.write(w)
+			}
+			ConsignmentMessage::ChannelChunk { txid, chunk } => {
+				txid.write(w)?;
+				chunk.write(w)
+			}
+		}
+	}
+}
+
+fn read_recipient_id<R: io::Read>(r: &mut R) -> Result<String, DecodeError> {
+	let bytes: Vec<u8> = Readable::read(r)?;
+	String::from_utf8(bytes).map_err(|_| DecodeError::InvalidValue)
+}
+
+/// What a stream of chunks carries
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+enum StreamId {
+	Transfer(String),
+	Channel(Txid),
+}
+
+/// The chunks received so far from a peer for a consignment
+struct PartialConsignment {
+	chunks: Vec<Option<Vec<u8>>>,
+	received: usize,
+	updated: Instant,
+}
+
+impl PartialConsignment {
+	fn new(total: u16) -> Self {
+		PartialConsignment {
+			chunks: vec![None; total as usize],
+			received: 0,
+			updated: Instant::now(),
+		}
+	}
+
+	/// Adds the chunk, returning whether all of them have been received
+	fn add(&mut self, chunk: Chunk) -> bool {
+		// a stream restarted with a different length replaces the previous one
+		if self.chunks.len() != chunk.total as usize {
+			*self = PartialConsignment::new(chunk.total);
+		}
+		let slot = &mut self.chunks[chunk.index as usize];
+		if slot.is_none() {
+			self.received += 1;
+		}
+		*slot = Some(chunk.data);
+		self.updated = Instant::now();
+		self.received == self.chunks.len()
+	}
+
+	fn into_consignment(self) -> Vec<u8> {
+		self.chunks.into_iter().flatten().flatten().collect()
+	}
+}
+
+type PartialConsignments = HashMap<(PublicKey, StreamId), PartialConsignment>;
+
+/// Adds the chunk to the ones received from `peer`, returning the consignment once complete.
+/// A new stream is refused while the peer already has `MAX_PARTIALS_PER_PEER` in progress
+fn add_partial_chunk(
+	partial: &mut PartialConsignments, peer: PublicKey, stream_id: StreamId, chunk: Chunk,
+) -> Result<Option<Vec<u8>>, String> {
+	let key = (peer, stream_id);
+	if !partial.contains_key(&key)
+		&& partial.keys().filter(|(p, _)| *p == peer).count() >= MAX_PARTIALS_PER_PEER
+	{
+		return Err(s!("too many consignments streamed at once"));
+	}
+	let consignment =
+		partial.entry(key.clone()).or_insert_with(|| PartialConsignment::new(chunk.total));
+	if !consignment.add(chunk) {
+		return Ok(None);
+	}
+	Ok(Some(partial.remove(&key).expect("partial consignment").into_consignment()))
+}
+
+/// A consignment offered to the recipient's node
+struct OutgoingConsignment {
+	consignment: Vec<u8>,
+	/// The only peer the consignment is sent to and whose ACK is accepted
+	peer: PublicKey,
+	ack: Option<bool>,
+	created: Instant,
+}
+
+/// The consignments received from peers for a recipient. They are validated in the order they
+/// arrived and only the first valid one gets an ACK, the others are ignored
+struct IncomingConsignments {
+	candidates: VecDeque<(PublicKey, Vec<u8>)>,
+	/// Peers whose consignment was rejected, ignored from then on
+	discarded: HashSet<PublicKey>,
+	/// Set once the first candidate has been acknowledged, no more are accepted then
+	acked: bool,
+	created: Instant,
+}
+
+impl IncomingConsignments {
+	fn new() -> Self {
+		IncomingConsignments {
+			candidates: VecDeque::new(),
+			discarded: HashSet::new(),
+			acked: false,
+			created: Instant::now(),
+		}
+	}
+
+	fn accepts(&self, peer: &PublicKey) -> bool {
+		!self.acked
+			&& !self.discarded.contains(peer)
+			&& !self.candidates.iter().any(|(p, _)| p == peer)
+	}
+}
+
+/// Serves the consignments posted by this node and collects the ones it asks its peers for.
+/// Consignments are streamed in chunks, each one in its own message.
+///
+/// Transfer consignments and ACKs are kept in memory, keyed by recipient ID like on the proxy,
+/// and only the ones this node requested are accepted from peers. Entries expire, so that peers
+/// can't fill the memory with them. Channel consignments are pushed to the channel
+/// counterparty, which saves them in its data directory if they are for the funding transaction
+/// of one of their channels or for a closing transaction it has seen broadcast.
+pub(crate) struct ConsignmentMessageHandler {
+	ldk_data_dir: String,
+	channel_manager: Arc<ChannelManager>,
+	outgoing: Mutex<HashMap<String, OutgoingConsignment>>,
+	incoming: Mutex<HashMap<String, IncomingConsignments>>,
+	requested: Mutex<HashMap<String, Instant>>,
+	partial: Mutex<PartialConsignments>,
+	/// Counterparties by funding outpoint, kept after the channels close
+	channel_peers: Mutex<HashMap<OutPoint, PublicKey>>,
+	/// Closing transactions whose consignment the counterparty can push
+	closing_txids: Mutex<HashMap<Txid, (PublicKey, Instant)>>,
+	pending_msgs: Mutex<Vec<(PublicKey, ConsignmentMessage)>>,
+	pending_chunks: Mutex<VecDeque<(PublicKey, ConsignmentMessage)>>,
+}
+
+impl ConsignmentMessageHandler {
+	pub(crate) fn new(ldk_data_dir: String, channel_manager: Arc<ChannelManager>) -> Self {
+		ConsignmentMessageHandler {
+			ldk_data_dir,
+			channel_manager,
+			outgoing: Mutex::new(HashMap::new()),
+			incoming: Mutex::new(HashMap::new()),
+			requested: Mutex::new(HashMap::new()),
+			partial: Mutex::new(HashMap::new()),
+			channel_peers: Mutex::new(HashMap::new()),
+			closing_txids: Mutex::new(HashMap::new()),
+			pending_msgs: Mutex::new(vec![]),
+			pending_chunks: Mutex::new(VecDeque::new()),
+		}
+	}
+
+	/// Makes the consignment available to `peer`, the recipient's node, when it asks for
+	/// `recipient_id`
+	pub(crate) fn offer(
+		&self, recipient_id: String, peer: PublicKey, consignment: Vec<u8>,
+	) -> Result<(), Error> {
+		if consignment.len() > MAX_CONSIGNMENT_LEN {
+			return Err(Error::FailedPeerTransfer(s!("consignment too big")));
+		}
+		let outgoing =
+			OutgoingConsignment { consignment, peer, ack: None, created: Instant::now() };
+		self.outgoing.lock().unwrap().insert(recipient_id, outgoing);
+		Ok(())
+	}
+
+	/// Asks the given peers for the consignment sent to `recipient_id`
+	pub(crate) fn request(&self, recipient_id: &str, peers: &[PublicKey]) {
+		self.requested.lock().unwrap().insert(recipient_id.to_string(), Instant::now());
+		let mut pending_msgs = self.pending_msgs.lock().unwrap();
+		for peer in peers {
+			let msg = ConsignmentMessage::Request { recipient_id: recipient_id.to_string() };
+			pending_msgs.push((*peer, msg));
+		}
+	}
+
+	/// The first consignment received for `recipient_id` that hasn't been discarded, if a peer
+	/// has sent all of it
+	pub(crate) fn received(&self, recipient_id: &str) -> Option<Vec<u8>> {
+		let incoming = self.incoming.lock().unwrap();
+		incoming.get(recipient_id)?.candidates.front().map(|(_, c)| c.clone())
+	}
+
+	/// Drops the consignment returned by `received` without answering its peer, whose
+	/// consignments for `recipient_id` are ignored from then on
+	pub(crate) fn discard(&self, recipient_id: &str) -> Result<(), Error> {
+		let mut incoming = self.incoming.lock().unwrap();
+		let consignments = match incoming.get_mut(recipient_id) {
+			Some(consignments) if !consignments.acked => consignments,
+			_ => return Err(Error::FailedPeerTransfer(s!("no consignment to discard"))),
+		};
+		if let Some((peer, _)) = consignments.candidates.pop_front() {
+			consignments.discarded.insert(peer);
+		}
+		Ok(())
+	}
+
+	/// Sends the ACK or NACK to the peer the consignment returned by `received` came from.
+	/// Once acknowledged, the consignments of other peers are ignored
+	pub(crate) fn send_ack(&self, recipient_id: &str, ack: bool) -> Result<(), Error> {
+		let mut incoming = self.incoming.lock().unwrap();
+		let consignments = incoming.get_mut(recipient_id);
+		let peer = match consignments.as_ref().and_then(|c| c.candidates.front()) {
+			Some((peer, _)) => *peer,
+			None => return Err(Error::FailedPeerTransfer(s!("no consignment received"))),
+		};
+		if ack {
+			let consignments = consignments.expect("received consignment");
+			consignments.acked = true;
+			consignments.candidates.truncate(1);
+			self.requested.lock().unwrap().remove(recipient_id);
+		}
+		let msg = ConsignmentMessage::Ack { recipient_id: recipient_id.to_string(), ack };
+		self.pending_msgs.lock().unwrap().push((peer, msg));
+		Ok(())
+	}
+
+	/// Whether the recipient accepted the consignment offered for `recipient_id`, dropping the
+	/// consignment once it has answered
+	pub(crate) fn ack(&self, recipient_id: &str) -> Option<bool> {
+		let mut outgoing = self.outgoing.lock().unwrap();
+		let ack = outgoing.get(recipient_id)?.ack;
+		if ack.is_some() {
+			outgoing.remove(recipient_id);
+		}
+		ack
+	}
+
+	/// Remembers the counterparty of the channel funded by `funding_outpoint`, so that the
+	/// consignments of the transactions closing it can be exchanged after the channel is gone
+	pub(crate) fn track_channel(&self, funding_outpoint: OutPoint, counterparty: PublicKey) {
+		self.channel_peers.lock().unwrap().insert(funding_outpoint, counterparty);
+	}
+
+	/// The counterparty of the channel whose funding output `tx` spends, if any
+	pub(crate) fn channel_counterparty(&self, tx: &Transaction) -> Option<PublicKey> {
+		let channel_peers = self.channel_peers.lock().unwrap();
+		tx.input.iter().find_map(|input| channel_peers.get(&input.previous_output).copied())
+	}
+
+	/// Accepts the consignment of the closing transaction `txid` from the channel counterparty
+	pub(crate) fn expect_closing_consignment(&self, txid: Txid, counterparty: PublicKey) {
+		self.closing_txids.lock().unwrap().insert(txid, (counterparty, Instant::now()));
+	}
+
+	/// Streams the consignment of the channel transaction `txid` to the channel counterparty
+	pub(crate) fn push_channel_consignment(
+		&self, peer: PublicKey, txid: Txid, consignment: &[u8],
+	) -> Result<(), Error> {
+		if consignment.len() > MAX_CONSIGNMENT_LEN {
+			return Err(Error::FailedPeerTransfer(s!("consignment too big")));
+		}
+		let mut pending_chunks = self.pending_chunks.lock().unwrap();
+		for chunk in into_chunks(consignment) {
+			pending_chunks.push_back((peer, ConsignmentMessage::ChannelChunk { txid, chunk }));
+		}
+		Ok(())
+	}
+
+	fn add_chunk(
+		&self, peer: PublicKey, stream_id: StreamId, chunk: Chunk,
+	) -> Result<Option<Vec<u8>>, LightningError> {
+		let mut partial = self.partial.lock().unwrap();
+		add_partial_chunk(&mut partial, peer, stream_id, chunk).map_err(ignored)
+	}
+
+	/// Drops the entries nobody has used for too long
+	fn remove_expired(&self) {
+		self.outgoing.lock().unwrap().retain(|_, o| o.created.elapsed() < EXPIRY);
+		self.incoming.lock().unwrap().retain(|_, i| i.created.elapsed() < EXPIRY);
+		self.requested.lock().unwrap().retain(|_, requested| requested.elapsed() < EXPIRY);
+		self.partial.lock().unwrap().retain(|_, p| p.updated.elapsed() < PARTIAL_EXPIRY);
+		self.closing_txids.lock().unwrap().retain(|_, (_, expected)| expected.elapsed() < EXPIRY);
+	}
+
+	/// Whether `txid` funds a channel with `peer` or closes one of them
+	fn is_channel_txid(&self, peer: &PublicKey, txid: &Txid) -> bool {
+		let funds_channel = self.channel_manager.list_channels().iter().any(|c| {
+			c.counterparty.node_id == *peer && c.funding_txo.map_or(false, |o| o.txid == *txid)
+		});
+		funds_channel
+			|| self.channel_peers.lock().unwrap().iter().any(|(o, p)| o.txid == *txid && p == peer)
+			|| self.closing_txids.lock().unwrap().get(txid).map_or(false, |(p, _)| p == peer)
+	}
+}
+
+/// Saves a channel consignment where the channel's other consignments are, unless it's already
+/// known
+pub(crate) fn save_channel_consignment(
+	ldk_data_dir: &str, txid: Txid, consignment: Vec<u8>,
+) -> Result<(), String> {
+	let consignment_path = Path::new(ldk_data_dir).join(format!("consignment_{txid}"));
+	if consignment_path.exists() {
+		return Ok(());
+	}
+	let invalid = || format!("invalid consignment for channel transaction {}", txid);
+	let transfer = strict_deserialize::<StateTransfer>(&consignment).map_err(|_| invalid())?;
+	if !transfer.anchored_bundles().any(|ab| ab.0.txid == txid) {
+		return Err(invalid());
+	}
+	fs::write(consignment_path, consignment).map_err(|e| e.to_string())
+}
+
+fn ignored(err: String) -> LightningError {
+	LightningError { err, action: ErrorAction::IgnoreError }
+}
+
+impl CustomMessageReader for ConsignmentMessageHandler {
+	type CustomMessage = ConsignmentMessage;
+
+	fn read<R: io::Read>(
+		&self, message_type: u16, buffer: &mut R,
+	) -> Result<Option<ConsignmentMessage>, DecodeError> {
+		let msg = match message_type {
+			CONSIGNMENT_REQUEST_TYPE => {
+				ConsignmentMessage::Request { recipient_id: read_recipient_id(buffer)? }
+			}
+			CONSIGNMENT_CHUNK_TYPE => ConsignmentMessage::Chunk {
+				recipient_id: read_recipient_id(buffer)?,
+				chunk: Readable::read(buffer)?,
+			},
+			CONSIGNMENT_ACK_TYPE => ConsignmentMessage::Ack {
+				recipient_id: read_recipient_id(buffer)?,
+				ack: Readable::read(buffer)?,
+			},
+			CHANNEL_CONSIGNMENT_CHUNK_TYPE => ConsignmentMessage::ChannelChunk {
+				txid: Readable::read(buffer)?,
+				chunk: Readable::read(buffer)?,
+			},
+			_ => return Ok(None),
+		};
+		Ok(Some(msg))
+	}
+}
+
+impl CustomMessageHandler for ConsignmentMessageHandler {
+	fn handle_custom_message(
+		&self, msg: ConsignmentMessage, sender_node_id: &PublicKey,
+	) -> Result<(), LightningError> {
+		match msg {
+			ConsignmentMessage::Request { recipient_id } => {
+				let mut outgoing = self.outgoing.lock().unwrap();
+				let offered = match outgoing.get(&recipient_id) {
+					Some(offered) if offered.peer == *sender_node_id => offered,
+					_ => return Ok(()),
+				};
+				let mut pending_chunks = self.pending_chunks.lock().unwrap();
+				for chunk in into_chunks(&offered.consignment) {
+					let recipient_id = recipient_id.clone();
+					let msg = ConsignmentMessage::Chunk { recipient_id, chunk };
+					pending_chunks.push_back((*sender_node_id, msg));
+				}
+			}
+			ConsignmentMessage::Chunk { recipient_id, chunk } => {
+				let requested = self.requested.lock().unwrap().contains_key(&recipient_id);
+				let accepted = self
+					.incoming
+					.lock()
+					.unwrap()
+					.get(&recipient_id)
+					.map_or(true, |i| i.accepts(sender_node_id));
+				if !requested || !accepted {
+					return Err(ignored(format!("unexpected consignment for {}", recipient_id)));
+				}
+				let stream_id = StreamId::Transfer(recipient_id.clone());
+				if let Some(consignment) = self.add_chunk(*sender_node_id, stream_id, chunk)? {
+					let mut incoming = self.incoming.lock().unwrap();
+					let consignments =
+						incoming.entry(recipient_id).or_insert_with(IncomingConsignments::new);
+					consignments.candidates.push_back((*sender_node_id, consignment));
+				}
+			}
+			ConsignmentMessage::Ack { recipient_id, ack } => {
+				let mut outgoing = self.outgoing.lock().unwrap();
+				match outgoing.get_mut(&recipient_id) {
+					Some(offered) if offered.peer == *sender_node_id => offered.ack = Some(ack),
+					_ => return Err(ignored(format!("unexpected ACK for {}", recipient_id))),
+				}
+			}
+			ConsignmentMessage::ChannelChunk { txid, chunk } => {
+				if !self.is_channel_txid(sender_node_id, &txid) {
+					return Err(ignored(format!("unexpected consignment for channel tx {}", txid)));
+				}
+				if let Some(consignment) =
+					self.add_chunk(*sender_node_id, StreamId::Channel(txid), chunk)?
+				{
+					save_channel_consignment(&self.ldk_data_dir, txid, consignment)
+						.map_err(ignored)?;
+				}
+			}
+		}
+		Ok(())
+	}
+
+	fn get_and_clear_pending_msg(&self) -> Vec<(PublicKey, ConsignmentMessage)> {
+		self.remove_expired();
+		let mut msgs = std::mem::take(&mut *self.pending_msgs.lock().unwrap());
+		let mut pending_chunks = self.pending_chunks.lock().unwrap();
+		let num_chunks = pending_chunks.len().min(CHUNKS_PER_ROUND);
+		msgs.extend(pending_chunks.drain(..num_chunks));
+		msgs
+	}
+
+	fn provided_node_features(&self) -> NodeFeatures {
+		NodeFeatures::empty()
+	}
+
+	fn provided_init_features(&self, _their_node_id: &PublicKey) -> InitFeatures {
+		InitFeatures::empty()
+	}
+}
+
+#[cfg(test)]
+mod p2p_tests {
+	use super::*;
+
+	fn reassemble(chunks: Vec<Chunk>) -> Option<Vec<u8>> {
+		let mut partial = PartialConsignment::new(chunks[0].total);
+		let mut complete = false;
+		for chunk in chunks {
+			assert!(!complete, "complete before the last chunk");
+			complete = partial.add(chunk);
+		}
+		if complete {
+			Some(partial.into_consignment())
+		} else {
+			None
+		}
+	}
+
+	#[test]
+	fn test_chunks_round_trip() {
+		let consignment: Vec<u8> = (0..CHUNK_LEN * 2 + 10).map(|i| i as u8).collect();
+		let chunks = into_chunks(&consignment);
+		assert_eq!(chunks.len(), 3);
+		assert_eq!(reassemble(chunks), Some(consignment));
+
+		// an empty consignment still takes one chunk
+		let chunks = into_chunks(&[]);
+		assert_eq!(chunks.len(), 1);
+		assert_eq!(reassemble(chunks), Some(vec![]));
+	}
+
+	#[test]
+	fn test_chunks_out_of_order() {
+		let consignment: Vec<u8> = (0..CHUNK_LEN * 3).map(|i| (i / 7) as u8).collect();
+		let mut chunks = into_chunks(&consignment);
+		chunks.reverse();
+		chunks.swap(0, 1);
+		assert_eq!(reassemble(chunks), Some(consignment.clone()));
+
+		// duplicates don't count twice
+		let mut partial = PartialConsignment::new(3);
+		let mut chunks = into_chunks(&consignment).into_iter();
+		let first = chunks.next().unwrap();
+		let duplicate = Chunk { index: first.index, total: first.total, data: first.data.clone() };
+		assert!(!partial.add(first));
+		assert!(!partial.add(duplicate));
+		assert!(!partial.add(chunks.next().unwrap()));
+		assert!(partial.add(chunks.next().unwrap()));
+		assert_eq!(partial.into_consignment(), consignment);
+	}
+
+	#[test]
+	fn test_chunks_restarted_stream() {
+		let old: Vec<u8> = vec![1; CHUNK_LEN * 3];
+		let new: Vec<u8> = vec![2; CHUNK_LEN + 1];
+		let mut partial = PartialConsignment::new(3);
+		let mut old_chunks = into_chunks(&old).into_iter();
+		assert!(!partial.add(old_chunks.next().unwrap()));
+		assert!(!partial.add(old_chunks.next().unwrap()));
+
+		// a different length drops the chunks received so far
+		let mut new_chunks = into_chunks(&new).into_iter();
+		assert!(!partial.add(new_chunks.next().unwrap()));
+		assert!(partial.add(new_chunks.next().unwrap()));
+		assert_eq!(partial.into_consignment(), new);
+	}
+
+	#[test]
+	fn test_max_partials_per_peer() {
+		let secp = bitcoin::secp256k1::Secp256k1::new();
+		let key = |b| {
+			let secret_key = bitcoin::secp256k1::SecretKey::from_slice(&[b; 32]).unwrap();
+			PublicKey::from_secret_key(&secp, &secret_key)
+		};
+		let (peer, other_peer) = (key(1), key(2));
+		let first_chunk = || Chunk { index: 0, total: 2, data: vec![0] };
+		let stream_id = |i: usize| StreamId::Transfer(i.to_string());
+		let mut partial = PartialConsignments::new();
+		for i in 0..MAX_PARTIALS_PER_PEER {
+			let res = add_partial_chunk(&mut partial, peer, stream_id(i), first_chunk());
+			assert_eq!(res, Ok(None));
+		}
+
+		// a new stream is refused, while the ones in progress and other peers' still go through
+		let res =
+			add_partial_chunk(&mut partial, peer, stream_id(MAX_PARTIALS_PER_PEER), first_chunk());
+		assert!(res.is_err());
+		let res = add_partial_chunk(&mut partial, other_peer, stream_id(0), first_chunk());
+		assert_eq!(res, Ok(None));
+		let last_chunk = Chunk { index: 1, total: 2, data: vec![1] };
+		let res = add_partial_chunk(&mut partial, peer, stream_id(0), last_chunk);
+		assert_eq!(res, Ok(Some(vec![0, 1])));
+
+		// a completed stream frees its slot
+		let res =
+			add_partial_chunk(&mut partial, peer, stream_id(MAX_PARTIALS_PER_PEER), first_chunk());
+		assert_eq!(res, Ok(None));
+	}
+
+	#[test]
+	fn test_max_chunks() {
+		let chunks = into_chunks(&vec![0; MAX_CONSIGNMENT_LEN]);
+		assert_eq!(chunks.len(), MAX_CHUNKS as usize);
+		let last = chunks.last().unwrap();
+		let decoded: Chunk = Readable::read(&mut io::Cursor::new(last.encode())).unwrap();
+		assert_eq!((decoded.index, decoded.total), (MAX_CHUNKS - 1, MAX_CHUNKS));
+
+		let too_many = Chunk { index: 0, total: MAX_CHUNKS + 1, data: vec![] };
+		let res: Result<Chunk, _> = Readable::read(&mut io::Cursor::new(too_many.encode()));
+		assert!(matches!(res, Err(DecodeError::InvalidValue)));
+
+		let out_of_range = Chunk { index: 2, total: 2, data: vec![] };
+		let res: Result<Chunk, _> = Readable::read(&mut io::Cursor::new(out_of_range.encode()));
+		assert!(matches!(res, Err(DecodeError::InvalidValue)));
+	}
+}
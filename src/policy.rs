@@ -0,0 +1,112 @@
+use bitcoin::secp256k1::PublicKey;
+use rgb::ContractId;
+
+/// Rules applied to inbound channel requests
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ChannelPolicy {
+	/// Queue compliant requests for `acceptchannel`/`rejectchannel` instead of accepting them
+	pub(crate) manual_review: bool,
+	/// Peers allowed to open channels to us, any peer if empty
+	pub(crate) allowed_peers: Vec<PublicKey>,
+	/// Assets allowed in inbound channels, any asset if empty
+	pub(crate) allowed_contract_ids: Vec<ContractId>,
+	pub(crate) min_rgb_amount: Option<u64>,
+	pub(crate) max_rgb_amount: Option<u64>,
+	pub(crate) max_channels_per_peer: Option<usize>,
+}
+
+impl ChannelPolicy {
+	/// Checks a channel request from `peer`, which already has `num_peer_channels` channels
+	/// (including the pending ones) with us
+	pub(crate) fn check_peer(
+		&self, peer: &PublicKey, num_peer_channels: usize,
+	) -> Result<(), String> {
+		if !self.allowed_peers.is_empty() && !self.allowed_peers.contains(peer) {
+			return Err("peer is not in the allowlist".to_string());
+		}
+		if let Some(max) = self.max_channels_per_peer {
+			if num_peer_channels >= max {
+				return Err(format!(
+					"peer already has {} channels (max {})",
+					num_peer_channels, max
+				));
+			}
+		}
+		Ok(())
+	}
+
+	/// Checks the RGB asset and amount the peer is bringing into the channel
+	pub(crate) fn check_rgb(&self, contract_id: &ContractId, amount: u64) -> Result<(), String> {
+		if !self.allowed_contract_ids.is_empty() && !self.allowed_contract_ids.contains(contract_id)
+		{
+			return Err(format!("contract ID {} is not in the allowlist", contract_id));
+		}
+		if let Some(min) = self.min_rgb_amount {
+			if amount < min {
+				return Err(format!("RGB amount {} is lower than the minimum ({})", amount, min));
+			}
+		}
+		if let Some(max) = self.max_rgb_amount {
+			if amount > max {
+				return Err(format!("RGB amount {} is higher than the maximum ({})", amount, max));
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod policy_tests {
+	use super::*;
+	use std::str::FromStr;
+
+	const PEER: &str = "03ddf2eedb06d5bbd128ccd4f558cb4a7428bfbe359259c718db7d2a8eead169fb";
+	const OTHER_PEER: &str = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+	const CONTRACT_ID: &str = "rgb1lfxs4dmqs7a90vrz0yaje60fakuvu9u9esx882shy437yxazmysqamnv2r";
+	const OTHER_CONTRACT_ID: &str =
+		"rgb1qqqsyqcyq5rqwzqfpg9scrgwpugpzysnzs23v9ccrydpk8qarc0s5a5nga";
+
+	fn pubkey(s: &str) -> PublicKey {
+		PublicKey::from_str(s).unwrap()
+	}
+
+	fn contract_id(s: &str) -> ContractId {
+		ContractId::from_str(s).unwrap()
+	}
+
+	#[test]
+	fn test_default_policy_accepts_all() {
+		let policy = ChannelPolicy::default();
+		assert!(policy.check_peer(&pubkey(PEER), 100).is_ok());
+		assert!(policy.check_rgb(&contract_id(CONTRACT_ID), 0).is_ok());
+		assert!(policy.check_rgb(&contract_id(CONTRACT_ID), u64::MAX).is_ok());
+	}
+
+	#[test]
+	fn test_check_peer() {
+		let policy = ChannelPolicy {
+			allowed_peers: vec![pubkey(PEER)],
+			max_channels_per_peer: Some(2),
+			..Default::default()
+		};
+		assert!(policy.check_peer(&pubkey(PEER), 0).is_ok());
+		assert!(policy.check_peer(&pubkey(PEER), 1).is_ok());
+		assert!(policy.check_peer(&pubkey(PEER), 2).is_err());
+		assert!(policy.check_peer(&pubkey(OTHER_PEER), 0).is_err());
+	}
+
+	#[test]
+	fn test_check_rgb() {
+		let policy = ChannelPolicy {
+			allowed_contract_ids: vec![contract_id(CONTRACT_ID)],
+			min_rgb_amount: Some(10),
+			max_rgb_amount: Some(100),
+			..Default::default()
+		};
+		assert!(policy.check_rgb(&contract_id(CONTRACT_ID), 10).is_ok());
+		assert!(policy.check_rgb(&contract_id(CONTRACT_ID), 100).is_ok());
+		assert!(policy.check_rgb(&contract_id(CONTRACT_ID), 9).is_err());
+		assert!(policy.check_rgb(&contract_id(CONTRACT_ID), 101).is_err());
+		assert!(policy.check_rgb(&contract_id(OTHER_CONTRACT_ID), 50).is_err());
+	}
+}
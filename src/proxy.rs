@@ -2,7 +2,9 @@ use crate::error::Error;
 use amplify::s;
 use reqwest::header::CONTENT_TYPE;
 use reqwest::{multipart, Body, Client};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use tokio::fs::File;
 use tokio_util::codec::{BytesCodec, FramedRead};
 
@@ -13,21 +15,24 @@ const JSON: &str = "application/json";
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct JsonRpcError {
 	pub(crate) code: i64,
-	message: String,
+	pub(crate) message: String,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct JsonRpcRequest<P> {
-	method: String,
-	jsonrpc: String,
-	id: Option<String>,
-	params: Option<P>,
+	pub(crate) method: String,
+	pub(crate) jsonrpc: String,
+	pub(crate) id: Option<Value>,
+	pub(crate) params: Option<P>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct JsonRpcResponse<R> {
-	id: Option<String>,
+	pub(crate) jsonrpc: Option<String>,
+	pub(crate) id: Option<Value>,
+	#[serde(skip_serializing_if = "Option::is_none")]
 	pub(crate) result: Option<R>,
+	#[serde(skip_serializing_if = "Option::is_none")]
 	pub(crate) error: Option<JsonRpcError>,
 }
 
@@ -35,6 +40,13 @@ pub struct JsonRpcResponse<R> {
 pub struct BlindedUtxoParam {
 	blinded_utxo: String,
 }
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AckParam {
+	blinded_utxo: String,
+	ack: bool,
+}
+
 pub async fn post_consignment(
 	proxy_client: Client, url: &str, consignment_id: String, consignment_path: PathBuf,
 ) -> Result<JsonRpcResponse<bool>, Error> {
@@ -61,11 +73,35 @@ pub async fn post_consignment(
 pub async fn get_consignment(
 	proxy_client: Client, url: &str, consignment_id: String,
 ) -> Result<JsonRpcResponse<String>, reqwest::Error> {
+	let params = BlindedUtxoParam { blinded_utxo: consignment_id };
+	call(proxy_client, url, "consignment.get", params).await
+}
+
+/// Reports whether the consignment posted for `consignment_id` has been accepted
+pub async fn post_ack(
+	proxy_client: Client, url: &str, consignment_id: String, ack: bool,
+) -> Result<JsonRpcResponse<bool>, reqwest::Error> {
+	let params = AckParam { blinded_utxo: consignment_id, ack };
+	call(proxy_client, url, "ack.post", params).await
+}
+
+/// Whether the recipient accepted the consignment posted for `consignment_id`, with no result
+/// until it has validated it
+pub async fn get_ack(
+	proxy_client: Client, url: &str, consignment_id: String,
+) -> Result<JsonRpcResponse<bool>, reqwest::Error> {
+	let params = BlindedUtxoParam { blinded_utxo: consignment_id };
+	call(proxy_client, url, "ack.get", params).await
+}
+
+async fn call<P: Serialize, R: DeserializeOwned>(
+	proxy_client: Client, url: &str, method: &str, params: P,
+) -> Result<JsonRpcResponse<R>, reqwest::Error> {
 	let body = JsonRpcRequest {
-		method: s!("consignment.get"),
+		method: method.to_string(),
 		jsonrpc: s!("2.0"),
 		id: None,
-		params: Some(BlindedUtxoParam { blinded_utxo: consignment_id }),
+		params: Some(params),
 	};
 	proxy_client
 		.post(url)
@@ -73,6 +109,6 @@ pub async fn get_consignment(
 		.json(&body)
 		.send()
 		.await?
-		.json::<JsonRpcResponse<String>>()
+		.json::<JsonRpcResponse<R>>()
 		.await
 }
@@ -7,10 +7,11 @@ use bdk::database::SqliteDatabase;
 use bdk::Wallet;
 use bitcoin::consensus::{deserialize, serialize};
 use bitcoin::hashes::Hash;
+use bitcoin::network::constants::Network;
 use bitcoin::psbt::serialize::Deserialize as BitcoinDeserialize;
 use bitcoin::psbt::PartiallySignedTransaction;
 use bitcoin::BlockHash;
-use bp::seals::txout::CloseMethod;
+use bp::seals::txout::{blind::ConcealedSeal, CloseMethod};
 use internet2::addr::ServiceAddr;
 use lnpbp::chain::{Chain, GENESIS_HASH_REGTEST};
 use psbt::Psbt;
@@ -20,17 +21,32 @@ use rgb::psbt::RgbInExt;
 use rgb::IntoRevealedSeal;
 use rgb::Node;
 use rgb::{
-	AssignedState, Contract, ContractId, EndpointValueMap, InmemConsignment, TransferConsignment,
+	seal, AssignedState, Consignment, Contract, ContractId, EndpointValueMap, InmemConsignment,
+	StateTransfer, TransferConsignment,
 };
 use rgb20::{Asset as Rgb20Asset, Rgb20};
 use rgb_rpc::client::Client;
 use std::collections::{BTreeMap, BTreeSet};
 use std::convert::TryFrom;
+use std::path::Path;
 use std::str::FromStr;
 use stens::AsciiString;
 
-use crate::bdk_utils::sync_wallet;
+use crate::bdk_utils::{sync_wallet_and_utxos, ElectrumConfig};
 use crate::error::Error;
+use crate::utxo_store::RgbUtxoStore;
+
+/// The RGB chain matching the given bitcoin network
+pub(crate) fn get_rgb_network(network: Network) -> Chain {
+	match network {
+		Network::Bitcoin => Chain::Mainnet,
+		Network::Testnet => Chain::Testnet3,
+		Network::Signet => Chain::Signet,
+		Network::Regtest => {
+			Chain::Regtest(BlockHash::from_slice(GENESIS_HASH_REGTEST).expect("valid block hash"))
+		}
+	}
+}
 
 pub(crate) fn get_rgb_node_client(port: u16, rgb_network: Chain) -> Client {
 	let ip = Ipv4Addr::new(127, 0, 0, 1);
@@ -41,16 +57,23 @@ pub(crate) fn get_rgb_node_client(port: u16, rgb_network: Chain) -> Client {
 
 pub(crate) fn get_rgb_total_amount(
 	contract_id: ContractId, rgb_node_client: Arc<Mutex<Client>>,
-	wallet_arc: Arc<Mutex<Wallet<SqliteDatabase>>>, electrum_url: String,
+	wallet_arc: Arc<Mutex<Wallet<SqliteDatabase>>>, electrum_config: ElectrumConfig,
+	rgb_utxos: &RgbUtxoStore,
 ) -> Result<u64, Error> {
-	let asset_owned_values =
-		get_asset_owned_values(contract_id, rgb_node_client, wallet_arc, electrum_url)?;
+	let asset_owned_values = get_asset_owned_values(
+		contract_id,
+		rgb_node_client,
+		wallet_arc,
+		electrum_config,
+		rgb_utxos,
+	)?;
 	Ok(asset_owned_values.iter().map(|ov| ov.state.value).sum())
 }
 
 pub(crate) fn get_asset_owned_values(
 	contract_id: ContractId, rgb_node_client: Arc<Mutex<Client>>,
-	wallet_arc: Arc<Mutex<Wallet<SqliteDatabase>>>, electrum_url: String,
+	wallet_arc: Arc<Mutex<Wallet<SqliteDatabase>>>, electrum_config: ElectrumConfig,
+	rgb_utxos: &RgbUtxoStore,
 ) -> Result<Vec<AssignedState<rgb::value::Revealed>>, Error> {
 	let mut rgb_client = rgb_node_client.lock().unwrap();
 	let contract_state = match rgb_client.contract_state(contract_id) {
@@ -58,7 +81,7 @@ pub(crate) fn get_asset_owned_values(
 		Err(_e) => return Err(Error::UnknownContractId),
 	};
 	let wallet = wallet_arc.lock().unwrap();
-	sync_wallet(&wallet, electrum_url);
+	sync_wallet_and_utxos(&wallet, electrum_config, rgb_utxos);
 	let unspents_outpoints: Vec<OutPoint> =
 		wallet.list_unspent().expect("valid unspent list").iter().map(|u| u.outpoint).collect();
 	Ok(contract_state
@@ -70,10 +93,90 @@ pub(crate) fn get_asset_owned_values(
 		.collect())
 }
 
+/// Parses an amount expressed in the asset's units (e.g. `1.5`) into base units, failing if it
+/// has more decimal places than the asset's precision instead of rounding it
+pub(crate) fn parse_asset_amount(amount: &str, precision: u8) -> Result<u64, Error> {
+	let invalid = || Error::InvalidAmount(format!("{amount} is not a valid asset amount"));
+	let (int_part, frac_part) = match amount.split_once('.') {
+		Some((_, "")) => return Err(invalid()),
+		Some(parts) => parts,
+		None => (amount, ""),
+	};
+	let is_number = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+	if int_part.is_empty() || !is_number(int_part) || !is_number(frac_part) {
+		return Err(invalid());
+	}
+	let frac_part = frac_part.trim_end_matches('0');
+	if frac_part.len() > precision as usize {
+		return Err(Error::InvalidAmount(format!(
+			"{amount} has more than {precision} decimal places and would be rounded"
+		)));
+	}
+	format!("{int_part}{frac_part:0<width$}", width = precision as usize)
+		.parse()
+		.map_err(|_| Error::InvalidAmount(format!("{amount} is too big")))
+}
+
+/// Shows an amount of base units in the asset's units
+pub(crate) fn format_asset_amount(amount: u64, precision: u8) -> String {
+	if precision == 0 {
+		return amount.to_string();
+	}
+	let digits = format!("{amount:0>width$}", width = precision as usize + 1);
+	let (int_part, frac_part) = digits.split_at(digits.len() - precision as usize);
+	format!("{int_part}.{frac_part}")
+}
+
+/// Seals and amounts of the value assignments made by the latest transfer of the consignment
+fn get_latest_assignments(consignment: &StateTransfer) -> Vec<(ConcealedSeal, u64)> {
+	let bundle = match consignment.anchored_bundles().last() {
+		Some((_, bundle)) => bundle.clone(),
+		None => return vec![],
+	};
+	let mut assignments = vec![];
+	for (transition, _) in bundle.into_revealed_iter() {
+		for (_, typed_assignments) in transition.owned_rights().iter() {
+			for assignment in typed_assignments.to_value_assignments() {
+				let amount = assignment.as_revealed_state().map_or(0, |state| state.value);
+				assignments.push((assignment.to_confidential_seal(), amount));
+			}
+		}
+	}
+	assignments
+}
+
+/// Amount assigned to `seal` by the latest transfer of the consignment
+pub(crate) fn get_received_amount(consignment: &StateTransfer, seal: &seal::Revealed) -> u64 {
+	let concealed_seal = seal.to_concealed_seal();
+	get_latest_assignments(consignment)
+		.into_iter()
+		.filter(|(assigned_seal, _)| *assigned_seal == concealed_seal)
+		.map(|(_, amount)| amount)
+		.sum()
+}
+
+/// Output of the transfer transaction assigned to the witness receive with the given blinding,
+/// which can be found before the transaction is broadcast. Witness outputs come first in the
+/// transaction, so their vouts are lower than the number of assignments
+pub(crate) fn find_witness_vout(consignment: &StateTransfer, blinding: u64) -> Option<u32> {
+	let assignments = get_latest_assignments(consignment);
+	(0..assignments.len() as u32).find(|vout| {
+		let seal =
+			seal::Revealed { method: CloseMethod::OpretFirst, txid: None, vout: *vout, blinding };
+		let concealed_seal = seal.to_concealed_seal();
+		assignments.iter().any(|(assigned_seal, _)| *assigned_seal == concealed_seal)
+	})
+}
+
+/// Whether the channel has RGB info, i.e. it was opened to move an RGB asset
+pub(crate) fn is_channel_rgb(channel_id: &[u8; 32], ldk_data_dir: &Path) -> bool {
+	ldk_data_dir.join(hex::encode(channel_id)).exists()
+}
+
 pub(crate) trait RgbUtilities {
 	fn issue_contract(
-		&mut self, amount: u64, outpoint: OutPoint, ticker: AsciiString, name: AsciiString,
-		precision: u8,
+		&mut self, rgb_network: Chain, amount: u64, outpoint: OutPoint, ticker: AsciiString,
+		name: AsciiString, precision: u8,
 	) -> ContractId;
 
 	fn send_rgb_internal(
@@ -91,15 +194,12 @@ pub(crate) trait RgbUtilities {
 
 impl RgbUtilities for Client {
 	fn issue_contract(
-		&mut self, amount: u64, outpoint: OutPoint, ticker: AsciiString, name: AsciiString,
-		precision: u8,
+		&mut self, rgb_network: Chain, amount: u64, outpoint: OutPoint, ticker: AsciiString,
+		name: AsciiString, precision: u8,
 	) -> ContractId {
 		let allocations = vec![OutpointValue::from_str(&format!("{amount}@{outpoint}"))
 			.expect("allocation structure should be correct")];
 
-		let rgb_network =
-			Chain::Regtest(BlockHash::from_slice(GENESIS_HASH_REGTEST).expect("valid bloch hash"));
-
 		let asset = Contract::create_rgb20(
 			rgb_network,
 			ticker,
@@ -185,3 +285,51 @@ impl RgbUtilities for Client {
 		self.send_rgb_internal(contract_id, &mut psbt, input_outpoints_bt, beneficiaries, change)
 	}
 }
+
+#[cfg(test)]
+mod amount_tests {
+	use super::*;
+
+	#[test]
+	fn parse_amounts() {
+		assert_eq!(parse_asset_amount("100", 0).unwrap(), 100);
+		assert_eq!(parse_asset_amount("1", 2).unwrap(), 100);
+		assert_eq!(parse_asset_amount("1.5", 2).unwrap(), 150);
+		assert_eq!(parse_asset_amount("0.01", 2).unwrap(), 1);
+		assert_eq!(parse_asset_amount("1.500", 2).unwrap(), 150);
+		assert_eq!(parse_asset_amount("18446744073709551615", 0).unwrap(), u64::MAX);
+	}
+
+	#[test]
+	fn parse_amounts_failures() {
+		for amount in ["", ".5", "1.", "-1", "1,5", "1.5.0", "1e3", " 1"] {
+			assert!(
+				matches!(parse_asset_amount(amount, 2), Err(Error::InvalidAmount(_))),
+				"{}",
+				amount
+			);
+		}
+		// no silent truncation
+		assert!(matches!(parse_asset_amount("1.005", 2), Err(Error::InvalidAmount(_))));
+		assert!(matches!(parse_asset_amount("0.5", 0), Err(Error::InvalidAmount(_))));
+		assert!(matches!(
+			parse_asset_amount("18446744073709551616", 0),
+			Err(Error::InvalidAmount(_))
+		));
+		assert!(matches!(
+			parse_asset_amount("184467440737095517", 2),
+			Err(Error::InvalidAmount(_))
+		));
+	}
+
+	#[test]
+	fn format_amounts() {
+		assert_eq!(format_asset_amount(100, 0), "100");
+		assert_eq!(format_asset_amount(150, 2), "1.50");
+		assert_eq!(format_asset_amount(1, 2), "0.01");
+		assert_eq!(format_asset_amount(0, 3), "0.000");
+		for amount in [0, 1, 150, 123456789] {
+			assert_eq!(parse_asset_amount(&format_asset_amount(amount, 4), 4).unwrap(), amount);
+		}
+	}
+}
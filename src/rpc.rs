@@ -0,0 +1,325 @@
+use crate::api::{self, NodeContext, TransferEvent, TransferEventsResponse};
+use crate::error::Error;
+use crate::proxy::{JsonRpcError, JsonRpcRequest, JsonRpcResponse};
+use amplify::s;
+use hyper::header::CONTENT_TYPE;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::runtime::Handle;
+use tokio::sync::broadcast::{self, error::TryRecvError};
+
+const JSON: &str = "application/json";
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+/// Transfer events not fetched with `transferevents` yet
+type TransferEvents = Arc<Mutex<broadcast::Receiver<TransferEvent>>>;
+
+/// Serves the node commands as JSON-RPC 2.0 methods on `127.0.0.1:<port>`
+pub(crate) async fn start_rpc_server(
+	port: u16, ctx: Arc<NodeContext>, transfer_events: broadcast::Receiver<TransferEvent>,
+) {
+	let addr = SocketAddr::from(([127, 0, 0, 1], port));
+	let transfer_events = Arc::new(Mutex::new(transfer_events));
+	let make_service = make_service_fn(move |_| {
+		let ctx = Arc::clone(&ctx);
+		let transfer_events = Arc::clone(&transfer_events);
+		async move {
+			Ok::<_, Infallible>(service_fn(move |req| {
+				handle_request(req, Arc::clone(&ctx), Arc::clone(&transfer_events))
+			}))
+		}
+	});
+	let server = match Server::try_bind(&addr) {
+		Ok(builder) => builder.serve(make_service),
+		Err(e) => {
+			println!("ERROR: failed to bind RPC server to {}: {}", addr, e);
+			return;
+		}
+	};
+	if let Err(e) = server.await {
+		println!("ERROR: RPC server failed: {}", e);
+	}
+}
+
+async fn handle_request(
+	req: Request<Body>, ctx: Arc<NodeContext>, transfer_events: TransferEvents,
+) -> Result<Response<Body>, Infallible> {
+	if req.method() != Method::POST {
+		let mut response = Response::new(Body::empty());
+		*response.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
+		return Ok(response);
+	}
+
+	let body = match hyper::body::to_bytes(req.into_body()).await {
+		Ok(body) => body,
+		Err(e) => return Ok(json_response(None, Err(rpc_error(PARSE_ERROR, e.to_string())))),
+	};
+	let request = match parse_request(&body) {
+		Ok(request) => request,
+		Err(e) => return Ok(json_response(None, Err(e))),
+	};
+	let result = match request.method.as_str() {
+		"transferevents" => {
+			Ok(serde_json::to_value(take_transfer_events(&transfer_events)).expect("valid result"))
+		}
+		method => dispatch(&ctx, method, request.params).await,
+	};
+
+	// notifications (requests without an id) are run but never answered
+	if request.id.is_none() {
+		let mut response = Response::new(Body::empty());
+		*response.status_mut() = StatusCode::NO_CONTENT;
+		return Ok(response);
+	}
+	Ok(json_response(request.id, result))
+}
+
+/// Drains the transfer events received since the last call
+fn take_transfer_events(transfer_events: &TransferEvents) -> TransferEventsResponse {
+	let mut transfer_events = transfer_events.lock().unwrap();
+	let mut events = vec![];
+	loop {
+		match transfer_events.try_recv() {
+			Ok(event) => events.push(event),
+			// the oldest events have been dropped, the newer ones are still available
+			Err(TryRecvError::Lagged(_)) => continue,
+			Err(TryRecvError::Empty) | Err(TryRecvError::Closed) => break,
+		}
+	}
+	TransferEventsResponse { events }
+}
+
+fn parse_request(body: &[u8]) -> Result<JsonRpcRequest<Value>, JsonRpcError> {
+	let value =
+		serde_json::from_slice::<Value>(body).map_err(|e| rpc_error(PARSE_ERROR, e.to_string()))?;
+	serde_json::from_value::<JsonRpcRequest<Value>>(value)
+		.map_err(|e| rpc_error(INVALID_REQUEST, e.to_string()))
+}
+
+fn response_body(id: Option<Value>, result: Result<Value, JsonRpcError>) -> Vec<u8> {
+	let (result, error) = match result {
+		Ok(r) => (Some(r), None),
+		Err(e) => (None, Some(e)),
+	};
+	let response = JsonRpcResponse { jsonrpc: Some(s!("2.0")), id, result, error };
+	serde_json::to_vec(&response).expect("valid response")
+}
+
+fn json_response(id: Option<Value>, result: Result<Value, JsonRpcError>) -> Response<Body> {
+	let mut response = Response::new(Body::from(response_body(id, result)));
+	response.headers_mut().insert(CONTENT_TYPE, JSON.parse().expect("valid header"));
+	response
+}
+
+/// Runs an API call on the blocking thread pool, as most of them do synchronous I/O (wallet
+/// sync, RGB node and bitcoind calls) that would stall the runtime workers. Async calls are
+/// driven to completion there with the runtime handle
+async fn blocking<R, F>(ctx: &Arc<NodeContext>, f: F) -> Result<Value, JsonRpcError>
+where
+	R: Serialize + Send + 'static,
+	F: FnOnce(&NodeContext) -> Result<R, Error> + Send + 'static,
+{
+	let ctx = Arc::clone(ctx);
+	match tokio::task::spawn_blocking(move || f(&ctx)).await {
+		Ok(res) => to_result(res),
+		Err(e) => Err(rpc_error(SERVER_ERROR, e.to_string())),
+	}
+}
+
+async fn dispatch(
+	ctx: &Arc<NodeContext>, method: &str, params: Option<Value>,
+) -> Result<Value, JsonRpcError> {
+	match method {
+		"mine" => {
+			let params = parse_params(params)?;
+			blocking(ctx, move |ctx| Handle::current().block_on(api::mine(ctx, params))).await
+		}
+		"listunspent" => blocking(ctx, api::list_unspent).await,
+		"listrgbutxos" => blocking(ctx, api::list_rgb_utxos).await,
+		"checkutxos" => blocking(ctx, api::check_utxos).await,
+		"getaddress" => blocking(ctx, api::get_address).await,
+		"createutxos" => {
+			let params = parse_params(params)?;
+			blocking(ctx, move |ctx| api::create_utxos(ctx, params)).await
+		}
+		"issueasset" => {
+			let params = parse_params(params)?;
+			blocking(ctx, move |ctx| api::issue_asset(ctx, params)).await
+		}
+		"listassets" => blocking(ctx, api::list_assets).await,
+		"assetinfo" => {
+			let params = parse_params(params)?;
+			blocking(ctx, move |ctx| api::asset_info(ctx, params)).await
+		}
+		"assetbalance" => {
+			let params = parse_params(params)?;
+			blocking(ctx, move |ctx| api::asset_balance(ctx, params)).await
+		}
+		"sendasset" => {
+			let params = parse_params(params)?;
+			blocking(ctx, move |ctx| Handle::current().block_on(api::send_asset(ctx, params))).await
+		}
+		"receiveasset" => {
+			let params = parse_params(params)?;
+			blocking(ctx, move |ctx| api::receive_asset(ctx, params)).await
+		}
+		"listreceives" => blocking(ctx, api::list_receives).await,
+		"cancelreceive" => {
+			let params = parse_params(params)?;
+			blocking(ctx, move |ctx| Handle::current().block_on(api::cancel_receive(ctx, params)))
+				.await
+		}
+		"refresh" => blocking(ctx, |ctx| Handle::current().block_on(api::refresh(ctx))).await,
+		"openchannel" => {
+			let params = parse_params(params)?;
+			blocking(ctx, move |ctx| Handle::current().block_on(api::open_channel(ctx, params)))
+				.await
+		}
+		"sendpayment" => to_result(api::send_payment(ctx, parse_params(params)?)),
+		"keysend" => to_result(api::keysend(ctx, parse_params(params)?)),
+		"getinvoice" => to_result(api::get_invoice(ctx, parse_params(params)?)),
+		"connectpeer" => to_result(api::connect_peer(ctx, parse_params(params)?).await),
+		"disconnectpeer" => to_result(api::disconnect_peer(ctx, parse_params(params)?)),
+		"listchannels" => to_result(api::list_channels(ctx)),
+		"listpendingchannels" => to_result(api::list_pending_channels(ctx)),
+		"acceptchannel" => to_result(api::accept_channel(ctx, parse_params(params)?)),
+		"rejectchannel" => to_result(api::reject_channel(ctx, parse_params(params)?)),
+		"listpayments" => to_result(api::list_payments(ctx)),
+		"invoicestatus" => to_result(api::invoice_status(ctx, parse_params(params)?)),
+		"closechannel" => to_result(api::close_channel(ctx, parse_params(params)?)),
+		"forceclosechannel" => to_result(api::force_close_channel(ctx, parse_params(params)?)),
+		"nodeinfo" => to_result(api::node_info(ctx)),
+		"listpeers" => to_result(api::list_peers(ctx)),
+		"signmessage" => to_result(api::sign_message(ctx, parse_params(params)?)),
+		"sendonionmessage" => to_result(api::send_onion_message(ctx, parse_params(params)?)),
+		_ => Err(rpc_error(METHOD_NOT_FOUND, format!("unknown method: {}", method))),
+	}
+}
+
+fn parse_params<P: DeserializeOwned>(params: Option<Value>) -> Result<P, JsonRpcError> {
+	// methods with only optional parameters can be called without params
+	serde_json::from_value(params.unwrap_or_else(|| Value::Object(Default::default())))
+		.map_err(|e| rpc_error(INVALID_PARAMS, e.to_string()))
+}
+
+fn to_result<R: Serialize>(res: Result<R, Error>) -> Result<Value, JsonRpcError> {
+	match res {
+		Ok(r) => Ok(serde_json::to_value(r).expect("valid result")),
+		Err(e) => {
+			let message = e.to_string();
+			let message = message.strip_prefix("ERROR: ").unwrap_or(&message).to_string();
+			Err(rpc_error(SERVER_ERROR, message))
+		}
+	}
+}
+
+fn rpc_error(code: i64, message: String) -> JsonRpcError {
+	JsonRpcError { code, message }
+}
+
+#[cfg(test)]
+mod rpc_tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_request() {
+		let body = br#"{"jsonrpc": "2.0", "id": 1, "method": "listassets"}"#;
+		let request = parse_request(body).unwrap();
+		assert_eq!(request.method, "listassets");
+		assert_eq!(request.id, Some(Value::from(1)));
+		assert!(request.params.is_none());
+
+		let body = br#"{"jsonrpc": "2.0", "method": "refresh"}"#;
+		assert!(parse_request(body).unwrap().id.is_none());
+	}
+
+	#[test]
+	fn test_parse_request_errors() {
+		assert_eq!(parse_request(b"{not json").unwrap_err().code, PARSE_ERROR);
+		assert_eq!(
+			parse_request(br#"{"jsonrpc": "2.0", "id": 1}"#).unwrap_err().code,
+			INVALID_REQUEST
+		);
+		assert_eq!(parse_request(b"[1, 2]").unwrap_err().code, INVALID_REQUEST);
+	}
+
+	#[test]
+	fn test_parse_params() {
+		#[derive(serde::Deserialize)]
+		struct Params {
+			amount: u64,
+			fee_rate: Option<f32>,
+		}
+		let params: Params = parse_params(Some(serde_json::json!({"amount": 5}))).unwrap();
+		assert_eq!(params.amount, 5);
+		assert!(params.fee_rate.is_none());
+
+		let err = parse_params::<Params>(None).unwrap_err();
+		assert_eq!(err.code, INVALID_PARAMS);
+	}
+
+	#[test]
+	fn test_take_transfer_events() {
+		let (sender, receiver) = broadcast::channel(2);
+		let transfer_events = Arc::new(Mutex::new(receiver));
+		assert!(take_transfer_events(&transfer_events).events.is_empty());
+
+		for i in 0..3 {
+			let transfer = api::RejectedTransfer { recipient: i.to_string(), reason: s!("bad") };
+			sender.send(TransferEvent::Rejected(transfer)).unwrap();
+		}
+		// the first event has been dropped as the channel holds 2
+		let events = take_transfer_events(&transfer_events).events;
+		let recipients: Vec<String> = events
+			.into_iter()
+			.map(|e| match e {
+				TransferEvent::Rejected(transfer) => transfer.recipient,
+				TransferEvent::Received(transfer) => transfer.recipient,
+			})
+			.collect();
+		assert_eq!(recipients, vec![s!("1"), s!("2")]);
+		assert!(take_transfer_events(&transfer_events).events.is_empty());
+
+		let value = serde_json::to_value(TransferEvent::Rejected(api::RejectedTransfer {
+			recipient: s!("utxob1"),
+			reason: s!("bad"),
+		}))
+		.unwrap();
+		assert_eq!(
+			value,
+			serde_json::json!({"type": "rejected", "recipient": "utxob1", "reason": "bad"})
+		);
+	}
+
+	#[test]
+	fn test_response_shape() {
+		let body = response_body(Some(Value::from(1)), Ok(Value::from(true)));
+		let value: Value = serde_json::from_slice(&body).unwrap();
+		assert_eq!(value, serde_json::json!({"jsonrpc": "2.0", "id": 1, "result": true}));
+
+		let result = to_result::<bool>(Err(Error::InvalidContractId));
+		let body = response_body(Some(Value::from("a")), result);
+		let value: Value = serde_json::from_slice(&body).unwrap();
+		assert_eq!(value["id"], "a");
+		assert!(value.get("result").is_none());
+		assert_eq!(value["error"]["code"], SERVER_ERROR);
+		assert!(!value["error"]["message"].as_str().unwrap().starts_with("ERROR: "));
+
+		let body = response_body(None, Err(rpc_error(PARSE_ERROR, s!("bad"))));
+		let value: Value = serde_json::from_slice(&body).unwrap();
+		assert_eq!(value["id"], Value::Null);
+		assert_eq!(value["error"]["code"], PARSE_ERROR);
+	}
+}
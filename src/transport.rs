@@ -0,0 +1,295 @@
+use crate::error::Error;
+use crate::p2p::ConsignmentMessageHandler;
+use crate::proxy::{self, JsonRpcResponse};
+use crate::PeerManager;
+use amplify::s;
+use async_trait::async_trait;
+use bitcoin::secp256k1::PublicKey;
+use bitcoin::Txid;
+use reqwest::Client as RestClient;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// How consignments are exchanged with the other party of a transfer, chosen with the
+/// `consignment_transport` setting
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum TransportConfig {
+	/// The RGB proxy server
+	Proxy,
+	/// A directory shared with the other nodes
+	Dir(PathBuf),
+	/// Custom messages to the connected peers
+	Peer,
+}
+
+/// Moves consignments and their ACKs between sender and recipient. Both are stored under the
+/// recipient ID: the blinded UTXO or witness recipient the transfer is sent to
+#[async_trait]
+pub(crate) trait ConsignmentTransport: Send + Sync {
+	/// Sends the consignment saved at `consignment_path` to the recipient, whose node is
+	/// `recipient_node` when known
+	async fn post_consignment(
+		&self, recipient_id: &str, recipient_node: Option<PublicKey>, consignment_path: &Path,
+	) -> Result<(), Error>;
+
+	/// The consignment sent to the recipient, if it's available yet
+	async fn get_consignment(&self, recipient_id: &str) -> Result<Option<Vec<u8>>, Error>;
+
+	/// Drops a consignment the recipient rejected when other senders may still deliver the
+	/// expected one. Returns false when the sender has to get a NACK instead
+	async fn discard_consignment(&self, _recipient_id: &str) -> Result<bool, Error> {
+		Ok(false)
+	}
+
+	/// Tells the sender whether the recipient accepted the consignment
+	async fn post_ack(&self, recipient_id: &str, ack: bool) -> Result<(), Error>;
+
+	/// Whether the recipient accepted the consignment, with no result until it has validated it
+	async fn get_ack(&self, recipient_id: &str) -> Result<Option<bool>, Error>;
+
+	/// Sends the consignment of the channel transaction `txid` to the channel counterparty
+	async fn post_channel_consignment(
+		&self, counterparty: PublicKey, txid: Txid, consignment_path: &Path,
+	) -> Result<(), Error> {
+		self.post_consignment(&txid.to_string(), Some(counterparty), consignment_path).await
+	}
+
+	/// The consignment of the channel transaction `txid`, if the counterparty has sent it
+	async fn get_channel_consignment(&self, txid: Txid) -> Result<Option<Vec<u8>>, Error> {
+		self.get_consignment(&txid.to_string()).await
+	}
+
+	/// The endpoint channel counterparties fetch funding consignments from, if any
+	fn consignment_endpoint(&self) -> Option<String> {
+		None
+	}
+}
+
+/// The consignment endpoint of the RGB proxy server at `url`
+pub(crate) fn proxy_endpoint(url: &str) -> String {
+	format!("rgbhttpjsonrpc:{url}")
+}
+
+/// Exchanges consignments through the RGB proxy server's JSON-RPC API
+pub(crate) struct ProxyTransport {
+	pub(crate) client: Arc<RestClient>,
+	pub(crate) url: String,
+}
+
+#[async_trait]
+impl ConsignmentTransport for ProxyTransport {
+	async fn post_consignment(
+		&self, recipient_id: &str, _recipient_node: Option<PublicKey>, consignment_path: &Path,
+	) -> Result<(), Error> {
+		let res = proxy::post_consignment(
+			(*self.client).clone(),
+			&self.url,
+			recipient_id.to_string(),
+			consignment_path.to_path_buf(),
+		)
+		.await;
+		match res {
+			Ok(JsonRpcResponse { result: Some(_), .. }) => Ok(()),
+			_ => Err(Error::FailedPostConsignment),
+		}
+	}
+
+	async fn get_consignment(&self, recipient_id: &str) -> Result<Option<Vec<u8>>, Error> {
+		let res =
+			proxy::get_consignment((*self.client).clone(), &self.url, recipient_id.to_string())
+				.await?;
+		// a consignment that can't be decoded is rejected as invalid by the recipient
+		Ok(res.result.map(|consignment| base64::decode(consignment).unwrap_or_default()))
+	}
+
+	async fn post_ack(&self, recipient_id: &str, ack: bool) -> Result<(), Error> {
+		let res = proxy::post_ack((*self.client).clone(), &self.url, recipient_id.to_string(), ack)
+			.await?;
+		match res {
+			JsonRpcResponse { result: Some(_), .. } => Ok(()),
+			JsonRpcResponse { error: Some(e), .. } => Err(Error::FailedPostAck(e.message)),
+			_ => Err(Error::FailedPostAck(s!("empty response"))),
+		}
+	}
+
+	async fn get_ack(&self, recipient_id: &str) -> Result<Option<bool>, Error> {
+		let res =
+			proxy::get_ack((*self.client).clone(), &self.url, recipient_id.to_string()).await?;
+		Ok(res.result)
+	}
+
+	fn consignment_endpoint(&self) -> Option<String> {
+		Some(proxy_endpoint(&self.url))
+	}
+}
+
+/// Exchanges consignments through files in a directory, which can be shared by nodes on the
+/// same machine or carried to an air-gapped one
+pub(crate) struct DirTransport {
+	pub(crate) dir: PathBuf,
+}
+
+impl DirTransport {
+	// recipient IDs can contain characters that aren't valid in file names, like `:`
+	fn consignment_path(&self, recipient_id: &str) -> PathBuf {
+		self.dir.join(format!("{}.consignment", hex::encode(recipient_id)))
+	}
+
+	fn ack_path(&self, recipient_id: &str) -> PathBuf {
+		self.dir.join(format!("{}.ack", hex::encode(recipient_id)))
+	}
+}
+
+#[async_trait]
+impl ConsignmentTransport for DirTransport {
+	async fn post_consignment(
+		&self, recipient_id: &str, _recipient_node: Option<PublicKey>, consignment_path: &Path,
+	) -> Result<(), Error> {
+		// renamed once complete, so that the recipient never reads a partial file
+		let path = self.consignment_path(recipient_id);
+		let tmp_path = path.with_extension("tmp");
+		fs::copy(consignment_path, &tmp_path)?;
+		fs::rename(tmp_path, path)?;
+		Ok(())
+	}
+
+	async fn get_consignment(&self, recipient_id: &str) -> Result<Option<Vec<u8>>, Error> {
+		let path = self.consignment_path(recipient_id);
+		if !path.exists() {
+			return Ok(None);
+		}
+		Ok(Some(fs::read(path)?))
+	}
+
+	async fn post_ack(&self, recipient_id: &str, ack: bool) -> Result<(), Error> {
+		fs::write(self.ack_path(recipient_id), if ack { "ack" } else { "nack" })?;
+		Ok(())
+	}
+
+	async fn get_ack(&self, recipient_id: &str) -> Result<Option<bool>, Error> {
+		let path = self.ack_path(recipient_id);
+		if !path.exists() {
+			return Ok(None);
+		}
+		Ok(Some(fs::read_to_string(path)?.trim() == "ack"))
+	}
+}
+
+/// Exchanges consignments with the connected peers over the Lightning connection. The recipient
+/// asks all its peers for the consignment sent to it and the sender streams it in chunks to the
+/// recipient's node only, both need to stay connected until the transfer is acknowledged
+pub(crate) struct PeerTransport {
+	pub(crate) handler: Arc<ConsignmentMessageHandler>,
+	pub(crate) peer_manager: Arc<PeerManager>,
+}
+
+#[async_trait]
+impl ConsignmentTransport for PeerTransport {
+	async fn post_consignment(
+		&self, recipient_id: &str, recipient_node: Option<PublicKey>, consignment_path: &Path,
+	) -> Result<(), Error> {
+		let recipient_node = recipient_node.ok_or_else(|| {
+			Error::InvalidArgument(format!("the node ID of recipient {recipient_id} is required"))
+		})?;
+		let consignment = fs::read(consignment_path)?;
+		self.handler.offer(recipient_id.to_string(), recipient_node, consignment)
+	}
+
+	async fn get_consignment(&self, recipient_id: &str) -> Result<Option<Vec<u8>>, Error> {
+		if let Some(consignment) = self.handler.received(recipient_id) {
+			return Ok(Some(consignment));
+		}
+		// the answer is picked up by a later call
+		let peers: Vec<_> =
+			self.peer_manager.get_peer_node_ids().into_iter().map(|(pubkey, _)| pubkey).collect();
+		self.handler.request(recipient_id, &peers);
+		self.peer_manager.process_events();
+		Ok(None)
+	}
+
+	async fn discard_consignment(&self, recipient_id: &str) -> Result<bool, Error> {
+		// any peer can answer a request, only the sender's consignment gets the ACK
+		self.handler.discard(recipient_id)?;
+		Ok(true)
+	}
+
+	async fn post_ack(&self, recipient_id: &str, ack: bool) -> Result<(), Error> {
+		self.handler.send_ack(recipient_id, ack)?;
+		self.peer_manager.process_events();
+		Ok(())
+	}
+
+	async fn get_ack(&self, recipient_id: &str) -> Result<Option<bool>, Error> {
+		Ok(self.handler.ack(recipient_id))
+	}
+
+	async fn post_channel_consignment(
+		&self, counterparty: PublicKey, txid: Txid, consignment_path: &Path,
+	) -> Result<(), Error> {
+		let consignment = fs::read(consignment_path)?;
+		self.handler.push_channel_consignment(counterparty, txid, &consignment)?;
+		self.peer_manager.process_events();
+		Ok(())
+	}
+
+	async fn get_channel_consignment(&self, _txid: Txid) -> Result<Option<Vec<u8>>, Error> {
+		// pushed by the counterparty and saved as soon as it's complete
+		Ok(None)
+	}
+}
+
+#[cfg(test)]
+mod transport_tests {
+	use super::*;
+	use std::env;
+
+	const WITNESS_RECIPIENT: &str = "wvout:bcrt1qf4hpr8j4wzm0tjkv2f8v3xvah3swn9pjjw3jch:777";
+
+	fn dir_transport(name: &str) -> DirTransport {
+		let dir =
+			env::temp_dir().join(format!("rgb_transport_tests_{}_{name}", std::process::id()));
+		let _ = fs::remove_dir_all(&dir);
+		fs::create_dir_all(&dir).unwrap();
+		DirTransport { dir }
+	}
+
+	#[tokio::test]
+	async fn test_dir_transport_round_trip() {
+		let transport = dir_transport("round_trip");
+		let consignment_path = transport.dir.join("consignment");
+		fs::write(&consignment_path, b"consignment").unwrap();
+
+		assert_eq!(transport.get_consignment(WITNESS_RECIPIENT).await.unwrap(), None);
+		assert_eq!(transport.get_ack(WITNESS_RECIPIENT).await.unwrap(), None);
+
+		transport.post_consignment(WITNESS_RECIPIENT, None, &consignment_path).await.unwrap();
+		assert_eq!(
+			transport.get_consignment(WITNESS_RECIPIENT).await.unwrap(),
+			Some(b"consignment".to_vec())
+		);
+		assert!(!transport.discard_consignment(WITNESS_RECIPIENT).await.unwrap());
+
+		transport.post_ack(WITNESS_RECIPIENT, true).await.unwrap();
+		assert_eq!(transport.get_ack(WITNESS_RECIPIENT).await.unwrap(), Some(true));
+		transport.post_ack(WITNESS_RECIPIENT, false).await.unwrap();
+		assert_eq!(transport.get_ack(WITNESS_RECIPIENT).await.unwrap(), Some(false));
+
+		// no `:` in the file names
+		for entry in fs::read_dir(&transport.dir).unwrap() {
+			assert!(!entry.unwrap().file_name().to_string_lossy().contains(':'));
+		}
+		fs::remove_dir_all(&transport.dir).unwrap();
+	}
+
+	#[test]
+	fn test_consignment_endpoint() {
+		let transport = dir_transport("endpoint");
+		assert_eq!(transport.consignment_endpoint(), None);
+		assert_eq!(
+			proxy_endpoint("http://127.0.0.1:3000/json-rpc"),
+			"rgbhttpjsonrpc:http://127.0.0.1:3000/json-rpc"
+		);
+		fs::remove_dir_all(&transport.dir).unwrap();
+	}
+}
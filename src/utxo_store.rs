@@ -0,0 +1,529 @@
+use bdk::bitcoin::{OutPoint, Script, Transaction, Txid};
+use bdk::database::SqliteDatabase;
+use bdk::Wallet;
+use lightning::rgb_utils::RgbUtxos;
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use crate::bdk_utils::BDK_DB_NAME;
+use crate::error::Error;
+use RgbUtxoStatus::{Colored, Reserved, Spent, Uncolored};
+
+// file used to track RGB UTXOs before they were moved to the BDK db
+const RGB_UTXOS_FNAME: &str = "rgb_utxos";
+
+// time the Electrum server gets to index a new transaction before its untracked outputs are
+// considered evicted
+const EVICTION_GRACE_SECS: u64 = 3600;
+
+/// Lifecycle of a tracked UTXO: `Uncolored` -> `Reserved` -> `Colored` -> `Spent`, where a
+/// reserved UTXO goes back to `Uncolored` when it's released
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum RgbUtxoStatus {
+	Uncolored,
+	Reserved,
+	Colored,
+	Spent,
+}
+
+impl RgbUtxoStatus {
+	fn as_str(&self) -> &'static str {
+		match self {
+			RgbUtxoStatus::Uncolored => "uncolored",
+			RgbUtxoStatus::Reserved => "reserved",
+			RgbUtxoStatus::Colored => "colored",
+			RgbUtxoStatus::Spent => "spent",
+		}
+	}
+
+	fn from_db(status: &str) -> Self {
+		match status {
+			"uncolored" => RgbUtxoStatus::Uncolored,
+			"reserved" => RgbUtxoStatus::Reserved,
+			"colored" => RgbUtxoStatus::Colored,
+			"spent" => RgbUtxoStatus::Spent,
+			_ => panic!("invalid rgb utxo status {}", status),
+		}
+	}
+}
+
+impl fmt::Display for RgbUtxoStatus {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.as_str())
+	}
+}
+
+/// Changes made by [`RgbUtxoStore::reconcile`]
+#[derive(Default)]
+pub(crate) struct Reconciliation {
+	/// Entries no longer tracked: spent outputs and outputs of transactions the wallet dropped
+	pub(crate) removed: Vec<OutPoint>,
+	/// Colored UTXOs spent by transactions that didn't move their RGB allocations
+	pub(crate) lost: Vec<OutPoint>,
+}
+
+/// Keeps track of the wallet UTXOs set aside for RGB allocations, in a table of the BDK db.
+///
+/// An uncolored UTXO is first reserved by the operation that needs it, which then either
+/// commits it (the UTXO now holds RGB assets) or releases it back to the pool. Each change
+/// happens in its own db transaction, so concurrent operations never get the same UTXO.
+pub(crate) struct RgbUtxoStore {
+	conn: Mutex<Connection>,
+}
+
+impl RgbUtxoStore {
+	/// Opens the store, importing the UTXOs from the old `rgb_utxos` file if present
+	pub(crate) fn open(ldk_data_dir: &str) -> Self {
+		let conn =
+			Connection::open(format!("{ldk_data_dir}/{BDK_DB_NAME}")).expect("able to open bdk db");
+		conn.busy_timeout(Duration::from_secs(5)).expect("valid busy timeout");
+		conn.execute_batch(
+			"CREATE TABLE IF NOT EXISTS rgb_utxos (
+				txid TEXT NOT NULL,
+				vout INTEGER NOT NULL,
+				status TEXT NOT NULL,
+				created_at INTEGER NOT NULL DEFAULT 0,
+				PRIMARY KEY (txid, vout)
+			);
+			CREATE TABLE IF NOT EXISTS rgb_witness_scripts (
+				script BLOB PRIMARY KEY
+			);",
+		)
+		.expect("able to create rgb_utxos table");
+		// tables created before entries had a creation time
+		let has_created_at: bool = conn
+			.query_row(
+				"SELECT COUNT(*) > 0 FROM pragma_table_info('rgb_utxos') WHERE name = 'created_at'",
+				[],
+				|row| row.get(0),
+			)
+			.expect("able to query rgb_utxos columns");
+		if !has_created_at {
+			conn.execute(
+				"ALTER TABLE rgb_utxos ADD COLUMN created_at INTEGER NOT NULL DEFAULT 0",
+				[],
+			)
+			.expect("able to add created_at column");
+		}
+		let store = RgbUtxoStore { conn: Mutex::new(conn) };
+
+		let rgb_utxos_path = Path::new(ldk_data_dir).join(RGB_UTXOS_FNAME);
+		if rgb_utxos_path.exists() {
+			let serialized_utxos =
+				fs::read_to_string(&rgb_utxos_path).expect("able to read rgb utxos file");
+			let rgb_utxos: RgbUtxos =
+				serde_json::from_str(&serialized_utxos).expect("valid rgb utxos");
+			for utxo in rgb_utxos.utxos {
+				store.add(utxo.outpoint, utxo.colored);
+			}
+			fs::remove_file(rgb_utxos_path).expect("able to remove rgb utxos file");
+		}
+		store
+	}
+
+	/// Starts tracking `outpoint`, as colored or as available for RGB allocations
+	pub(crate) fn add(&self, outpoint: OutPoint, colored: bool) {
+		let status = if colored { Colored } else { Uncolored };
+		self.conn
+			.lock()
+			.unwrap()
+			.execute(
+				"INSERT OR IGNORE INTO rgb_utxos (txid, vout, status, created_at)
+				VALUES (?1, ?2, ?3, ?4)",
+				params![outpoint.txid.to_string(), outpoint.vout, status.as_str(), now_secs()],
+			)
+			.expect("able to add rgb utxo");
+	}
+
+	/// Takes an uncolored UTXO from the pool
+	pub(crate) fn reserve(&self) -> Result<OutPoint, Error> {
+		let mut conn = self.conn.lock().unwrap();
+		let tx = conn
+			.transaction_with_behavior(TransactionBehavior::Immediate)
+			.expect("able to start transaction");
+		let outpoint = tx
+			.query_row(
+				"SELECT txid, vout FROM rgb_utxos WHERE status = ?1 ORDER BY rowid LIMIT 1",
+				params![Uncolored.as_str()],
+				|row| Ok((row.get::<_, String>(0)?, row.get::<_, u32>(1)?)),
+			)
+			.optional()
+			.expect("able to query rgb utxos")
+			.map(|(txid, vout)| OutPoint { txid: Txid::from_str(&txid).expect("valid txid"), vout })
+			.ok_or(Error::NoAvailableUtxos)?;
+		set_status(&tx, outpoint, Reserved, &[Uncolored])?;
+		tx.commit().expect("able to commit transaction");
+		Ok(outpoint)
+	}
+
+	/// Marks a reserved UTXO as colored
+	pub(crate) fn commit(&self, outpoint: OutPoint) -> Result<(), Error> {
+		set_status(&self.conn.lock().unwrap(), outpoint, Colored, &[Reserved])
+	}
+
+	/// Returns a reserved UTXO to the pool
+	pub(crate) fn release(&self, outpoint: OutPoint) -> Result<(), Error> {
+		set_status(&self.conn.lock().unwrap(), outpoint, Uncolored, &[Reserved])
+	}
+
+	/// Marks the UTXO of a fulfilled receive request as colored, if it's still reserved
+	pub(crate) fn commit_receive(&self, outpoint: OutPoint) {
+		update_status(&self.conn.lock().unwrap(), outpoint, Colored, &[Reserved]);
+	}
+
+	/// Returns the UTXO of a dropped receive request to the pool. Requests made before they
+	/// kept their UTXO reserved have it colored, without allocations
+	pub(crate) fn release_receive(&self, outpoint: OutPoint) {
+		update_status(&self.conn.lock().unwrap(), outpoint, Uncolored, &[Reserved, Colored]);
+	}
+
+	/// Marks the given outpoints as spent, ignoring the ones that are not tracked
+	pub(crate) fn spend(&self, outpoints: &[OutPoint]) {
+		let conn = self.conn.lock().unwrap();
+		for outpoint in outpoints {
+			update_status(&conn, *outpoint, Spent, &[Colored, Reserved]);
+		}
+	}
+
+	/// Undoes the changes made for a transaction that will never be broadcast: its outputs are
+	/// no longer tracked and the colored UTXOs it spent are available again
+	pub(crate) fn discard_tx(&self, tx: &Transaction) {
+		let mut conn = self.conn.lock().unwrap();
+		let db_tx = conn.transaction().expect("able to start transaction");
+		db_tx
+			.execute("DELETE FROM rgb_utxos WHERE txid = ?1", params![tx.txid().to_string()])
+			.expect("able to remove rgb utxos");
+		for input in &tx.input {
+			update_status(&db_tx, input.previous_output, Colored, &[Spent]);
+		}
+		db_tx.commit().expect("able to commit transaction");
+	}
+
+	/// Number of uncolored UTXOs in the pool
+	pub(crate) fn num_uncolored(&self) -> usize {
+		self.conn
+			.lock()
+			.unwrap()
+			.query_row(
+				"SELECT COUNT(*) FROM rgb_utxos WHERE status = ?1",
+				params![Uncolored.as_str()],
+				|row| row.get(0),
+			)
+			.expect("able to count rgb utxos")
+	}
+
+	/// Brings the store in line with a freshly synced wallet. Reserved UTXOs are left alone, as
+	/// their operation is still in progress
+	pub(crate) fn reconcile(&self, wallet: &Wallet<SqliteDatabase>) -> Reconciliation {
+		self.reconcile_at(wallet, now_secs())
+	}
+
+	fn reconcile_at(&self, wallet: &Wallet<SqliteDatabase>, now: u64) -> Reconciliation {
+		let unspents: HashSet<OutPoint> =
+			wallet.list_unspent().expect("valid unspent list").iter().map(|u| u.outpoint).collect();
+		let mut reconciliation = Reconciliation::default();
+		let mut conn = self.conn.lock().unwrap();
+		let db_tx = conn.transaction().expect("able to start transaction");
+		for (outpoint, status, created_at) in list_with_creation(&db_tx) {
+			if status == Reserved || unspents.contains(&outpoint) {
+				continue;
+			}
+			if wallet.get_tx(&outpoint.txid, false).expect("valid wallet db").is_some() {
+				// the output has been spent, by a plain transaction if it was still colored
+				if status == Colored {
+					reconciliation.lost.push(outpoint);
+				}
+			} else if status != Uncolored {
+				// the transaction (e.g. a funding one) may not have been broadcast yet
+				continue;
+			} else if now < created_at + EVICTION_GRACE_SECS {
+				// the Electrum server may not have indexed the transaction yet
+				continue;
+			}
+			// otherwise the transaction creating the UTXO never confirmed and was evicted
+			db_tx
+				.execute(
+					"DELETE FROM rgb_utxos WHERE txid = ?1 AND vout = ?2",
+					params![outpoint.txid.to_string(), outpoint.vout],
+				)
+				.expect("able to remove rgb utxo");
+			reconciliation.removed.push(outpoint);
+		}
+		db_tx.commit().expect("able to commit transaction");
+		reconciliation
+	}
+
+	/// All the tracked UTXOs with their status
+	pub(crate) fn list(&self) -> Vec<(OutPoint, RgbUtxoStatus)> {
+		list(&self.conn.lock().unwrap())
+	}
+
+	/// All the tracked UTXOs
+	pub(crate) fn outpoints(&self) -> Vec<OutPoint> {
+		self.list().into_iter().map(|(outpoint, _)| outpoint).collect()
+	}
+
+	/// Protects the outputs paying `script_pubkey`, the address of a pending witness receive,
+	/// until they are tracked as colored
+	pub(crate) fn add_witness_script(&self, script_pubkey: &Script) {
+		self.conn
+			.lock()
+			.unwrap()
+			.execute(
+				"INSERT OR IGNORE INTO rgb_witness_scripts (script) VALUES (?1)",
+				params![script_pubkey.as_bytes()],
+			)
+			.expect("able to add witness script");
+	}
+
+	/// Stops protecting the outputs paying `script_pubkey`
+	pub(crate) fn remove_witness_script(&self, script_pubkey: &Script) {
+		self.conn
+			.lock()
+			.unwrap()
+			.execute(
+				"DELETE FROM rgb_witness_scripts WHERE script = ?1",
+				params![script_pubkey.as_bytes()],
+			)
+			.expect("able to remove witness script");
+	}
+
+	/// The wallet UTXOs that plain bitcoin transactions must not spend: the tracked ones and
+	/// the ones paying the address of a pending witness receive
+	pub(crate) fn unspendable(&self, wallet: &Wallet<SqliteDatabase>) -> Vec<OutPoint> {
+		let witness_scripts: HashSet<Vec<u8>> = {
+			let conn = self.conn.lock().unwrap();
+			let mut stmt =
+				conn.prepare("SELECT script FROM rgb_witness_scripts").expect("valid query");
+			let rows = stmt.query_map([], |row| row.get(0)).expect("able to query scripts");
+			rows.map(|r| r.expect("valid witness script")).collect()
+		};
+		let mut unspendable = self.outpoints();
+		for utxo in wallet.list_unspent().expect("valid unspent list") {
+			if witness_scripts.contains(utxo.txout.script_pubkey.as_bytes())
+				&& !unspendable.contains(&utxo.outpoint)
+			{
+				unspendable.push(utxo.outpoint);
+			}
+		}
+		unspendable
+	}
+}
+
+fn now_secs() -> u64 {
+	SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs()
+}
+
+fn list(conn: &Connection) -> Vec<(OutPoint, RgbUtxoStatus)> {
+	list_with_creation(conn).into_iter().map(|(outpoint, status, _)| (outpoint, status)).collect()
+}
+
+/// All the tracked UTXOs with their status and the time they started being tracked
+fn list_with_creation(conn: &Connection) -> Vec<(OutPoint, RgbUtxoStatus, u64)> {
+	let mut stmt = conn
+		.prepare("SELECT txid, vout, status, created_at FROM rgb_utxos ORDER BY rowid")
+		.expect("valid query");
+	let rows = stmt
+		.query_map([], |row| {
+			Ok((
+				row.get::<_, String>(0)?,
+				row.get::<_, u32>(1)?,
+				row.get::<_, String>(2)?,
+				row.get::<_, u64>(3)?,
+			))
+		})
+		.expect("able to query rgb utxos");
+	rows.map(|r| {
+		let (txid, vout, status, created_at) = r.expect("valid rgb utxo");
+		let outpoint = OutPoint { txid: Txid::from_str(&txid).expect("valid txid"), vout };
+		(outpoint, RgbUtxoStatus::from_db(&status), created_at)
+	})
+	.collect()
+}
+
+/// Moves `outpoint` to `status` if it's currently in one of the `from` states, returning whether
+/// it was updated
+fn update_status(
+	conn: &Connection, outpoint: OutPoint, status: RgbUtxoStatus, from: &[RgbUtxoStatus],
+) -> bool {
+	let mut updated = 0;
+	for from_status in from {
+		updated += conn
+			.execute(
+				"UPDATE rgb_utxos SET status = ?1 WHERE txid = ?2 AND vout = ?3 AND status = ?4",
+				params![
+					status.as_str(),
+					outpoint.txid.to_string(),
+					outpoint.vout,
+					from_status.as_str()
+				],
+			)
+			.expect("able to update rgb utxo");
+	}
+	updated > 0
+}
+
+/// Like [`update_status`], failing if `outpoint` is not in one of the `from` states
+fn set_status(
+	conn: &Connection, outpoint: OutPoint, status: RgbUtxoStatus, from: &[RgbUtxoStatus],
+) -> Result<(), Error> {
+	if !update_status(conn, outpoint, status, from) {
+		return Err(Error::InvalidUtxoStatus(outpoint.to_string(), status.to_string()));
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod utxo_store_tests {
+	use super::*;
+	use crate::bdk_utils::get_bdk_wallet_seckey;
+	use bdk::bitcoin::secp256k1::SecretKey;
+	use bdk::bitcoin::{Network, PackedLockTime, Script, Sequence, TxIn, TxOut, Witness};
+	use lightning::rgb_utils::RgbUtxo;
+	use std::env;
+	use std::process;
+
+	fn test_dir(name: &str) -> String {
+		let dir = env::temp_dir().join(format!("rgb_utxo_store_{}_{}", name, process::id()));
+		let _ = fs::remove_dir_all(&dir);
+		fs::create_dir_all(&dir).unwrap();
+		dir.to_string_lossy().into_owned()
+	}
+
+	fn outpoint(n: u8, vout: u32) -> OutPoint {
+		let txid = Txid::from_str(&format!("{:064x}", n)).unwrap();
+		OutPoint { txid, vout }
+	}
+
+	fn status(store: &RgbUtxoStore, outpoint: OutPoint) -> Option<RgbUtxoStatus> {
+		store.list().into_iter().find(|(o, _)| *o == outpoint).map(|(_, s)| s)
+	}
+
+	#[test]
+	fn test_reserve_commit_release() {
+		let dir = test_dir("lifecycle");
+		let store = RgbUtxoStore::open(&dir);
+		let (first, second) = (outpoint(1, 0), outpoint(2, 1));
+		store.add(first, false);
+		store.add(second, false);
+		store.add(first, true);
+		assert_eq!(store.num_uncolored(), 2);
+
+		assert_eq!(store.reserve().unwrap(), first);
+		assert_eq!(store.reserve().unwrap(), second);
+		assert!(matches!(store.reserve(), Err(Error::NoAvailableUtxos)));
+		assert_eq!(status(&store, first), Some(Reserved));
+
+		store.commit(first).unwrap();
+		assert_eq!(status(&store, first), Some(Colored));
+		assert!(matches!(store.commit(first), Err(Error::InvalidUtxoStatus(_, _))));
+		assert!(store.release(first).is_err());
+
+		store.release(second).unwrap();
+		assert_eq!(status(&store, second), Some(Uncolored));
+		assert!(store.release(second).is_err());
+		assert_eq!(store.num_uncolored(), 1);
+
+		store.spend(&[first, outpoint(3, 0)]);
+		assert_eq!(status(&store, first), Some(Spent));
+		assert_eq!(status(&store, outpoint(3, 0)), None);
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn test_receive_and_discard() {
+		let dir = test_dir("discard");
+		let store = RgbUtxoStore::open(&dir);
+		let (first, second) = (outpoint(1, 0), outpoint(2, 0));
+		store.add(first, true);
+		store.add(second, false);
+
+		let reserved = store.reserve().unwrap();
+		store.commit_receive(reserved);
+		assert_eq!(status(&store, reserved), Some(Colored));
+		store.release_receive(reserved);
+		assert_eq!(status(&store, reserved), Some(Uncolored));
+
+		let tx = Transaction {
+			version: 2,
+			lock_time: PackedLockTime(0),
+			input: vec![TxIn {
+				previous_output: first,
+				script_sig: Script::new(),
+				sequence: Sequence::MAX,
+				witness: Witness::new(),
+			}],
+			output: vec![TxOut { value: 1000, script_pubkey: Script::new() }],
+		};
+		let change = OutPoint { txid: tx.txid(), vout: 0 };
+		store.spend(&[first]);
+		store.add(change, true);
+		store.discard_tx(&tx);
+		assert_eq!(status(&store, first), Some(Colored));
+		assert_eq!(status(&store, change), None);
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn test_reconcile() {
+		let dir = test_dir("reconcile");
+		let store = RgbUtxoStore::open(&dir);
+		let wallet = get_bdk_wallet_seckey(
+			dir.clone(),
+			Network::Regtest,
+			SecretKey::from_slice(&[1; 32]).unwrap(),
+		);
+		let (uncolored, reserved, colored) = (outpoint(1, 0), outpoint(2, 0), outpoint(3, 0));
+		store.add(uncolored, false);
+		store.add(reserved, false);
+		store.add(colored, true);
+		store.reserve().unwrap();
+		store.reserve().unwrap();
+		store.release(uncolored).unwrap();
+
+		// none of the outputs is known to the (empty) wallet, but they were just created
+		let reconciliation = store.reconcile(&wallet);
+		assert!(reconciliation.removed.is_empty());
+		assert_eq!(status(&store, uncolored), Some(Uncolored));
+
+		let reconciliation = store.reconcile_at(&wallet, now_secs() + EVICTION_GRACE_SECS);
+		assert_eq!(reconciliation.removed, vec![uncolored]);
+		assert!(reconciliation.lost.is_empty());
+		assert_eq!(status(&store, uncolored), None);
+		assert_eq!(status(&store, reserved), Some(Reserved));
+		assert_eq!(status(&store, colored), Some(Colored));
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn test_migrate_rgb_utxos_file() {
+		let dir = test_dir("migrate");
+		let (uncolored, colored) = (outpoint(1, 0), outpoint(2, 3));
+		let rgb_utxos = RgbUtxos {
+			utxos: vec![
+				RgbUtxo { outpoint: uncolored, colored: false },
+				RgbUtxo { outpoint: colored, colored: true },
+			],
+		};
+		let rgb_utxos_path = Path::new(&dir).join(RGB_UTXOS_FNAME);
+		fs::write(&rgb_utxos_path, serde_json::to_string(&rgb_utxos).unwrap()).unwrap();
+
+		let store = RgbUtxoStore::open(&dir);
+		assert!(!rgb_utxos_path.exists());
+		assert_eq!(store.list(), vec![(uncolored, Uncolored), (colored, Colored)]);
+		drop(store);
+
+		// reopening keeps the imported UTXOs
+		let store = RgbUtxoStore::open(&dir);
+		assert_eq!(store.list().len(), 2);
+		fs::remove_dir_all(dir).unwrap();
+	}
+}
@@ -186,7 +186,7 @@ send_assets() {
     $TMUX_CMD send-keys -t node$num "sendasset $asset_id $rgb_amt $blinded_utxo" C-m
     timestamp
     check $num
-    _wait_for_text_multi $T_1 node$num "sendasset" "RGB send complete"
+    _wait_for_text_multi $T_2 node$num "sendasset" "RGB send complete"
     timestamp
     sleep 1
 }
@@ -68,11 +68,11 @@ _start_tmux() {
 
     echo "starting tmux"
     $TMUX_CMD -f tests/tmux.conf new-session -d -n node1 -s rgb-lightning-sample -x 200 -y 100
-    $TMUX_CMD send-keys 'target/debug/ldk-sample user:password@localhost:18443 dataldk0/ 63963 9735 regtest' C-m
+    $TMUX_CMD send-keys 'target/debug/ldk-sample --config=tests/config.toml --ldk-storage-dir-path=dataldk0/ --rgb-node-port=63963 --ldk-peer-listening-port=9735' C-m
     $TMUX_CMD new-window -n node2
-    $TMUX_CMD send-keys 'target/debug/ldk-sample user:password@localhost:18443 dataldk1/ 63964 9736 regtest' C-m
+    $TMUX_CMD send-keys 'target/debug/ldk-sample --config=tests/config.toml --ldk-storage-dir-path=dataldk1/ --rgb-node-port=63964 --ldk-peer-listening-port=9736' C-m
     $TMUX_CMD new-window -n node3
-    $TMUX_CMD send-keys 'target/debug/ldk-sample user:password@localhost:18443 dataldk2/ 63965 9737 regtest' C-m
+    $TMUX_CMD send-keys 'target/debug/ldk-sample --config=tests/config.toml --ldk-storage-dir-path=dataldk2/ --rgb-node-port=63965 --ldk-peer-listening-port=9737' C-m
     sleep 1
 
     echo